@@ -1,17 +1,31 @@
 mod actions;
 mod animation;
 mod ants;
-mod brains;
+mod behaviours;
+mod calendar;
 mod camera;
+mod chunks;
+mod climate;
 mod debug;
+mod dig;
 mod eggs;
+mod flow_field;
 mod food;
 mod food_types;
 mod hunger;
+mod jobs;
+mod level;
 mod map;
+mod map_generator;
+mod mind;
 mod mouse;
+mod movement;
 mod new_brain;
+mod occupancy;
+mod path_hierarchy;
+mod path_validation;
 mod pathfinding;
+mod pheromones;
 mod plugin;
 mod positions;
 mod queen;
@@ -19,8 +33,12 @@ mod setup;
 mod side_effects;
 mod simple_brain;
 mod skill;
+mod stability;
+mod steering;
+mod tilemap;
 mod time;
 mod ui;
+mod water;
 mod zones;
 
 pub use plugin::GamePlugin;
@@ -1,3 +1,7 @@
+use crate::game::chunks::{LoadChunkEvent, SideMapChunks};
+use crate::game::dig::{cancel_designation, designate_cell};
+use crate::game::jobs::JobBoard;
+use crate::game::map::{CellContent, SideMapPosToEntities};
 use crate::game::mouse::MouseWorldPosition;
 use crate::game::pathfinding::Path;
 use crate::game::plugin::{ActionMode, PlayerState};
@@ -11,17 +15,43 @@ pub fn primary_mouse_click(
     mouse_world_position: Res<MouseWorldPosition>,
     input_state: Res<InputStates>,
     player_state: Res<PlayerState>,
+    mut job_board: ResMut<JobBoard>,
+    chunks: Res<SideMapChunks>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    cells: Query<&CellContent>,
+    mut load_chunk_writer: EventWriter<LoadChunkEvent>,
 ) {
-    if !input_state.just_pressed(InputAction::PrimaryAction) {
-        return;
-    }
+    match &player_state.action_mode {
+        // Dragging with the primary button designates every cell under the mouse, and the secondary
+        // button cancels them.
+        ActionMode::Dig => {
+            let pos = mouse_world_position.to_cell();
+            if input_state.is_pressed(InputAction::PrimaryAction) {
+                designate_cell(
+                    &mut job_board,
+                    &chunks,
+                    &side_map_pos_to_entities,
+                    &cells,
+                    &mut load_chunk_writer,
+                    pos,
+                );
+            } else if input_state.is_pressed(InputAction::SecondaryAction)
+                && cancel_designation(&mut job_board, pos)
+            {
+                info!(?pos, "Cancelled dig designation");
+            }
+        }
+        _ => {
+            if !input_state.just_pressed(InputAction::PrimaryAction) {
+                return;
+            }
 
-    info!("Left mouse click: {:?}", mouse_world_position);
+            info!("Left mouse click: {:?}", mouse_world_position);
 
-    match &player_state.action_mode {
-        _ => warn!(
-            "TODO left_mouse_click: action_mode: {:?}",
-            player_state.action_mode
-        ),
+            warn!(
+                "TODO left_mouse_click: action_mode: {:?}",
+                player_state.action_mode
+            );
+        }
     }
 }
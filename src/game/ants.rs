@@ -1,10 +1,12 @@
 use crate::game::animation::{AnimationIndices, AnimationTimer};
+use crate::game::behaviours::Behaviours;
 use crate::game::eggs::SpawnAntEvent;
 use crate::game::food::AssignedFoodId;
 use crate::game::hunger::Hunger;
-use crate::game::map::SIDE_CELL_SIZE;
-use crate::game::new_brain::new_eat_food_steps;
+use crate::game::map::{SoilLoad, SIDE_CELL_SIZE};
+use crate::game::mind::Mind;
 use crate::game::pathfinding::Path;
+use crate::game::pheromones::Scent;
 use crate::game::plugin::{Crawler, Speed, ANT_Z};
 use crate::game::positions::SideIPos;
 use crate::game::queen::EggLaidEvent;
@@ -12,12 +14,15 @@ use crate::game::setup::{sprite, texture_atlas_sprite};
 use crate::game::side_effects::{
     AppliedFoodSideEffect, AppliedFoodSideEffects, CalculatedSideEffects,
 };
-use crate::game::simple_brain::Idea;
+use crate::game::simple_brain::{Idea, Sequence};
+use crate::game::steering::{Heading, LateralOffset};
 use bevy::prelude::*;
 use bevy::render::render_graph::NodeLabel::Name;
-use big_brain::prelude::*;
+use serde::Deserialize;
 
-#[derive(Component, Debug, Eq, PartialEq, Default, Copy, Clone)]
+#[derive(
+    Component, Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Default, Copy, Clone, Deserialize,
+)]
 pub enum AntType {
     #[default]
     Scout,
@@ -67,6 +72,7 @@ struct AntSpawnData {
 pub fn spawn_ants(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    behaviours: Res<Behaviours>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut spawn_ant_reader: EventReader<SpawnAntEvent>,
 ) {
@@ -92,29 +98,7 @@ pub fn spawn_ants(
             ..Default::default()
         };
 
-        // let thinker = match ant_type {
-        //     AntType::Scout => Thinker::build()
-        //         .label("ScoutThinker")
-        //         .picker(FirstToScore { threshold: 0.5 })
-        //         .when(HungryScorer, eat_food())
-        //         .otherwise(discover_food_and_offer_to_the_queen_steps()),
-        //
-        //     AntType::Cargo => Thinker::build()
-        //         .label("CargoThinker")
-        //         .picker(FirstToScore { threshold: 0.5 })
-        //         .when(HungryScorer, eat_food())
-        //         .otherwise(gather_food_from_outside_steps()),
-        //
-        //     AntType::Nurse => Thinker::build()
-        //         .label("NurseThinker")
-        //         .picker(FirstToScore { threshold: 0.5 })
-        //         .when(HungryScorer, eat_food())
-        //         .otherwise(feed_queen_steps()),
-        //
-        //     _ => todo!(),
-        // };
-
-        let thinker = Idea::from(new_eat_food_steps());
+        let thinker = Idea::from(Sequence::new());
 
         let name: bevy::core::Name = format!("Ant{:?}", ant_type).into();
 
@@ -125,11 +109,17 @@ pub fn spawn_ants(
             AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
             Crawler,
             *ant_type,
-            thinker,
-            Speed::default(),
+            (thinker, Mind::for_ant(&behaviours, *ant_type)),
+            (
+                Speed::default(),
+                Heading::default(),
+                LateralOffset::random(),
+            ),
             Hunger::default(),
             AssignedFoodId::default(),
+            SoilLoad::default(),
             Path::None,
+            Scent::default(),
             AppliedFoodSideEffects::new(),
             CalculatedSideEffects::new(),
         ));
@@ -0,0 +1,426 @@
+//! What ants do, loaded from `assets/behaviours.yaml`.
+//!
+//! `sequences` names lists of steps. A sequence can name a `fallback` sequence to run instead when
+//! one of its steps fails, e.g. because there was no food to fetch. `castes` picks the sequence
+//! each [AntType] runs for each [Scorer]. The steps are:
+//!
+//! ```text
+//! path_to: <StoredFood|Job|Outside|Queen|DiscoveredFood|Storage|Safety|Nursery>
+//! follow_path          Walk the path set by the last path_to.
+//! wait: <5s|500ms>
+//! eat: <amount>
+//! dig
+//! leave_map
+//! enter_map
+//! discover_food        Look for food while off the map.
+//! gather_food          Fetch food from the discovered food while off the map.
+//! offer_food_to_queen
+//! place_food
+//! pick_up_food: <amount>
+//! feed_queen
+//! pick_up_egg          Pick up a loose egg in the ant's cell.
+//! put_down_egg         Put down the carried egg, in the nursery if the ant is in it.
+//! feed_brood           Feed the carried food to the hungriest egg in the ant's cell.
+//! check: <StoredFood|DiscoveredFood|FoodStorage|Hungry>   Fail unless this holds.
+//! ```
+//!
+//! Each kind of job on the [crate::game::jobs::JobBoard] needs a sequence, named after the kind.
+
+use crate::game::ants::AntType;
+use crate::game::food::{FoodState, DEFAULT_CARGO_CAPACITY};
+use crate::game::hunger::Hunger;
+use crate::game::jobs::JobKind;
+use crate::game::mind::{Choice, Scorer};
+use crate::game::new_brain::{Action, Condition, TransitionDirection};
+use crate::game::simple_brain::Sequence;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use serde::Deserialize;
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BehavioursFile {
+    sequences: BTreeMap<String, SequenceFile>,
+    castes: BTreeMap<AntType, BTreeMap<Scorer, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SequenceFile {
+    steps: Vec<Value>,
+    #[serde(default)]
+    fallback: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Step {
+    PathTo(Destination),
+    FollowPath,
+    Wait(String),
+    Eat(f32),
+    Dig,
+    LeaveMap,
+    EnterMap,
+    DiscoverFood,
+    GatherFood,
+    OfferFoodToQueen,
+    PlaceFood,
+    PickUpFood(f32),
+    FeedQueen,
+    PickUpEgg,
+    PutDownEgg,
+    FeedBrood,
+    Check(Condition),
+}
+
+#[derive(Debug, Deserialize)]
+enum Destination {
+    StoredFood,
+    Job,
+    Outside,
+    Queen,
+    DiscoveredFood,
+    Storage,
+    Safety,
+    Nursery,
+}
+
+#[derive(Resource, Debug)]
+pub struct Behaviours {
+    sequences: HashMap<String, Sequence>,
+    castes: HashMap<AntType, Vec<(Scorer, String)>>,
+}
+
+impl Behaviours {
+    /// `name` is only used for error messages.
+    pub fn load_str(name: &str, source: &str) -> Result<Self> {
+        let file: BehavioursFile =
+            serde_yaml::from_str(source).map_err(|err| match err.location() {
+                Some(location) => eyre!("{name}:{}:{}: {err}", location.line(), location.column()),
+                None => eyre!("{name}: {err}"),
+            })?;
+
+        let mut steps = HashMap::default();
+        for (sequence, sequence_file) in &file.sequences {
+            let actions = parse_steps(&sequence_file.steps)
+                .map_err(|err| eyre!("{name}: sequence {sequence}, {err}"))?;
+            steps.insert(sequence.clone(), actions);
+        }
+
+        let mut sequences = HashMap::default();
+        for sequence in file.sequences.keys() {
+            let resolved = resolve(sequence, &file.sequences, &steps, &mut Vec::new())
+                .map_err(|err| eyre!("{name}: sequence {sequence}: {err}"))?;
+            sequences.insert(sequence.clone(), resolved);
+        }
+
+        for kind in JobKind::ALL {
+            if !sequences.contains_key(kind.sequence()) {
+                return Err(eyre!("{name}: there's no {} sequence", kind.sequence()));
+            }
+        }
+
+        let mut castes = HashMap::default();
+        for (ant_type, choices) in file.castes {
+            for (scorer, sequence) in &choices {
+                if !sequences.contains_key(sequence) {
+                    return Err(eyre!(
+                        "{name}: caste {ant_type:?}, {scorer:?}: unknown sequence {sequence}"
+                    ));
+                }
+            }
+            castes.insert(ant_type, choices.into_iter().collect());
+        }
+
+        Ok(Self { sequences, castes })
+    }
+
+    /// Panics if there's no such sequence, which can't happen for the names that were checked
+    /// when loading.
+    pub fn sequence(&self, name: &str) -> Sequence {
+        self.sequences
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown sequence {name}"))
+            .clone()
+    }
+
+    /// Whether the checks the sequence starts with hold, so it won't fail straight away.
+    pub fn can_start(&self, name: &str, food_state: &FoodState, hunger: &Hunger) -> bool {
+        let Some(sequence) = self.sequences.get(name) else {
+            return false;
+        };
+
+        sequence
+            .iter()
+            .map_while(|action| match action {
+                Action::CheckAction(condition) => Some(condition),
+                _ => None,
+            })
+            .all(|condition| condition.holds(food_state, hunger))
+    }
+
+    /// What an ant of this type could do.
+    pub fn choices(&self, ant_type: AntType) -> Vec<Choice> {
+        let Some(choices) = self.castes.get(&ant_type) else {
+            warn!(?ant_type, "No behaviours for ant type");
+            return Vec::new();
+        };
+
+        choices
+            .iter()
+            .map(|(scorer, sequence)| Choice::new(*scorer, sequence.clone()))
+            .collect()
+    }
+}
+
+fn parse_steps(values: &[Value]) -> Result<Vec<Action>> {
+    if values.is_empty() {
+        return Err(eyre!("no steps"));
+    }
+
+    let mut actions = Vec::new();
+    let mut has_path = false;
+    for (index, value) in values.iter().enumerate() {
+        let step: Step = serde_yaml::from_value(as_tagged(value))
+            .map_err(|err| eyre!("step {}: {err}", index + 1))?;
+        let action =
+            step_action(step, has_path).map_err(|err| eyre!("step {}: {err}", index + 1))?;
+        has_path |= is_set_path(&action);
+        actions.push(action);
+    }
+    Ok(actions)
+}
+
+/// serde_yaml only reads `path_to: Queen` as an enum when parsing text, so turn a single entry map
+/// into the `!path_to Queen` form it reads from a [Value].
+fn as_tagged(value: &Value) -> Value {
+    let Value::Mapping(map) = value else {
+        return value.clone();
+    };
+
+    match map.iter().next() {
+        Some((Value::String(tag), inner)) if map.len() == 1 => {
+            Value::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(tag),
+                value: inner.clone(),
+            }))
+        }
+        _ => value.clone(),
+    }
+}
+
+fn is_set_path(action: &Action) -> bool {
+    matches!(
+        action,
+        Action::SetPathToStoredFoodAction
+            | Action::SetPathToJobAction
+            | Action::SetPathToRandomOutsideAction
+            | Action::SetPathToQueenAction
+            | Action::SetPathToDiscoveredFoodAction
+            | Action::SetPathToFoodStorageAction
+            | Action::SetPathAwayFromDangerAction
+            | Action::SetPathToNurseryAction
+    )
+}
+
+fn step_action(step: Step, has_path: bool) -> Result<Action> {
+    let positive = |amount: f32| {
+        if amount > 0.0 {
+            Ok(amount)
+        } else {
+            Err(eyre!("amount has to be more than 0, not {amount}"))
+        }
+    };
+
+    Ok(match step {
+        Step::PathTo(destination) => match destination {
+            Destination::StoredFood => Action::SetPathToStoredFoodAction,
+            Destination::Job => Action::SetPathToJobAction,
+            Destination::Outside => Action::SetPathToRandomOutsideAction,
+            Destination::Queen => Action::SetPathToQueenAction,
+            Destination::DiscoveredFood => Action::SetPathToDiscoveredFoodAction,
+            Destination::Storage => Action::SetPathToFoodStorageAction,
+            Destination::Safety => Action::SetPathAwayFromDangerAction,
+            Destination::Nursery => Action::SetPathToNurseryAction,
+        },
+        Step::FollowPath if !has_path => return Err(eyre!("follow_path before any path_to")),
+        Step::FollowPath => Action::PathfindingAction,
+        Step::Wait(duration) => Action::WaitAction(parse_duration(&duration)?),
+        Step::Eat(amount) => Action::EatAction(positive(amount)?),
+        Step::Dig => Action::DigAction,
+        Step::LeaveMap => Action::MapTransitionAction(TransitionDirection::Exit),
+        Step::EnterMap => Action::MapTransitionAction(TransitionDirection::Enter),
+        Step::DiscoverFood => Action::OutsideMapDiscoveringNewFoodAction,
+        Step::GatherFood => Action::OutsideMapGatheringExistingFoodAction,
+        Step::OfferFoodToQueen => Action::OfferFoodDiscoveryToQueenAction,
+        Step::PlaceFood => Action::PlaceFoodIfPossibleAction,
+        Step::PickUpFood(amount) if amount > DEFAULT_CARGO_CAPACITY => {
+            return Err(eyre!(
+                "can't carry {amount}, at most {DEFAULT_CARGO_CAPACITY}"
+            ))
+        }
+        Step::PickUpFood(amount) => Action::PickUpFoodAction(positive(amount)?),
+        Step::FeedQueen => Action::FeedQueenAction,
+        Step::PickUpEgg => Action::PickUpEggAction,
+        Step::PutDownEgg => Action::PutDownEggAction,
+        Step::FeedBrood => Action::FeedBroodAction,
+        Step::Check(condition) => Action::CheckAction(condition),
+    })
+}
+
+/// `5s` or `500ms`.
+fn parse_duration(duration: &str) -> Result<Duration> {
+    let (number, scale) = if let Some(number) = duration.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = duration.strip_suffix('s') {
+        (number, 1.0)
+    } else {
+        return Err(eyre!("wait {duration:?} needs to end in s or ms"));
+    };
+
+    match number.trim().parse::<f32>() {
+        Ok(number) if number >= 0.0 => Ok(Duration::from_secs_f32(number * scale)),
+        _ => Err(eyre!("wait {duration:?} isn't a duration")),
+    }
+}
+
+/// A sequence with its chain of fallbacks, failing on unknown or cyclic fallbacks.
+fn resolve(
+    name: &str,
+    files: &BTreeMap<String, SequenceFile>,
+    steps: &HashMap<String, Vec<Action>>,
+    seen: &mut Vec<String>,
+) -> Result<Sequence> {
+    if seen.iter().any(|seen| seen == name) {
+        return Err(eyre!("fallbacks loop: {} -> {name}", seen.join(" -> ")));
+    }
+    seen.push(name.to_string());
+
+    let Some(file) = files.get(name) else {
+        return Err(eyre!("unknown fallback {name}"));
+    };
+
+    let mut sequence = Sequence::new();
+    sequence.extend(steps[name].iter().copied());
+    match &file.fallback {
+        Some(fallback) => Ok(sequence.with_fallback(resolve(fallback, files, steps, seen)?)),
+        None => Ok(sequence),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEHAVIOURS: &str = "\
+sequences:
+  Dig:
+    steps:
+      - path_to: Job
+      - follow_path
+      - dig
+    fallback: Rest
+  HaulFood:
+    steps:
+      - pick_up_food: 5
+  FeedQueen:
+    steps:
+      - feed_queen
+  CarryEgg:
+    steps:
+      - pick_up_egg
+      - path_to: Nursery
+      - follow_path
+      - put_down_egg
+  FeedBrood:
+    steps:
+      - feed_brood
+  Rest:
+    steps:
+      - wait: 500ms
+castes:
+  Soldier:
+    Work: Dig
+    Rest: Rest
+";
+
+    #[test]
+    fn behaviours_asset_loads() {
+        let source = include_str!("../../assets/behaviours.yaml");
+        let behaviours = Behaviours::load_str("behaviours.yaml", source).unwrap();
+        assert_eq!(behaviours.choices(AntType::Cargo).len(), 4);
+        assert_eq!(behaviours.choices(AntType::Soldier).len(), 3);
+    }
+
+    #[test]
+    fn sequences_start_only_when_their_checks_hold() {
+        let source = include_str!("../../assets/behaviours.yaml");
+        let behaviours = Behaviours::load_str("behaviours.yaml", source).unwrap();
+        let food_state = FoodState::default();
+        let hunger = Hunger::default();
+        assert!(!behaviours.can_start("GatherFood", &food_state, &hunger));
+        assert!(behaviours.can_start("DiscoverFood", &food_state, &hunger));
+    }
+
+    #[test]
+    fn sequences_have_steps_and_fallbacks() {
+        let behaviours = Behaviours::load_str("test", BEHAVIOURS).unwrap();
+        let dig = behaviours.sequence("Dig");
+        assert_eq!(dig.len(), 3);
+        assert!(matches!(dig[2], Action::DigAction));
+
+        let rest = behaviours.sequence("Rest");
+        assert!(
+            matches!(rest[0], Action::WaitAction(duration) if duration == Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn bad_steps_name_their_sequence_and_step() {
+        let source = BEHAVIOURS.replace("- dig", "- dance");
+        let err = Behaviours::load_str("test", &source).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("test: sequence Dig, step 3: unknown variant `dance`"),
+            "{err}"
+        );
+
+        let source = BEHAVIOURS.replace("wait: 500ms", "wait: soon");
+        let err = Behaviours::load_str("test", &source).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("test: sequence Rest, step 1: wait"),
+            "{err}"
+        );
+
+        let source = BEHAVIOURS.replace("      - path_to: Job\n", "");
+        let err = Behaviours::load_str("test", &source).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("test: sequence Dig, step 1: follow_path"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn fallbacks_must_exist_and_not_loop() {
+        let source = BEHAVIOURS.replace("fallback: Rest", "fallback: Nap");
+        let err = Behaviours::load_str("test", &source).unwrap_err();
+        assert!(err.to_string().contains("unknown fallback Nap"), "{err}");
+
+        let source = BEHAVIOURS.replace("wait: 500ms\n", "wait: 500ms\n    fallback: Dig\n");
+        let err = Behaviours::load_str("test", &source).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("fallbacks loop: Dig -> Rest -> Dig"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! An in-game clock with days, hours and seasons, counted in game time so it stops while paused and
+//! speeds up with the time scale.
+//!
+//! Set `QUEEN_DAY_LENGTH` to the number of seconds a whole day and night should take.
+
+use crate::game::time::GameTime;
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::prelude::*;
+use std::f32::consts::PI;
+use std::fmt;
+use std::time::Duration;
+
+const DAY_LENGTH_ENV: &str = "QUEEN_DAY_LENGTH";
+
+pub const HOURS_PER_DAY: f32 = 24.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    fn from_index(index: u32) -> Self {
+        match index % 4 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct Calendar {
+    /// Game time for a whole day and night.
+    pub day_length: Duration,
+
+    pub days_per_season: u32,
+
+    /// The game starts at this hour on the first day of spring, so it doesn't start in the dark.
+    pub start_hour: f32,
+
+    pub sunrise_hour: f32,
+    pub sunset_hour: f32,
+
+    elapsed: Duration,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Self {
+            day_length: Duration::from_secs(240),
+            days_per_season: 7,
+            start_hour: 8.0,
+            sunrise_hour: 6.0,
+            sunset_hour: 20.0,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl Calendar {
+    /// Use the day length from `QUEEN_DAY_LENGTH` if it is set, otherwise the default.
+    pub fn from_env() -> Self {
+        let from_env = std::env::var(DAY_LENGTH_ENV)
+            .ok()
+            .and_then(|seconds| seconds.parse::<f32>().ok())
+            .filter(|seconds| *seconds > 0.0);
+
+        match from_env {
+            Some(seconds) => Self {
+                day_length: Duration::from_secs_f32(seconds),
+                ..Default::default()
+            },
+            None => Self::default(),
+        }
+    }
+
+    pub fn advance(&mut self, delta: Duration) {
+        self.elapsed += delta;
+    }
+
+    /// Hours since the start of the first day.
+    fn total_hours(&self) -> f32 {
+        self.elapsed.as_secs_f32() / self.day_length.as_secs_f32() * HOURS_PER_DAY + self.start_hour
+    }
+
+    /// Starts at 1.
+    pub fn day(&self) -> u32 {
+        (self.total_hours() / HOURS_PER_DAY) as u32 + 1
+    }
+
+    /// 0 - 24.
+    pub fn hour(&self) -> f32 {
+        self.total_hours() % HOURS_PER_DAY
+    }
+
+    pub fn is_night(&self) -> bool {
+        let hour = self.hour();
+        hour < self.sunrise_hour || hour >= self.sunset_hour
+    }
+
+    pub fn season(&self) -> Season {
+        Season::from_index((self.day() - 1) / self.days_per_season)
+    }
+
+    /// 0 at night, rising to 1 halfway between sunrise and sunset.
+    pub fn daylight(&self) -> f32 {
+        if self.is_night() {
+            return 0.0;
+        }
+
+        let t = (self.hour() - self.sunrise_hour) / (self.sunset_hour - self.sunrise_hour);
+        (t * PI).sin()
+    }
+
+    /// How much longer it takes scouts to find new food than usual.
+    pub fn food_discovery_time_scale(&self) -> f32 {
+        let season = match self.season() {
+            Season::Spring => 1.0,
+            Season::Summer => 0.75,
+            Season::Autumn => 1.25,
+            Season::Winter => 3.0,
+        };
+
+        if self.is_night() {
+            season * 2.0
+        } else {
+            season
+        }
+    }
+
+    /// Chance of a forager not coming back from a trip off the map. 0 - 1.
+    pub fn forager_risk(&self) -> f32 {
+        let risk = if self.is_night() { 0.1 } else { 0.02 };
+
+        if self.season() == Season::Winter {
+            risk + 0.05
+        } else {
+            risk
+        }
+    }
+}
+
+impl fmt::Display for Calendar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hour = self.hour();
+        let minute = (hour.fract() * 60.0) as u32;
+        write!(
+            f,
+            "Day {}, {:02}:{:02} ({:?})",
+            self.day(),
+            hour as u32,
+            minute,
+            self.season()
+        )
+    }
+}
+
+pub fn advance_calendar(time: Res<GameTime>, mut calendar: ResMut<Calendar>) {
+    calendar.advance(time.delta());
+}
+
+/// Brighten the sky during the day.
+pub fn update_sky_colour(calendar: Res<Calendar>, mut cameras: Query<&mut Camera2d>) {
+    let night = Color::rgb(0.05, 0.05, 0.2).as_rgba_f32();
+    let day = Color::rgb(0.45, 0.7, 0.95).as_rgba_f32();
+    let t = calendar.daylight();
+    let lerp = |i: usize| night[i] + (day[i] - night[i]) * t;
+
+    for mut camera in &mut cameras {
+        camera.clear_color = ClearColorConfig::Custom(Color::rgb(lerp(0), lerp(1), lerp(2)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn after_hours(hours: f32) -> Calendar {
+        let mut calendar = Calendar {
+            start_hour: 0.0,
+            ..Default::default()
+        };
+        let day_seconds = calendar.day_length.as_secs_f32();
+        calendar.advance(Duration::from_secs_f32(hours / HOURS_PER_DAY * day_seconds));
+        calendar
+    }
+
+    #[test]
+    fn hours_roll_over_into_days() {
+        let calendar = after_hours(30.0);
+        assert_eq!(calendar.day(), 2);
+        assert!((calendar.hour() - 6.0).abs() < 0.01);
+
+        assert_eq!(Calendar::default().to_string(), "Day 1, 08:00 (Spring)");
+    }
+
+    #[test]
+    fn night_is_dark() {
+        assert!(after_hours(2.0).is_night());
+        assert_eq!(after_hours(2.0).daylight(), 0.0);
+        assert!(!after_hours(13.0).is_night());
+        assert!(after_hours(13.0).daylight() > 0.99);
+    }
+
+    #[test]
+    fn seasons_follow_the_days() {
+        // Midday on the first day of each season.
+        let season_hours = Calendar::default().days_per_season as f32 * HOURS_PER_DAY;
+        let season_at = |season: f32| after_hours(season * season_hours + 12.0).season();
+
+        assert_eq!(season_at(0.0), Season::Spring);
+        assert_eq!(season_at(1.0), Season::Summer);
+        assert_eq!(season_at(3.0), Season::Winter);
+        assert_eq!(season_at(4.0), Season::Spring);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::game::positions::SidePosition;
-use crate::game::setup::queen_start;
+use crate::game::setup::QueenStart;
 use crate::input::{InputAction, InputStates};
 use bevy::core_pipeline::clear_color::ClearColorConfig;
 use bevy::prelude::*;
@@ -31,7 +31,7 @@ impl CameraFocus {
     }
 }
 
-pub fn setup(mut commands: Commands) {
+pub fn setup(mut commands: Commands, queen_start: Res<QueenStart>) {
     commands.spawn(Camera2dBundle {
         camera_2d: Camera2d {
             clear_color: ClearColorConfig::Custom(Color::INDIGO),
@@ -40,7 +40,7 @@ pub fn setup(mut commands: Commands) {
         ..Default::default()
     });
 
-    commands.insert_resource(CameraFocus::new(queen_start().into()));
+    commands.insert_resource(CameraFocus::new((**queen_start).into()));
 }
 
 pub fn control(
@@ -0,0 +1,156 @@
+//! The side map is split into square chunks that are generated when creatures get close to them,
+//! so the world can keep growing sideways and downwards.
+//!
+//! Chunk keys are [SideIPos]s in chunk coordinates, so they sort in Morton order. Levels loaded
+//! from a file never grow.
+
+use crate::game::level::MapSource;
+use crate::game::map::{CellContent, SideMapPosToEntities};
+use crate::game::map_generator::{MapGenerator, MapGeneratorParams, MapSeed};
+use crate::game::movement::{movement_weight, update_edges_around};
+use crate::game::pathfinding::{ChangedCells, SideMapGraph, VisitedNodeEvent};
+use crate::game::positions::SideIPos;
+use crate::game::setup::spawn_cells;
+use crate::game::water::WaterLevels;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::BTreeSet;
+
+/// Width and height of a chunk in cells.
+pub const CHUNK_SIZE: i32 = 16;
+
+/// Load the next chunk when a creature is this many cells away from it.
+const LOAD_DISTANCE: i32 = 4;
+
+pub fn chunk_of(pos: SideIPos) -> SideIPos {
+    SideIPos::new(pos.x.div_euclid(CHUNK_SIZE), pos.y.div_euclid(CHUNK_SIZE))
+}
+
+/// The bottom left cell of a chunk.
+pub fn chunk_min(chunk: SideIPos) -> IVec2 {
+    *chunk * CHUNK_SIZE
+}
+
+/// The chunks that should be loaded when something is happening at this cell.
+pub fn nearby_chunks(pos: SideIPos) -> [SideIPos; 4] {
+    [
+        chunk_of(SideIPos::new(pos.x + LOAD_DISTANCE, pos.y)),
+        chunk_of(SideIPos::new(pos.x - LOAD_DISTANCE, pos.y)),
+        chunk_of(SideIPos::new(pos.x, pos.y + LOAD_DISTANCE)),
+        chunk_of(SideIPos::new(pos.x, pos.y - LOAD_DISTANCE)),
+    ]
+}
+
+pub struct LoadChunkEvent(pub SideIPos);
+
+#[derive(Resource, Debug, Default)]
+pub struct SideMapChunks {
+    loaded: BTreeSet<SideIPos>,
+
+    /// False for levels, which have a fixed size.
+    can_grow: bool,
+}
+
+impl SideMapChunks {
+    pub fn growing(loaded: impl IntoIterator<Item = SideIPos>) -> Self {
+        Self {
+            loaded: loaded.into_iter().collect(),
+            can_grow: true,
+        }
+    }
+
+    pub fn fixed() -> Self {
+        Self::default()
+    }
+
+    /// Whether the chunk still needs to be generated.
+    pub fn is_missing(&self, chunk: SideIPos) -> bool {
+        self.can_grow && !self.loaded.contains(&chunk)
+    }
+}
+
+/// Ask for the chunks around any cell a creature walks through.
+pub fn request_chunks_near_visitors(
+    chunks: Res<SideMapChunks>,
+    mut visited_node_reader: EventReader<VisitedNodeEvent>,
+    mut load_chunk_writer: EventWriter<LoadChunkEvent>,
+) {
+    for event in visited_node_reader.iter() {
+        for chunk in nearby_chunks(event.position) {
+            if chunks.is_missing(chunk) {
+                load_chunk_writer.send(LoadChunkEvent(chunk));
+            }
+        }
+    }
+}
+
+/// Generate requested chunks, then spawn their cells and join them up to the graph.
+pub fn load_chunks(
+    mut commands: Commands,
+    map_source: Res<MapSource>,
+    map_seed: Res<MapSeed>,
+    map_params: Res<MapGeneratorParams>,
+    mut chunks: ResMut<SideMapChunks>,
+    mut side_map_pos_to_entities: ResMut<SideMapPosToEntities>,
+    mut graph: ResMut<SideMapGraph>,
+    mut changed_cells: ResMut<ChangedCells>,
+    water_levels: Res<WaterLevels>,
+    cells: Query<&CellContent>,
+    mut load_chunk_reader: EventReader<LoadChunkEvent>,
+) {
+    let MapSource::Generated = *map_source else {
+        load_chunk_reader.clear();
+        return;
+    };
+
+    let generator = MapGenerator::new(*map_seed, map_params.clone());
+
+    // Cells spawned this frame aren't in the query yet.
+    let mut spawned: HashMap<SideIPos, CellContent> = HashMap::new();
+
+    for LoadChunkEvent(chunk) in load_chunk_reader.iter() {
+        if !chunks.is_missing(*chunk) {
+            continue;
+        }
+
+        chunks.loaded.insert(*chunk);
+        if !generator.can_generate(*chunk) {
+            continue;
+        }
+
+        info!(?chunk, "Generating chunk");
+        let grid = generator.generate_chunk(*chunk);
+        spawn_cells(
+            &mut commands,
+            &grid,
+            &mut side_map_pos_to_entities,
+            &mut graph,
+            &mut changed_cells,
+        );
+
+        for (pos, cell) in grid.iter() {
+            spawned.insert(pos, *cell);
+        }
+
+        let cell_at = |pos: SideIPos| {
+            spawned.get(&pos).copied().or_else(|| {
+                let entity = side_map_pos_to_entities.get(&pos)?;
+                cells.get(*entity).ok().copied()
+            })
+        };
+
+        // Join the edges of the chunk to any cells that were already there. Cells on either side
+        // of the border may have been holding on to what they thought was solid.
+        for (pos, _) in grid.iter() {
+            if pos.sides().iter().all(|side| grid.contains(*side)) {
+                continue;
+            }
+
+            let changed = update_edges_around(&mut graph, pos, |from, to| {
+                let weight = movement_weight(from, to, &cell_at)?;
+                Some(weight + water_levels.extra_weight(&from) + water_levels.extra_weight(&to))
+            });
+            changed_cells.extend(changed);
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! Temperature and humidity over the side map.
+//!
+//! Air above ground takes on the [SurfaceClimate], which follows the time of day and the season.
+//! Underground, every cell is slowly pulled towards the steady temperature and humidity of deep
+//! soil, and each step it mixes with its neighbours. Open tunnels mix much faster than solid dirt,
+//! so a tunnel to the surface lets the weather in. Wet cells are fully humid.
+//!
+//! Eggs grow fastest in warm, humid cells. Anywhere too far off that, they grow slowly and get
+//! weaker, so they're less likely to hatch.
+
+use crate::game::calendar::{Calendar, Season};
+use crate::game::map::{CellContent, SIDE_CELL_SIZE};
+use crate::game::plugin::CLIMATE_Z;
+use crate::game::positions::SideIPos;
+use crate::game::setup::sprite;
+use crate::game::time::GameTime;
+use crate::game::water::{Rain, WaterLevels};
+use crate::input::{InputAction, InputStates};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellClimate {
+    /// Degrees celsius.
+    pub temperature: f32,
+
+    /// 0 is bone dry and 1 is soaking.
+    pub humidity: f32,
+}
+
+impl CellClimate {
+    pub fn new(temperature: f32, humidity: f32) -> Self {
+        Self {
+            temperature,
+            humidity,
+        }
+    }
+
+    fn lerp(&self, other: &CellClimate, t: f32) -> Self {
+        Self {
+            temperature: self.temperature + (other.temperature - self.temperature) * t,
+            humidity: self.humidity + (other.humidity - self.humidity) * t,
+        }
+    }
+
+    /// How good this cell is for brood. 1 is ideal and 0 is hopeless.
+    pub fn brood_suitability(&self, settings: &ClimateSettings) -> f32 {
+        let falloff = |value: f32, ideal: f32, tolerance: f32| {
+            (1.0 - (value - ideal).abs() / tolerance).clamp(0.0, 1.0)
+        };
+
+        falloff(
+            self.temperature,
+            settings.ideal_temperature,
+            settings.temperature_tolerance,
+        ) * falloff(
+            self.humidity,
+            settings.ideal_humidity,
+            settings.humidity_tolerance,
+        )
+    }
+}
+
+/// The weather above ground. Rain makes it fully humid.
+#[derive(Resource, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct SurfaceClimate(pub CellClimate);
+
+impl Default for SurfaceClimate {
+    fn default() -> Self {
+        Self(CellClimate::new(22.0, 0.5))
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ClimateSettings {
+    /// How much game time between each step.
+    pub step_interval: Duration,
+
+    /// What the soil settles to away from the surface.
+    pub deep: CellClimate,
+
+    /// How much warmer the surface is at midday than at night.
+    pub daily_temperature_swing: f32,
+
+    /// How many rows down until a new cell starts at the deep climate. Cells above that start
+    /// somewhere between the surface and the deep.
+    pub stable_depth: i32,
+
+    /// How much underground cells move towards the deep climate each step. 0 - 1.
+    pub ground_pull: f32,
+
+    /// How much of the difference with each neighbour is mixed in each step, when both are open.
+    pub open_mixing: f32,
+
+    /// The same, when either of them is dirt or rock.
+    pub solid_mixing: f32,
+
+    pub ideal_temperature: f32,
+
+    /// Brood doesn't grow at all this many degrees away from the ideal.
+    pub temperature_tolerance: f32,
+
+    pub ideal_humidity: f32,
+    pub humidity_tolerance: f32,
+
+    /// Eggs grow at least this fast, as a fraction of their normal speed.
+    pub min_egg_growth: f32,
+
+    /// Eggs get weaker while the brood suitability of their cell is below this.
+    pub poor_brood_suitability: f32,
+
+    /// Egg health lost every second in a poor cell. Eggs start with 1.
+    pub egg_damage_per_second: f32,
+}
+
+impl Default for ClimateSettings {
+    fn default() -> Self {
+        Self {
+            step_interval: Duration::from_secs(1),
+            deep: CellClimate::new(18.0, 0.8),
+            daily_temperature_swing: 10.0,
+            stable_depth: 20,
+            ground_pull: 0.02,
+            open_mixing: 0.2,
+            solid_mixing: 0.05,
+            ideal_temperature: 25.0,
+            temperature_tolerance: 12.0,
+            ideal_humidity: 0.75,
+            humidity_tolerance: 0.5,
+            min_egg_growth: 0.1,
+            poor_brood_suitability: 0.2,
+            egg_damage_per_second: 0.01,
+        }
+    }
+}
+
+impl ClimateSettings {
+    /// The climate of a cell that hasn't been simulated yet.
+    pub fn initial(&self, pos: SideIPos, surface: &CellClimate) -> CellClimate {
+        let depth = (-pos.y).clamp(0, self.stable_depth);
+        surface.lerp(&self.deep, depth as f32 / self.stable_depth as f32)
+    }
+}
+
+/// The climate of every cell that has been simulated.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct Climate(HashMap<SideIPos, CellClimate>);
+
+impl Climate {
+    pub fn at(
+        &self,
+        pos: SideIPos,
+        surface: &CellClimate,
+        settings: &ClimateSettings,
+    ) -> CellClimate {
+        self.0
+            .get(&pos)
+            .copied()
+            .unwrap_or_else(|| settings.initial(pos, surface))
+    }
+}
+
+/// The surface at night, before it warms up during the day.
+fn night_surface(season: Season) -> CellClimate {
+    match season {
+        Season::Spring => CellClimate::new(12.0, 0.6),
+        Season::Summer => CellClimate::new(20.0, 0.5),
+        Season::Autumn => CellClimate::new(10.0, 0.7),
+        Season::Winter => CellClimate::new(0.0, 0.7),
+    }
+}
+
+/// Warm up and dry out the surface during the day.
+pub fn update_surface_climate(
+    calendar: Res<Calendar>,
+    settings: Res<ClimateSettings>,
+    mut surface: ResMut<SurfaceClimate>,
+) {
+    let daylight = calendar.daylight();
+    let mut climate = night_surface(calendar.season());
+    climate.temperature += settings.daily_temperature_swing * daylight;
+    climate.humidity -= 0.2 * daylight;
+
+    **surface = climate;
+}
+
+/// Move every cell one step towards its neighbours and the deep climate.
+pub fn step_climate(
+    cells: &HashMap<SideIPos, CellContent>,
+    water: &WaterLevels,
+    surface: &CellClimate,
+    climate: &mut Climate,
+    settings: &ClimateSettings,
+) {
+    let before: HashMap<SideIPos, CellClimate> = cells
+        .keys()
+        .map(|pos| (*pos, climate.at(*pos, surface, settings)))
+        .collect();
+
+    for (pos, cell) in cells {
+        let current = before[pos];
+
+        let mut next = if cell.is_empty() && !cell.is_underground() {
+            *surface
+        } else {
+            let mut next = current.lerp(&settings.deep, settings.ground_pull);
+
+            for side in pos.sides() {
+                let (Some(side_cell), Some(side_climate)) = (cells.get(&side), before.get(&side))
+                else {
+                    continue;
+                };
+
+                let mixing = if cell.is_empty() && side_cell.is_empty() {
+                    settings.open_mixing
+                } else {
+                    settings.solid_mixing
+                };
+
+                next.temperature += (side_climate.temperature - current.temperature) * mixing;
+                next.humidity += (side_climate.humidity - current.humidity) * mixing;
+            }
+
+            next
+        };
+
+        if water.level(pos) > 0 {
+            next.humidity = 1.0;
+        }
+
+        climate.insert(*pos, next);
+    }
+}
+
+/// Step the climate every so often. Rain soaks the surface.
+pub fn simulate_climate(
+    time: Res<GameTime>,
+    settings: Res<ClimateSettings>,
+    surface: Res<SurfaceClimate>,
+    rain: Res<Rain>,
+    water: Res<WaterLevels>,
+    mut climate: ResMut<Climate>,
+    mut since_last_step: Local<Duration>,
+    cells: Query<(&SideIPos, &CellContent)>,
+) {
+    *since_last_step += time.delta();
+    if *since_last_step < settings.step_interval {
+        return;
+    }
+    *since_last_step = Duration::ZERO;
+
+    let mut surface = **surface;
+    if rain.is_raining() {
+        surface.humidity = 1.0;
+    }
+
+    let snapshot: HashMap<SideIPos, CellContent> =
+        cells.iter().map(|(pos, cell)| (*pos, *cell)).collect();
+
+    step_climate(&snapshot, &water, &surface, &mut climate, &settings);
+}
+
+/// Which field the debug overlay is showing.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClimateOverlay {
+    #[default]
+    Off,
+    Temperature,
+    Humidity,
+}
+
+pub fn toggle_climate_overlay(input_states: Res<InputStates>, mut overlay: ResMut<ClimateOverlay>) {
+    if !input_states.just_pressed(InputAction::Debug3) {
+        return;
+    }
+
+    *overlay = match *overlay {
+        ClimateOverlay::Off => ClimateOverlay::Temperature,
+        ClimateOverlay::Temperature => ClimateOverlay::Humidity,
+        ClimateOverlay::Humidity => ClimateOverlay::Off,
+    };
+    info!(?overlay, "Climate overlay");
+}
+
+/// How many shades the overlay has, so cells are only redrawn when their shade changes.
+const OVERLAY_SHADES: f32 = 32.0;
+
+/// A translucent box over each cell. Temperature goes from blue at 0C to red at 40C, and humidity
+/// from yellow when dry to blue when wet.
+pub fn update_climate_overlay(
+    mut commands: Commands,
+    overlay: Res<ClimateOverlay>,
+    climate: Res<Climate>,
+    mut overlay_sprites: Local<HashMap<SideIPos, (Entity, Color)>>,
+) {
+    if !overlay.is_changed() && !climate.is_changed() {
+        return;
+    }
+
+    if *overlay == ClimateOverlay::Off {
+        for (_, (entity, _)) in overlay_sprites.drain() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let shade = |value: f32| (value.clamp(0.0, 1.0) * OVERLAY_SHADES).round() / OVERLAY_SHADES;
+
+    for (pos, cell_climate) in climate.iter() {
+        let color = match *overlay {
+            ClimateOverlay::Temperature => {
+                let t = shade(cell_climate.temperature / 40.0);
+                Color::rgba(t, 0.2, 1.0 - t, 0.5)
+            }
+            _ => {
+                let h = shade(cell_climate.humidity);
+                Color::rgba(1.0 - h, 1.0 - h * 0.5, h, 0.5)
+            }
+        };
+
+        if let Some((_, shown)) = overlay_sprites.get(pos) {
+            if *shown == color {
+                continue;
+            }
+        }
+
+        let sprite = Sprite {
+            color,
+            custom_size: Some(Vec2::splat(SIDE_CELL_SIZE as f32)),
+            ..sprite()
+        };
+
+        match overlay_sprites.get_mut(pos) {
+            Some((entity, shown)) => {
+                commands.entity(*entity).insert(sprite);
+                *shown = color;
+            }
+            None => {
+                let entity = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite,
+                            transform: pos.to_transform(CLIMATE_Z),
+                            ..Default::default()
+                        },
+                        Name::new("Climate"),
+                    ))
+                    .id();
+                overlay_sprites.insert(*pos, (entity, color));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `.` is air, `_` is an empty tunnel and `#` is dirt. The top row is y = 0.
+    fn cells(rows: &[&str]) -> HashMap<SideIPos, CellContent> {
+        let mut cells = HashMap::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    '.' => CellContent::empty_air(),
+                    '_' => CellContent::empty_underground(),
+                    '#' => CellContent::dirt(255),
+                    _ => unreachable!(),
+                };
+                cells.insert(SideIPos::new(x as i32, -(y as i32)), cell);
+            }
+        }
+        cells
+    }
+
+    fn run(cells: &HashMap<SideIPos, CellContent>, surface: CellClimate, steps: usize) -> Climate {
+        let settings = ClimateSettings::default();
+        let mut climate = Climate::default();
+        for _ in 0..steps {
+            step_climate(
+                cells,
+                &WaterLevels::default(),
+                &surface,
+                &mut climate,
+                &settings,
+            );
+        }
+        climate
+    }
+
+    #[test]
+    fn deeper_cells_are_closer_to_the_deep_climate() {
+        let cells = cells(&["...", "###", "###", "###", "###", "###"]);
+        let climate = run(&cells, CellClimate::new(35.0, 0.2), 100);
+
+        let shallow = climate[&SideIPos::new(1, -1)].temperature;
+        let deep = climate[&SideIPos::new(1, -5)].temperature;
+        assert!(shallow > deep, "{shallow} {deep}");
+        assert!(deep > ClimateSettings::default().deep.temperature);
+    }
+
+    #[test]
+    fn open_tunnels_let_the_surface_in() {
+        let cells = cells(&["...", "#_#", "#_#", "#_#", "#_#", "###"]);
+        let climate = run(&cells, CellClimate::new(35.0, 0.2), 100);
+
+        let tunnel = climate[&SideIPos::new(1, -4)].temperature;
+        let dirt = climate[&SideIPos::new(0, -4)].temperature;
+        assert!(tunnel > dirt, "{tunnel} {dirt}");
+    }
+
+    #[test]
+    fn brood_grows_best_near_the_ideal() {
+        let settings = ClimateSettings::default();
+        let ideal = CellClimate::new(settings.ideal_temperature, settings.ideal_humidity);
+        let cold = CellClimate::new(5.0, settings.ideal_humidity);
+
+        assert_eq!(ideal.brood_suitability(&settings), 1.0);
+        assert!(cold.brood_suitability(&settings) < settings.poor_brood_suitability);
+    }
+}
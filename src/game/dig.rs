@@ -0,0 +1,109 @@
+//! Cells the player has marked for digging, posted as jobs on the [JobBoard].
+//!
+//! Jobs nearer the surface are handed out first, because the cells below them are usually only
+//! reachable once they're dug out.
+
+use crate::game::chunks::{nearby_chunks, LoadChunkEvent, SideMapChunks};
+use crate::game::jobs::{JobBoard, JobKind};
+use crate::game::map::{CellContent, SideMapPosToEntities, SIDE_CELL_SIZE};
+use crate::game::plugin::OVERLAY_Z;
+use crate::game::positions::SideIPos;
+use crate::game::setup::sprite;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Designate a cell for digging. Only cells with dirt in them can be dug.
+pub fn designate_cell(
+    job_board: &mut JobBoard,
+    chunks: &SideMapChunks,
+    side_map_pos_to_entities: &SideMapPosToEntities,
+    cells: &Query<&CellContent>,
+    load_chunk_writer: &mut EventWriter<LoadChunkEvent>,
+    pos: SideIPos,
+) {
+    let Some(entity) = side_map_pos_to_entities.get(&pos) else {
+        return;
+    };
+
+    let Ok(cell) = cells.get(*entity) else {
+        return;
+    };
+
+    if cell.is_empty() || cell.is_rock() {
+        return;
+    }
+
+    // Shallower cells are higher priority.
+    if job_board.post(JobKind::Dig, pos, pos.y, None).is_some() {
+        info!(?pos, "Designated cell for digging");
+
+        // Make sure there's somewhere to dig into.
+        for chunk in nearby_chunks(pos) {
+            if chunks.is_missing(chunk) {
+                load_chunk_writer.send(LoadChunkEvent(chunk));
+            }
+        }
+    }
+}
+
+/// Returns whether the cell was designated.
+pub fn cancel_designation(job_board: &mut JobBoard, pos: SideIPos) -> bool {
+    let Some(id) = job_board.find(JobKind::Dig, pos) else {
+        return false;
+    };
+
+    job_board.remove(id);
+    true
+}
+
+/// A translucent box over each designated cell. Brighter once an ant is on its way.
+pub fn update_dig_overlay(
+    mut commands: Commands,
+    job_board: Res<JobBoard>,
+    mut overlay_sprites: Local<HashMap<SideIPos, Entity>>,
+) {
+    if !job_board.is_changed() {
+        return;
+    }
+
+    overlay_sprites.retain(|pos, entity| {
+        let keep = job_board.find(JobKind::Dig, *pos).is_some();
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+
+    for job in job_board
+        .iter()
+        .map(|(_, job)| job)
+        .filter(|job| job.kind == JobKind::Dig)
+    {
+        let pos = &job.pos;
+        let alpha = if job.claimed_by.is_some() { 0.6 } else { 0.3 };
+        let sprite = Sprite {
+            color: Color::rgba(1.0, 0.6, 0.1, alpha),
+            custom_size: Some(Vec2::splat(SIDE_CELL_SIZE as f32)),
+            ..sprite()
+        };
+
+        match overlay_sprites.get(pos) {
+            Some(entity) => {
+                commands.entity(*entity).insert(sprite);
+            }
+            None => {
+                let entity = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite,
+                            transform: pos.to_transform(OVERLAY_Z),
+                            ..Default::default()
+                        },
+                        Name::new("Dig Designation"),
+                    ))
+                    .id();
+                overlay_sprites.insert(*pos, entity);
+            }
+        }
+    }
+}
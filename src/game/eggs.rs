@@ -1,22 +1,85 @@
+//! Eggs laid by the queen, and the nurses that look after them.
+//!
+//! A newly laid egg is loose wherever the queen laid it. A nurse carries it to a nursery zone, and
+//! keeps it fed from stored food. Each feeding fills its care back up, and care drains away over
+//! time. An egg outside of the nursery or low on care is untended: it grows slowly, and once its
+//! care runs out it gets weaker until it dies.
+
 use crate::game::ants::AntType;
+use crate::game::climate::{Climate, ClimateSettings, SurfaceClimate};
+use crate::game::food::FoodState;
+use crate::game::jobs::{JobBoard, JobKind};
 use crate::game::plugin::EGG_Z;
 use crate::game::positions::SideIPos;
 use crate::game::queen::EggLaidEvent;
 use crate::game::setup::sprite;
 use crate::game::time::GameTime;
+use crate::game::zones::NurseryZones;
 use bevy::asset::AssetServer;
 use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+#[derive(Resource, Debug)]
+pub struct BroodSettings {
+    /// Care lost every second. Eggs start with 1.
+    pub care_per_second: f32,
+
+    /// Eggs with less care than this need feeding.
+    pub hungry_care: f32,
+
+    /// How fast untended eggs grow, as a fraction of their normal speed.
+    pub untended_growth: f32,
+
+    /// Egg health lost every second once care runs out.
+    pub neglect_damage_per_second: f32,
+
+    pub carry_egg_priority: i32,
+    pub feed_brood_priority: i32,
+}
+
+impl Default for BroodSettings {
+    fn default() -> Self {
+        Self {
+            care_per_second: 1.0 / 60.0,
+            hungry_care: 0.5,
+            untended_growth: 0.25,
+            neglect_damage_per_second: 0.02,
+            carry_egg_priority: 5,
+            feed_brood_priority: 5,
+        }
+    }
+}
 
 pub struct SpawnAntEvent {
     pub ant_type: AntType,
     pub position: SideIPos,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EggLocation {
+    /// Lying outside of the nursery, waiting to be carried there.
+    Loose,
+
+    /// Being carried by this nurse.
+    Carried(Entity),
+
+    Nursery,
+}
+
 #[derive(Component, Clone, Copy, Debug)]
 pub struct Egg {
     pub ant_type: AntType,
     pub growth: f32,
     pub hatch_at: f32,
+
+    /// Goes down while the egg is flooded, somewhere too cold or dry, or neglected. The egg dies at
+    /// 0, and is less likely to hatch the lower it gets.
+    pub health: f32,
+
+    /// How well looked after the egg is, 0 - 1. Feeding fills it up.
+    pub care: f32,
+
+    pub location: EggLocation,
 }
 
 impl Egg {
@@ -25,16 +88,40 @@ impl Egg {
             ant_type,
             growth: 0f32,
             hatch_at,
+            health: 1f32,
+            care: 1f32,
+            location: EggLocation::Loose,
         }
     }
+
+    pub fn is_untended(&self, settings: &BroodSettings) -> bool {
+        self.location != EggLocation::Nursery || self.care < settings.hungry_care
+    }
+
+    /// Leave the egg where it is, in the nursery if that's where it is.
+    pub fn put_down(&mut self, pos: SideIPos, nursery_zones: &NurseryZones) {
+        self.location = if nursery_zones.contains(&pos) {
+            EggLocation::Nursery
+        } else {
+            EggLocation::Loose
+        };
+    }
 }
 
+/// The egg a nurse is carrying. Taken away if the nurse gives up, and then the egg is put down.
+#[derive(Component, Debug, Deref)]
+pub struct CarriedEgg(pub Entity);
+
 pub fn spawn_eggs(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    nursery_zones: Res<NurseryZones>,
     mut egg_laid_reader: EventReader<EggLaidEvent>,
 ) {
     for EggLaidEvent { egg, position } in egg_laid_reader.iter() {
+        let mut egg = *egg;
+        egg.put_down(*position, &nursery_zones);
+
         let texture = asset_server.load("creatures/egg.png");
         let transform = position.to_transform(EGG_Z);
 
@@ -44,28 +131,155 @@ pub fn spawn_eggs(
             texture,
             ..Default::default()
         };
-        commands.spawn((sprite_bundle, *egg));
+        commands.spawn((sprite_bundle, egg));
     }
 }
 
+/// Eggs grow faster the better the climate of their cell is, and slower when they're untended.
 pub fn grow_eggs(
     mut commands: Commands,
     time: Res<GameTime>,
+    brood_settings: Res<BroodSettings>,
+    settings: Res<ClimateSettings>,
+    surface: Res<SurfaceClimate>,
+    climate: Res<Climate>,
     mut query: Query<(Entity, &mut Egg, &Transform)>,
     mut spawn_ant_writer: EventWriter<SpawnAntEvent>,
 ) {
+    let delta = time.delta_seconds();
     for (entity, mut egg, transform) in query.iter_mut() {
-        egg.growth += time.delta_seconds();
+        let position = SideIPos::from(transform);
+        let suitability = climate
+            .at(position, &surface, &settings)
+            .brood_suitability(&settings);
+
+        egg.care = (egg.care - brood_settings.care_per_second * delta).max(0f32);
+
+        let mut growth = suitability.max(settings.min_egg_growth);
+        if egg.is_untended(&brood_settings) {
+            growth *= brood_settings.untended_growth;
+        }
+        egg.growth += delta * growth;
+
+        if suitability < settings.poor_brood_suitability {
+            egg.health -= settings.egg_damage_per_second * delta;
+        }
+        if egg.care == 0f32 {
+            egg.health -= brood_settings.neglect_damage_per_second * delta;
+        }
+        if egg.health <= 0f32 {
+            info!(?entity, ?position, "Egg died from the cold, dry or neglect");
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
         if egg.growth < egg.hatch_at {
             continue;
         }
 
-        let position = SideIPos::from(transform);
+        commands.entity(entity).despawn();
+
+        if rand::random::<f32>() >= egg.health {
+            info!(?entity, ?position, "Egg failed to hatch");
+            continue;
+        }
+
         spawn_ant_writer.send(SpawnAntEvent {
             ant_type: egg.ant_type,
             position,
         });
+    }
+}
 
-        commands.entity(entity).despawn();
+/// Carried eggs go wherever their nurse goes. Eggs whose nurse has given up on them or died are put
+/// down where they are.
+pub fn carry_eggs(
+    nursery_zones: Res<NurseryZones>,
+    carriers: Query<(&CarriedEgg, &Transform), Without<Egg>>,
+    mut eggs: Query<(Entity, &mut Egg, &mut Transform)>,
+) {
+    for (entity, mut egg, mut transform) in &mut eggs {
+        let EggLocation::Carried(carrier) = egg.location else {
+            continue;
+        };
+
+        match carriers.get(carrier) {
+            Ok((carried, carrier_transform)) if **carried == entity => {
+                transform.translation = carrier_transform.translation.truncate().extend(EGG_Z);
+            }
+            _ => {
+                let pos = SideIPos::from(&*transform);
+                info!(?entity, ?pos, "Egg was dropped");
+                egg.put_down(pos, &nursery_zones);
+                *transform = pos.to_transform(EGG_Z);
+            }
+        }
+    }
+}
+
+/// Nurses carry loose eggs to the nursery, and feed the hungry ones there from storage.
+pub fn post_brood_jobs(
+    settings: Res<BroodSettings>,
+    food_state: Res<FoodState>,
+    nursery_zones: Res<NurseryZones>,
+    mut job_board: ResMut<JobBoard>,
+    eggs: Query<(&Egg, &Transform)>,
+) {
+    let mut loose = HashSet::new();
+    let mut hungry = HashSet::new();
+    for (egg, transform) in &eggs {
+        let pos = SideIPos::from(transform);
+        match egg.location {
+            EggLocation::Loose if !nursery_zones.is_empty() => {
+                loose.insert(pos);
+            }
+            EggLocation::Nursery if egg.care < settings.hungry_care => {
+                hungry.insert(pos);
+            }
+            _ => {}
+        }
+    }
+
+    if food_state.food_position_cells.is_empty() {
+        hungry.clear();
+    }
+
+    for (kind, cells, priority) in [
+        (JobKind::CarryEgg, &loose, settings.carry_egg_priority),
+        (JobKind::FeedBrood, &hungry, settings.feed_brood_priority),
+    ] {
+        for id in job_board.stale(kind, |job| !cells.contains(&job.pos)) {
+            job_board.remove(id);
+        }
+
+        for pos in cells {
+            if job_board.find(kind, *pos).is_none() {
+                job_board.post(kind, *pos, priority, Some(AntType::Nurse));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eggs_are_tended_in_the_nursery_while_fed() {
+        let settings = BroodSettings::default();
+        let mut nursery_zones = NurseryZones::default();
+        nursery_zones.add(SideIPos::new(1, -1));
+
+        let mut egg = Egg::new(AntType::Nurse, 3f32);
+        egg.put_down(SideIPos::new(0, -1), &nursery_zones);
+        assert_eq!(egg.location, EggLocation::Loose);
+        assert!(egg.is_untended(&settings));
+
+        egg.put_down(SideIPos::new(1, -1), &nursery_zones);
+        assert_eq!(egg.location, EggLocation::Nursery);
+        assert!(!egg.is_untended(&settings));
+
+        egg.care = settings.hungry_care / 2f32;
+        assert!(egg.is_untended(&settings));
     }
 }
@@ -0,0 +1,334 @@
+//! Flow fields toward the places most trips go to: the queen, the food storage zones and the exits.
+//!
+//! Each field holds the cost of getting from every reachable cell to the nearest of its targets, and
+//! which way to step to get there. Ants heading to one of them follow the field one cell at a time
+//! instead of each running their own search. See [crate::game::pathfinding::Path::FollowField].
+//!
+//! When edges change, only the part of a field that went through the changed cells is worked out
+//! again. Those are found by following the field backwards from them. Fields are rebuilt from
+//! scratch when their targets change.
+
+use crate::game::food::FoodState;
+use crate::game::map::ExitPositions;
+use crate::game::pathfinding::{ChangedCells, SideMapGraph};
+use crate::game::positions::SideIPos;
+use crate::game::setup::QueenStart;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowDestination {
+    Queen,
+    FoodStorage,
+    Exits,
+}
+
+impl FlowDestination {
+    pub const ALL: [FlowDestination; 3] = [
+        FlowDestination::Queen,
+        FlowDestination::FoodStorage,
+        FlowDestination::Exits,
+    ];
+}
+
+#[derive(Debug, Default)]
+pub struct FlowField {
+    targets: Vec<SideIPos>,
+
+    /// Cost of the cheapest path from each cell to the nearest target.
+    costs: HashMap<SideIPos, u64>,
+
+    /// The next cell along the cheapest path. Targets don't have one.
+    next: HashMap<SideIPos, SideIPos>,
+
+    /// The cells whose next cell is each cell.
+    previous: HashMap<SideIPos, HashSet<SideIPos>>,
+}
+
+impl FlowField {
+    pub fn new(graph: &SideMapGraph, targets: Vec<SideIPos>) -> Self {
+        let mut field = Self {
+            targets,
+            ..Default::default()
+        };
+
+        let mut queue = BinaryHeap::new();
+        field.seed_targets(graph, &mut queue);
+        field.propagate(graph, queue);
+        field
+    }
+
+    pub fn is_target(&self, pos: SideIPos) -> bool {
+        self.targets.contains(&pos)
+    }
+
+    pub fn cost(&self, pos: SideIPos) -> Option<u64> {
+        self.costs.get(&pos).copied()
+    }
+
+    /// Where to step from this cell to get closer to a target. None at a target, or if no target can
+    /// be reached from here.
+    pub fn next_step(&self, pos: SideIPos) -> Option<SideIPos> {
+        self.next.get(&pos).copied()
+    }
+
+    /// Work out the field again around cells that changed. `changed` must hold every cell with an edge
+    /// that changed.
+    ///
+    /// Cells whose path went through a changed cell are cleared, then filled in again from the cells
+    /// around them. Changed cells that kept their path are searched from too, in case they opened up
+    /// a cheaper way.
+    pub fn update(&mut self, graph: &SideMapGraph, changed: &HashSet<SideIPos>) {
+        let mut invalid = HashSet::new();
+        let mut stack: Vec<SideIPos> = changed
+            .iter()
+            .filter(|pos| self.costs.contains_key(*pos))
+            .copied()
+            .collect();
+        while let Some(pos) = stack.pop() {
+            if !invalid.insert(pos) {
+                continue;
+            }
+
+            if let Some(previous) = self.previous.get(&pos) {
+                stack.extend(previous.iter().copied());
+            }
+        }
+
+        for pos in &invalid {
+            self.clear(*pos);
+        }
+
+        let mut queue = BinaryHeap::new();
+        self.seed_targets(graph, &mut queue);
+
+        for pos in invalid.iter().chain(changed.iter()).copied() {
+            if !graph.contains_node(pos) {
+                continue;
+            }
+
+            for (_, neighbour, _) in graph.edges(pos) {
+                if let Some(cost) = self.cost(neighbour) {
+                    queue.push(Reverse((cost, neighbour)));
+                }
+            }
+
+            if let Some(cost) = self.cost(pos) {
+                queue.push(Reverse((cost, pos)));
+            }
+        }
+
+        self.propagate(graph, queue);
+    }
+
+    fn set_next(&mut self, pos: SideIPos, next: SideIPos) {
+        if let Some(old) = self.next.insert(pos, next) {
+            if let Some(previous) = self.previous.get_mut(&old) {
+                previous.remove(&pos);
+            }
+        }
+        self.previous.entry(next).or_default().insert(pos);
+    }
+
+    /// Forget a cell's cost and next step. Whatever stepped to it is cleared separately.
+    fn clear(&mut self, pos: SideIPos) {
+        self.costs.remove(&pos);
+        self.previous.remove(&pos);
+        if let Some(next) = self.next.remove(&pos) {
+            if let Some(previous) = self.previous.get_mut(&next) {
+                previous.remove(&pos);
+            }
+        }
+    }
+
+    fn seed_targets(
+        &mut self,
+        graph: &SideMapGraph,
+        queue: &mut BinaryHeap<Reverse<(u64, SideIPos)>>,
+    ) {
+        for target in &self.targets {
+            if !graph.contains_node(*target) || self.costs.contains_key(target) {
+                continue;
+            }
+
+            self.costs.insert(*target, 0);
+            queue.push(Reverse((0, *target)));
+        }
+    }
+
+    /// Dijkstra outwards from the queued cells, lowering costs wherever a cheaper path is found.
+    fn propagate(&mut self, graph: &SideMapGraph, mut queue: BinaryHeap<Reverse<(u64, SideIPos)>>) {
+        while let Some(Reverse((cost, pos))) = queue.pop() {
+            if self.cost(pos).map(|best| cost > best).unwrap_or(true) {
+                continue;
+            }
+
+            for (_, neighbour, weight) in graph.edges(pos) {
+                let neighbour_cost = cost + weight;
+                if self
+                    .cost(neighbour)
+                    .map(|best| neighbour_cost >= best)
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+
+                self.costs.insert(neighbour, neighbour_cost);
+                self.set_next(neighbour, pos);
+                queue.push(Reverse((neighbour_cost, neighbour)));
+            }
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct FlowFields {
+    fields: HashMap<FlowDestination, FlowField>,
+}
+
+impl FlowFields {
+    pub fn get(&self, destination: FlowDestination) -> Option<&FlowField> {
+        self.fields.get(&destination)
+    }
+
+    /// Build the field for a destination from scratch if its targets changed, otherwise update it
+    /// around the changed cells.
+    fn update(
+        &mut self,
+        graph: &SideMapGraph,
+        destination: FlowDestination,
+        targets: Vec<SideIPos>,
+        changed: &HashSet<SideIPos>,
+    ) {
+        match self.fields.get_mut(&destination) {
+            Some(field) if field.targets == targets => {
+                if !changed.is_empty() {
+                    field.update(graph, changed);
+                }
+            }
+            _ => {
+                self.fields
+                    .insert(destination, FlowField::new(graph, targets));
+            }
+        }
+    }
+}
+
+pub fn update_flow_fields(
+    graph: Res<SideMapGraph>,
+    changed_cells: Res<ChangedCells>,
+    queen_start: Res<QueenStart>,
+    food_state: Res<FoodState>,
+    exit_positions: Option<Res<ExitPositions>>,
+    mut flow_fields: ResMut<FlowFields>,
+) {
+    for destination in FlowDestination::ALL {
+        let mut targets = match destination {
+            FlowDestination::Queen => vec![**queen_start],
+            FlowDestination::FoodStorage => food_state.food_zones.iter().copied().collect(),
+            FlowDestination::Exits => exit_positions
+                .as_ref()
+                .map(|exits| exits.to_vec())
+                .unwrap_or_default(),
+        };
+        targets.sort_by_key(|pos| (pos.x, pos.y));
+
+        flow_fields.update(&graph, destination, targets, &changed_cells);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::{CellContent, CellGrid};
+    use crate::game::map_generator::{MapGenerator, MapGeneratorParams, MapSeed};
+    use crate::game::movement::{add_grid_to_graph, movement_weight, update_edges_around};
+    use crate::game::path_hierarchy::astar_path;
+    use rand::prelude::*;
+
+    fn graph_for(grid: &CellGrid) -> SideMapGraph {
+        let mut graph = SideMapGraph::new();
+        add_grid_to_graph(&mut graph, grid);
+        graph
+    }
+
+    fn open_cells(grid: &CellGrid) -> Vec<SideIPos> {
+        grid.iter()
+            .filter(|(_, cell)| !cell.is_rock())
+            .map(|(pos, _)| pos)
+            .collect()
+    }
+
+    #[test]
+    fn following_the_field_is_as_cheap_as_astar() {
+        let grid = MapGenerator::new(MapSeed::new(3), MapGeneratorParams::default()).generate();
+        let graph = graph_for(&grid);
+        let cells = open_cells(&grid);
+        let mut rng = StdRng::seed_from_u64(3);
+        let target = *cells.choose(&mut rng).unwrap();
+        let field = FlowField::new(&graph, vec![target]);
+
+        for _ in 0..50 {
+            let start = *cells.choose(&mut rng).unwrap();
+            let Some((_, best)) = astar_path(&graph, start, target) else {
+                assert_eq!(field.cost(start), None);
+                continue;
+            };
+
+            let mut cost = 0;
+            let mut pos = start;
+            while let Some(next) = field.next_step(pos) {
+                cost += graph.edge_weight(pos, next).unwrap();
+                pos = next;
+            }
+            assert_eq!(pos, target);
+            assert_eq!(cost, best);
+            assert_eq!(field.cost(start), Some(best));
+        }
+    }
+
+    #[test]
+    fn updates_match_a_full_rebuild() {
+        let mut grid = MapGenerator::new(MapSeed::new(5), MapGeneratorParams::default()).generate();
+        let cells = open_cells(&grid);
+        let mut rng = StdRng::seed_from_u64(5);
+        let targets = vec![
+            *cells.choose(&mut rng).unwrap(),
+            *cells.choose(&mut rng).unwrap(),
+        ];
+        let mut graph = graph_for(&grid);
+        let mut field = FlowField::new(&graph, targets.clone());
+
+        // Dig some tunnels and fill some cells in.
+        let mut changed = HashSet::new();
+        for _ in 0..30 {
+            let pos = *cells.choose(&mut rng).unwrap();
+            let cell = if rng.gen_bool(0.7) {
+                CellContent::empty_underground()
+            } else {
+                CellContent::dirt(50)
+            };
+            grid.set(pos, cell);
+            let cell_at = |pos| grid.get(pos).copied();
+            changed.extend(update_edges_around(&mut graph, pos, |from, to| {
+                movement_weight(from, to, &cell_at)
+            }));
+        }
+        field.update(&graph, &changed);
+
+        let full = FlowField::new(&graph_for(&grid), targets);
+        assert_eq!(field.costs, full.costs);
+        for (pos, next) in &field.next {
+            let weight = graph.edge_weight(*pos, *next).unwrap();
+            assert_eq!(field.costs[pos], field.costs[next] + weight);
+            assert!(field.previous[next].contains(pos));
+        }
+        for (pos, previous) in &field.previous {
+            for cell in previous {
+                assert_eq!(field.next[cell], *pos);
+            }
+        }
+    }
+}
@@ -2,7 +2,6 @@ use crate::game::food_types::FoodId;
 use crate::game::hunger::Hunger;
 use crate::game::positions::SideIPos;
 use crate::game::queen::Queen;
-use crate::game::setup::queen_start;
 use crate::game::side_effects::{AppliedFoodSideEffects, SideEffect};
 use crate::game::time::GameTime;
 use crate::game::zones::FoodStorageZones;
@@ -76,7 +75,7 @@ pub struct AssignedFoodId(pub Option<FoodId>);
 
 /// Specifically for scout ants that have discovered a new food.
 /// Note: Attached to a child of the ant.
-#[derive(Component, Deref, Debug)]
+#[derive(Component, Deref, Debug, Clone)]
 pub struct CarryingDiscoveredFood(DiscoveredFood);
 
 pub fn attach_food_to_ant(
@@ -126,19 +125,6 @@ impl FoodState {
             .find(|f| f.food_info.food_id == food_id)
     }
 
-    /// This won't fail. It will always pick some spot.
-    ///
-    /// First try a random zone. If not, somewhere near the queen.
-    pub fn find_destination_to_place_food(&self) -> SideIPos {
-        if let Some(position) = self.food_zones.random() {
-            return position;
-        };
-
-        // TODO: More random?
-        // TODO: Make sure it's not on top of the queen.
-        queen_start()
-    }
-
     pub fn find_destination_to_take_food(&self) -> Option<SideIPos> {
         if self.food_position_cells.is_empty() {
             return None;
@@ -317,11 +303,11 @@ impl NextDiscoverTime {
         self.0 = Duration::from_secs_f32(self.0.as_secs_f32() * 1.1f32) as Duration;
     }
 
-    /// Random between MIN_FOOD_TIME and self
+    /// Random between MIN_FOOD_TIME and self, multiplied by `scale` for the time of day and season.
     /// Also increases the time for the next call.
-    pub fn get_and_increase(&mut self) -> Duration {
+    pub fn get_and_increase(&mut self, scale: f32) -> Duration {
         let mut rng = rand::thread_rng();
-        let time = rng.gen_range(MIN_FOOD_TIME..self.0.as_secs_f32());
+        let time = rng.gen_range(MIN_FOOD_TIME..self.0.as_secs_f32()) * scale;
         let duration = Duration::from_secs_f32(time);
         self.increase();
         duration
@@ -342,20 +328,60 @@ pub fn feed_and_apply(
     mut query: Query<(&mut Hunger, &mut AppliedFoodSideEffects)>,
 ) {
     for event in feed_reader.iter() {
-        for (mut hunger, mut applied) in query.iter_mut() {
-            info!("Feeding {event:?} with {hunger:?} and {applied:?}");
-            hunger.feed(event.carrying_food.amount);
+        let Ok((mut hunger, mut applied)) = query.get_mut(event.target) else {
+            warn!(?event.target, "Nothing to feed");
+            continue;
+        };
 
-            let carrying_food = &event.carrying_food;
-            let Some(discovered_food) = food_state.get_discovered_food(carrying_food.food_id) else {
-                error!("Food type not found in discovered food!");
-                continue;
+        info!("Feeding {event:?} with {hunger:?} and {applied:?}");
+        hunger.feed(event.carrying_food.amount);
+
+        let carrying_food = &event.carrying_food;
+        let Some(discovered_food) = food_state.get_discovered_food(carrying_food.food_id) else {
+            error!("Food type not found in discovered food!");
+            continue;
+        };
+
+        applied.add_or_update(
+            discovered_food.food_info.clone(),
+            time.since_startup() + Duration::from_secs(5 * 60),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_target_is_fed() {
+        let mut world = World::new();
+        world.insert_resource(GameTime::default());
+        world.insert_resource(FoodState::default());
+        world.init_resource::<Events<FeedEvent>>();
+
+        let hungry = || {
+            let hunger = Hunger {
+                current: 50.0,
+                ..Default::default()
             };
+            (hunger, AppliedFoodSideEffects::new())
+        };
+        let target = world.spawn(hungry()).id();
+        let other = world.spawn(hungry()).id();
+        world.send_event(FeedEvent {
+            target,
+            carrying_food: CarryingFood {
+                food_id: FoodId::random(),
+                amount: 20.0,
+            },
+        });
 
-            applied.add_or_update(
-                discovered_food.food_info.clone(),
-                time.since_startup() + Duration::from_secs(5 * 60),
-            );
-        }
+        let mut schedule = Schedule::new();
+        schedule.add_system(feed_and_apply);
+        schedule.run(&mut world);
+
+        assert_eq!(world.get::<Hunger>(target).unwrap().current, 30.0);
+        assert_eq!(world.get::<Hunger>(other).unwrap().current, 50.0);
     }
 }
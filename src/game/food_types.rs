@@ -1,9 +1,10 @@
 use bevy::prelude::Deref;
 use rand::prelude::IteratorRandom;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use strum::{EnumIter, IntoEnumIterator};
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct FoodId {
     flavor: FoodFlavor,
     texture: FoodTexture,
@@ -31,7 +32,7 @@ impl Display for FoodId {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter, Serialize, Deserialize)]
 pub enum FoodFlavor {
     Bitter,
     Salty,
@@ -42,7 +43,7 @@ pub enum FoodFlavor {
     Tasty,
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter, Serialize, Deserialize)]
 pub enum FoodTexture {
     Burnt,
     Chewy,
@@ -59,7 +60,7 @@ pub enum FoodTexture {
     Sticky,
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, EnumIter, Serialize, Deserialize)]
 pub enum FoodType {
     Almond,
     Anchovy,
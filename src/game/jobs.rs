@@ -0,0 +1,502 @@
+//! The colony's job board.
+//!
+//! Systems post jobs for things the colony needs done: cells to dig, food lying outside of storage
+//! to haul in, a hungry queen to feed, and eggs to carry to the nursery and feed. Each job has a
+//! place, a priority and optionally a caste that has to do it. An ant with nothing to do claims the
+//! best job it can do, nearest first, and runs the job's sequence from [Behaviours]. The claim is
+//! released if the ant gives up or dies, and the job is done when the ant finishes the sequence.
+//!
+//! F6 shows the jobs nobody has claimed, and the ones taking too long.
+
+use crate::game::ants::AntType;
+use crate::game::behaviours::Behaviours;
+use crate::game::food::FoodState;
+use crate::game::hunger::Hunger;
+use crate::game::mind::{Mind, MindSettings};
+use crate::game::positions::SideIPos;
+use crate::game::queen::Queen;
+use crate::game::simple_brain::Idea;
+use crate::game::time::GameTime;
+use crate::input::{InputAction, InputStates};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContexts};
+use std::time::Duration;
+
+#[derive(Resource, Debug)]
+pub struct JobSettings {
+    /// A claimed job that isn't done after this long is stalled.
+    pub stall_after: Duration,
+
+    pub haul_food_priority: i32,
+    pub feed_queen_priority: i32,
+}
+
+impl Default for JobSettings {
+    fn default() -> Self {
+        Self {
+            stall_after: Duration::from_secs(60),
+            haul_food_priority: 0,
+            feed_queen_priority: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JobKind {
+    /// Dig out the cell.
+    Dig,
+
+    /// Take the food in the cell to storage.
+    HaulFood,
+
+    /// Bring stored food to the queen, who is in the cell.
+    FeedQueen,
+
+    /// Carry a loose egg in the cell to the nursery.
+    CarryEgg,
+
+    /// Bring stored food to a hungry egg in the cell.
+    FeedBrood,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 5] = [
+        JobKind::Dig,
+        JobKind::HaulFood,
+        JobKind::FeedQueen,
+        JobKind::CarryEgg,
+        JobKind::FeedBrood,
+    ];
+
+    /// The name of the sequence in [Behaviours] that does the job.
+    pub fn sequence(&self) -> &'static str {
+        match self {
+            JobKind::Dig => "Dig",
+            JobKind::HaulFood => "HaulFood",
+            JobKind::FeedQueen => "FeedQueen",
+            JobKind::CarryEgg => "CarryEgg",
+            JobKind::FeedBrood => "FeedBrood",
+        }
+    }
+}
+
+/// Also the order jobs were posted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(u64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub kind: JobKind,
+    pub pos: SideIPos,
+
+    /// Higher priority jobs are claimed first.
+    pub priority: i32,
+
+    /// Only ants of this caste can claim the job. None is anyone.
+    pub caste: Option<AntType>,
+
+    pub claimed_by: Option<Entity>,
+    claimed_at: Option<Duration>,
+}
+
+impl Job {
+    pub fn is_stalled(&self, now: Duration, settings: &JobSettings) -> bool {
+        self.claimed_at
+            .map(|claimed_at| now >= claimed_at + settings.stall_after)
+            .unwrap_or(false)
+    }
+
+    fn suits(&self, ant_type: AntType) -> bool {
+        self.caste.map(|caste| caste == ant_type).unwrap_or(true)
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct JobBoard {
+    jobs: HashMap<JobId, Job>,
+    by_cell: HashMap<(JobKind, SideIPos), JobId>,
+    next_id: u64,
+}
+
+impl JobBoard {
+    /// Returns None if there's already a job of this kind in the cell.
+    pub fn post(
+        &mut self,
+        kind: JobKind,
+        pos: SideIPos,
+        priority: i32,
+        caste: Option<AntType>,
+    ) -> Option<JobId> {
+        if self.find(kind, pos).is_some() {
+            return None;
+        }
+
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+        self.by_cell.insert((kind, pos), id);
+        self.jobs.insert(
+            id,
+            Job {
+                kind,
+                pos,
+                priority,
+                caste,
+                claimed_by: None,
+                claimed_at: None,
+            },
+        );
+        Some(id)
+    }
+
+    /// Take the job off the board, because it's done or not needed. Whoever claimed it will notice
+    /// it's gone and give up.
+    pub fn remove(&mut self, id: JobId) -> Option<Job> {
+        let job = self.jobs.remove(&id)?;
+        self.by_cell.remove(&(job.kind, job.pos));
+        Some(job)
+    }
+
+    pub fn find(&self, kind: JobKind, pos: SideIPos) -> Option<JobId> {
+        self.by_cell.get(&(kind, pos)).copied()
+    }
+
+    pub fn get(&self, id: JobId) -> Option<&Job> {
+        self.jobs.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&JobId, &Job)> {
+        self.jobs.iter()
+    }
+
+    /// Unclaimed jobs of this kind that `is_stale` returns true for.
+    pub fn stale(&self, kind: JobKind, is_stale: impl Fn(&Job) -> bool) -> Vec<JobId> {
+        self.jobs
+            .iter()
+            .filter(|(_, job)| job.kind == kind && job.claimed_by.is_none() && is_stale(job))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// The best job nobody is working on that this caste can do: the highest priority, then the
+    /// nearest, then the oldest.
+    pub fn next_for(&self, ant_type: AntType, from: SideIPos) -> Option<JobId> {
+        self.jobs
+            .iter()
+            .filter(|(_, job)| job.claimed_by.is_none() && job.suits(ant_type))
+            .min_by_key(|(id, job)| {
+                let distance = (job.pos.x - from.x).pow(2) + (job.pos.y - from.y).pow(2);
+                (-job.priority, distance, **id)
+            })
+            .map(|(id, _)| *id)
+    }
+
+    pub fn claim(&mut self, id: JobId, entity: Entity, now: Duration) {
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.claimed_by = Some(entity);
+            job.claimed_at = Some(now);
+        }
+    }
+
+    /// Put the job back on the board, if it's still claimed by this entity.
+    pub fn release(&mut self, id: JobId, entity: Entity) {
+        if let Some(job) = self.jobs.get_mut(&id) {
+            if job.claimed_by == Some(entity) {
+                job.claimed_by = None;
+                job.claimed_at = None;
+            }
+        }
+    }
+
+    /// Whether the job is still on the board and claimed by this entity.
+    pub fn is_claimed_by(&self, id: JobId, entity: Entity) -> bool {
+        self.get(id).and_then(|job| job.claimed_by) == Some(entity)
+    }
+}
+
+/// The job an ant is working on.
+#[derive(Component, Debug, Deref)]
+pub struct ClaimedJob(pub JobId);
+
+type IdleAnts<'a> = (
+    Entity,
+    &'a AntType,
+    &'a Transform,
+    &'a mut Idea,
+    Option<&'a mut Mind>,
+);
+
+/// Give the best job going to each ant that has nothing else to do.
+pub fn claim_jobs(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    behaviours: Res<Behaviours>,
+    mind_settings: Res<MindSettings>,
+    mut job_board: ResMut<JobBoard>,
+    mut ants: Query<IdleAnts, Without<ClaimedJob>>,
+) {
+    for (entity, ant_type, transform, mut idea, mind) in &mut ants {
+        if !idea.is_finished() {
+            continue;
+        }
+
+        let Some(id) = job_board.next_for(*ant_type, SideIPos::from(transform)) else {
+            continue;
+        };
+
+        let kind = job_board.get(id).unwrap().kind;
+        info!(?entity, ?id, ?kind, "Ant claimed job");
+        job_board.claim(id, entity, time.since_startup());
+        *idea = Idea::from(behaviours.sequence(kind.sequence()));
+        if let Some(mut mind) = mind {
+            mind.started_outside(mind_settings.work_score);
+        }
+        commands.entity(entity).insert(ClaimedJob(id));
+    }
+}
+
+/// Take jobs off the board when their ant finishes them, and put them back when the ant gave up
+/// or died.
+pub fn settle_jobs(
+    mut commands: Commands,
+    mut job_board: ResMut<JobBoard>,
+    workers: Query<(Entity, &Idea, &ClaimedJob)>,
+    ants: Query<(), With<AntType>>,
+) {
+    for (entity, idea, claimed) in &workers {
+        if !idea.is_finished() {
+            continue;
+        }
+
+        if idea.did_fail() {
+            job_board.release(**claimed, entity);
+        } else if job_board.is_claimed_by(**claimed, entity) {
+            debug!(?entity, ?claimed, "Job done");
+            job_board.remove(**claimed);
+        }
+        commands.entity(entity).remove::<ClaimedJob>();
+    }
+
+    let dead: Vec<(JobId, Entity)> = job_board
+        .iter()
+        .filter_map(|(id, job)| Some((*id, job.claimed_by?)))
+        .filter(|(_, entity)| ants.get(*entity).is_err())
+        .collect();
+
+    for (id, entity) in dead {
+        info!(?id, ?entity, "Releasing job");
+        job_board.release(id, entity);
+    }
+}
+
+/// Cargo ants bring food that's lying outside of storage into storage, once there is some.
+pub fn post_haul_food_jobs(
+    settings: Res<JobSettings>,
+    food_state: Res<FoodState>,
+    mut job_board: ResMut<JobBoard>,
+) {
+    let zones = &food_state.food_zones;
+    let needs_hauling = |pos: &SideIPos| {
+        !zones.is_empty()
+            && !zones.contains(pos)
+            && food_state.food_position_cells.contains_key(pos)
+    };
+
+    // Only borrow the board mutably when something changes, so the dig overlay isn't rebuilt
+    // every frame.
+    for id in job_board.stale(JobKind::HaulFood, |job| !needs_hauling(&job.pos)) {
+        job_board.remove(id);
+    }
+
+    for pos in food_state.food_position_cells.keys() {
+        if !needs_hauling(pos) || job_board.find(JobKind::HaulFood, *pos).is_some() {
+            continue;
+        }
+
+        job_board.post(
+            JobKind::HaulFood,
+            *pos,
+            settings.haul_food_priority,
+            Some(AntType::Cargo),
+        );
+    }
+}
+
+/// Nurses feed the queen from storage when she's hungry.
+pub fn post_feed_queen_jobs(
+    settings: Res<JobSettings>,
+    food_state: Res<FoodState>,
+    mut job_board: ResMut<JobBoard>,
+    queen: Query<(&Hunger, &Transform), With<Queen>>,
+) {
+    let Ok((hunger, transform)) = queen.get_single() else {
+        return;
+    };
+
+    let is_hungry = hunger.current >= hunger.hungry_at;
+    for id in job_board.stale(JobKind::FeedQueen, |_| !is_hungry) {
+        job_board.remove(id);
+    }
+
+    let pos = SideIPos::from(transform);
+    if !is_hungry
+        || food_state.food_position_cells.is_empty()
+        || job_board.find(JobKind::FeedQueen, pos).is_some()
+    {
+        return;
+    }
+
+    job_board.post(
+        JobKind::FeedQueen,
+        pos,
+        settings.feed_queen_priority,
+        Some(AntType::Nurse),
+    );
+}
+
+#[derive(Resource, Debug, Default, Deref)]
+pub struct JobBoardPanel(bool);
+
+pub fn toggle_job_board_panel(input_states: Res<InputStates>, mut panel: ResMut<JobBoardPanel>) {
+    if input_states.just_pressed(InputAction::Debug5) {
+        panel.0 = !panel.0;
+    }
+}
+
+/// How many jobs of each kind there are, and the ones that need looking at.
+pub fn show_job_board_panel(
+    mut contexts: EguiContexts,
+    panel: Res<JobBoardPanel>,
+    time: Res<GameTime>,
+    settings: Res<JobSettings>,
+    job_board: Res<JobBoard>,
+) {
+    if !**panel {
+        return;
+    }
+
+    let now = time.since_startup();
+    let mut jobs: Vec<(&JobId, &Job)> = job_board.iter().collect();
+    jobs.sort_by_key(|(id, _)| **id);
+
+    egui::Window::new("Jobs").show(contexts.ctx_mut(), |ui| {
+        for kind in JobKind::ALL {
+            let of_kind = jobs.iter().filter(|(_, job)| job.kind == kind);
+            let claimed = of_kind.clone().filter(|(_, job)| job.claimed_by.is_some());
+            ui.label(format!(
+                "{:?}: {} ({} claimed)",
+                kind,
+                of_kind.count(),
+                claimed.count()
+            ));
+        }
+
+        ui.separator();
+        ui.heading("Unclaimed");
+        for (id, job) in jobs.iter().filter(|(_, job)| job.claimed_by.is_none()) {
+            let caste = job
+                .caste
+                .map(|caste| format!("{caste:?}"))
+                .unwrap_or_else(|| "Any".to_string());
+            ui.label(format!(
+                "#{} {:?} at {:?}, priority {}, {}",
+                id.0, job.kind, job.pos, job.priority, caste
+            ));
+        }
+
+        ui.separator();
+        ui.heading("Stalled");
+        for (id, job) in jobs
+            .iter()
+            .filter(|(_, job)| job.is_stalled(now, &settings))
+        {
+            let claimed_for = now - job.claimed_at.unwrap_or(now);
+            ui.label(format!(
+                "#{} {:?} at {:?}, {:?} for {:.0}s",
+                id.0,
+                job.kind,
+                job.pos,
+                job.claimed_by.unwrap(),
+                claimed_for.as_secs_f32()
+            ));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_then_nearest_then_oldest() {
+        let mut board = JobBoard::default();
+        let far = board
+            .post(JobKind::Dig, SideIPos::new(10, -2), -2, None)
+            .unwrap();
+        let near = board
+            .post(JobKind::Dig, SideIPos::new(1, -2), -2, None)
+            .unwrap();
+        let as_near = board
+            .post(JobKind::Dig, SideIPos::new(-1, -2), -2, None)
+            .unwrap();
+        let deep = board
+            .post(JobKind::Dig, SideIPos::new(0, -5), -5, None)
+            .unwrap();
+        assert_eq!(
+            board.post(JobKind::Dig, SideIPos::new(0, -5), -5, None),
+            None
+        );
+
+        let from = SideIPos::new(0, -5);
+        assert_eq!(board.next_for(AntType::Scout, from), Some(near));
+
+        board.claim(near, Entity::from_raw(1), Duration::ZERO);
+        assert_eq!(board.next_for(AntType::Scout, from), Some(as_near));
+        board.claim(as_near, Entity::from_raw(1), Duration::ZERO);
+        assert_eq!(board.next_for(AntType::Scout, from), Some(far));
+        board.claim(far, Entity::from_raw(2), Duration::ZERO);
+        assert_eq!(board.next_for(AntType::Scout, from), Some(deep));
+    }
+
+    #[test]
+    fn castes_only_claim_their_own_jobs() {
+        let mut board = JobBoard::default();
+        let pos = SideIPos::new(0, -1);
+        let feed = board
+            .post(JobKind::FeedQueen, pos, 10, Some(AntType::Nurse))
+            .unwrap();
+        let dig = board.post(JobKind::Dig, pos, -1, None).unwrap();
+
+        assert_eq!(board.next_for(AntType::Nurse, pos), Some(feed));
+        assert_eq!(board.next_for(AntType::Cargo, pos), Some(dig));
+    }
+
+    #[test]
+    fn claims_are_released_by_their_ant_and_stall() {
+        let settings = JobSettings::default();
+        let mut board = JobBoard::default();
+        let ant = Entity::from_raw(1);
+        let pos = SideIPos::new(0, -1);
+        let id = board.post(JobKind::Dig, pos, -1, None).unwrap();
+
+        let start = Duration::from_secs(10);
+        board.claim(id, ant, start);
+        assert_eq!(board.next_for(AntType::Scout, pos), None);
+        assert!(!board.get(id).unwrap().is_stalled(start, &settings));
+        assert!(board
+            .get(id)
+            .unwrap()
+            .is_stalled(start + settings.stall_after, &settings));
+
+        // Only the claiming ant can release it.
+        board.release(id, Entity::from_raw(2));
+        assert_eq!(board.next_for(AntType::Scout, pos), None);
+
+        board.release(id, ant);
+        assert_eq!(board.next_for(AntType::Scout, pos), Some(id));
+        assert!(!board.get(id).unwrap().is_stalled(start * 10, &settings));
+
+        board.remove(id);
+        assert_eq!(board.next_for(AntType::Scout, pos), None);
+    }
+}
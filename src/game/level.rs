@@ -0,0 +1,415 @@
+//! Hand crafted side maps.
+//!
+//! A level is a YAML file. The `grid` is an ASCII picture of the map with the top row first, and
+//! `origin` is the position of its top left character. The legend is:
+//!
+//! ```text
+//! .    Empty air, above ground.
+//! _    Empty tunnel, underground.
+//! =    Rock, above ground.
+//! #    Rock, underground.
+//! 0-9  Dirt, underground. 0 is nearly dug out and 9 is fully packed.
+//! ```
+//!
+//! See `assets/levels/tutorial.yaml` for an example. Set `QUEEN_LEVEL` to the path of a level to
+//! play it instead of a generated map.
+
+use crate::game::food_types::FoodId;
+use crate::game::map::{CellContent, CellGrid};
+use crate::game::map_generator::{MapGenerator, MapGeneratorParams, MapSeed};
+use crate::game::positions::SideIPos;
+use bevy::prelude::*;
+use color_eyre::eyre::{eyre, WrapErr};
+use color_eyre::Result;
+use serde::Deserialize;
+
+const LEVEL_ENV: &str = "QUEEN_LEVEL";
+
+/// Where the side map comes from.
+#[derive(Resource, Debug)]
+pub enum MapSource {
+    Generated,
+    Level(Level),
+}
+
+impl MapSource {
+    /// Load the level from `QUEEN_LEVEL` if it is set, otherwise generate a map.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(LEVEL_ENV) {
+            Ok(path) => Ok(Self::Level(Level::load(&path)?)),
+            Err(_) => Ok(Self::Generated),
+        }
+    }
+
+    pub fn queen_start(&self, params: &MapGeneratorParams) -> SideIPos {
+        match self {
+            MapSource::Generated => params.queen_position,
+            MapSource::Level(level) => level.queen,
+        }
+    }
+
+    pub fn level(&self, seed: MapSeed, params: &MapGeneratorParams) -> Level {
+        match self {
+            MapSource::Generated => Level::generate(seed, params),
+            MapSource::Level(level) => level.clone(),
+        }
+    }
+}
+
+/// Food placed on the map at the start.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelFood {
+    pub position: SideIPos,
+    pub amount: f32,
+
+    /// A random food is used when this isn't set.
+    #[serde(default)]
+    pub food_id: Option<FoodId>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    origin: SideIPos,
+    grid: String,
+    queen: SideIPos,
+    #[serde(default)]
+    food_zones: Vec<SideIPos>,
+    #[serde(default)]
+    food: Vec<LevelFood>,
+    #[serde(default)]
+    spoil_zones: Vec<SideIPos>,
+    #[serde(default)]
+    nursery_zones: Vec<SideIPos>,
+    exits: Vec<SideIPos>,
+}
+
+/// Everything needed to build the side map resources.
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub grid: CellGrid,
+    pub queen: SideIPos,
+    pub food_zones: Vec<SideIPos>,
+    pub food: Vec<LevelFood>,
+    pub spoil_zones: Vec<SideIPos>,
+    pub nursery_zones: Vec<SideIPos>,
+    pub exits: Vec<SideIPos>,
+}
+
+impl Level {
+    pub fn load(path: &str) -> Result<Self> {
+        let source =
+            std::fs::read_to_string(path).wrap_err_with(|| format!("Could not read {path}"))?;
+        Self::load_str(path, &source)
+    }
+
+    /// `name` is only used for error messages.
+    pub fn load_str(name: &str, source: &str) -> Result<Self> {
+        let file: LevelFile = serde_yaml::from_str(source).map_err(|err| match err.location() {
+            Some(location) => eyre!("{name}:{}:{}: {err}", location.line(), location.column()),
+            None => eyre!("{name}: {err}"),
+        })?;
+
+        let grid = parse_grid(name, source, &file)?;
+        let key_line = |key: &str| key_line(source, key).unwrap_or(1);
+
+        match grid.get(file.queen) {
+            Some(cell) if cell.is_empty() => {}
+            _ => {
+                return Err(eyre!(
+                    "{name}:{}: queen {:?} must be on an empty cell in the grid",
+                    key_line("queen"),
+                    file.queen
+                ))
+            }
+        }
+
+        if let Some(zone) = file.food_zones.iter().find(|zone| !grid.contains(**zone)) {
+            return Err(eyre!(
+                "{name}:{}: food zone {zone:?} is outside of the grid",
+                key_line("food_zones")
+            ));
+        }
+
+        if let Some(zone) = file.spoil_zones.iter().find(|zone| !grid.contains(**zone)) {
+            return Err(eyre!(
+                "{name}:{}: spoil zone {zone:?} is outside of the grid",
+                key_line("spoil_zones")
+            ));
+        }
+
+        if let Some(zone) = file
+            .nursery_zones
+            .iter()
+            .find(|zone| !grid.contains(**zone))
+        {
+            return Err(eyre!(
+                "{name}:{}: nursery zone {zone:?} is outside of the grid",
+                key_line("nursery_zones")
+            ));
+        }
+
+        if let Some(food) = file.food.iter().find(|food| !grid.contains(food.position)) {
+            return Err(eyre!(
+                "{name}:{}: food at {:?} is outside of the grid",
+                key_line("food"),
+                food.position
+            ));
+        }
+
+        if file.exits.is_empty() {
+            return Err(eyre!(
+                "{name}:{}: there must be at least one exit",
+                key_line("exits")
+            ));
+        }
+
+        Ok(Self {
+            grid,
+            queen: file.queen,
+            food_zones: file.food_zones,
+            food: file.food,
+            spoil_zones: file.spoil_zones,
+            nursery_zones: file.nursery_zones,
+            exits: file.exits,
+        })
+    }
+
+    /// Wrap a generated map with the default food, zones and exits.
+    pub fn generate(seed: MapSeed, params: &MapGeneratorParams) -> Self {
+        let grid = MapGenerator::new(seed, params.clone()).generate();
+
+        // Exit points on the surface, spaced out a bit past each edge of the map.
+        let exits = (params.min_x() - 1..params.max_x() + 2)
+            .step_by(4)
+            .map(|x| SideIPos::new(x, 0))
+            .collect();
+
+        Self {
+            grid,
+            queen: params.queen_position,
+            // TODO: Temporary...
+            food_zones: vec![
+                SideIPos::new(10, -20),
+                SideIPos::new(9, -20),
+                SideIPos::new(8, -20),
+            ],
+            food: vec![LevelFood {
+                position: SideIPos::new(5, -20),
+                amount: 5f32,
+                food_id: None,
+            }],
+            spoil_zones: Vec::new(),
+            // Either side of the queen.
+            nursery_zones: vec![
+                SideIPos::new(params.queen_position.x - 1, params.queen_position.y),
+                SideIPos::new(params.queen_position.x + 1, params.queen_position.y),
+            ],
+            exits,
+        }
+    }
+}
+
+fn parse_cell(c: char) -> Option<CellContent> {
+    match c {
+        '.' => Some(CellContent::empty_air()),
+        '_' => Some(CellContent::empty_underground()),
+        '=' => Some(CellContent::rock(false)),
+        '#' => Some(CellContent::rock(true)),
+        '0'..='9' => {
+            let digit = c.to_digit(10)? as u16;
+            Some(CellContent::dirt((digit * 255 / 9) as u8))
+        }
+        _ => None,
+    }
+}
+
+fn parse_grid(name: &str, source: &str, file: &LevelFile) -> Result<CellGrid> {
+    let rows: Vec<&str> = file.grid.lines().collect();
+    let Some(width) = rows.first().map(|row| row.chars().count()) else {
+        return Err(eyre!(
+            "{name}:{}: grid is empty",
+            key_line(source, "grid").unwrap_or(1)
+        ));
+    };
+
+    let locate = GridLocator::new(source);
+    let height = rows.len() as i32;
+    let min = IVec2::new(file.origin.x, file.origin.y - height + 1);
+    let mut grid = CellGrid::new(
+        min,
+        IVec2::new(width as i32, height),
+        CellContent::empty_air(),
+    );
+
+    for (row_index, row) in rows.iter().enumerate() {
+        let row_width = row.chars().count();
+        if row_width != width {
+            return Err(eyre!(
+                "{name}:{}: row is {row_width} cells wide but the first row is {width}",
+                locate.at(row_index, 0)
+            ));
+        }
+
+        for (column_index, c) in row.chars().enumerate() {
+            let Some(cell) = parse_cell(c) else {
+                return Err(eyre!(
+                    "{name}:{}: unknown cell '{c}'. Expected one of . _ = # or 0-9",
+                    locate.at(row_index, column_index)
+                ));
+            };
+
+            let pos = SideIPos::new(
+                file.origin.x + column_index as i32,
+                file.origin.y - row_index as i32,
+            );
+            grid.set(pos, cell);
+        }
+    }
+
+    Ok(grid)
+}
+
+/// The 1 based line of a top level key.
+fn key_line(source: &str, key: &str) -> Option<usize> {
+    let prefix = format!("{key}:");
+    source
+        .lines()
+        .position(|line| line.starts_with(&prefix))
+        .map(|index| index + 1)
+}
+
+/// Maps a grid row and column back to where it is in the file, assuming the grid is written as a
+/// `grid: |` block.
+struct GridLocator {
+    first_line: Option<usize>,
+    indent: usize,
+}
+
+impl GridLocator {
+    fn new(source: &str) -> Self {
+        let Some(key_line) = key_line(source, "grid") else {
+            return Self {
+                first_line: None,
+                indent: 0,
+            };
+        };
+
+        let indent = source
+            .lines()
+            .nth(key_line)
+            .map(|line| line.len() - line.trim_start().len())
+            .unwrap_or(0);
+
+        Self {
+            first_line: Some(key_line + 1),
+            indent,
+        }
+    }
+
+    fn at(&self, row: usize, column: usize) -> String {
+        match self.first_line {
+            Some(first_line) => format!("{}:{}", first_line + row, self.indent + column + 1),
+            None => format!("grid row {} column {}", row + 1, column + 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "\
+origin: [-2, 1]
+grid: |
+  .....
+  .....
+  9_5_9
+  9___=
+  #####
+queen: [0, -2]
+food_zones: [[-1, -1]]
+food:
+  - position: [1, -1]
+    amount: 5
+exits: [[-2, 0]]
+";
+
+    #[test]
+    fn tutorial_level_loads() {
+        let source = include_str!("../../assets/levels/tutorial.yaml");
+        Level::load_str("tutorial.yaml", source).unwrap();
+    }
+
+    #[test]
+    fn loads_grid_and_sections() {
+        let level = Level::load_str("test", LEVEL).unwrap();
+
+        assert_eq!(level.grid.len(), 25);
+        assert_eq!(
+            level.grid.get(SideIPos::new(-2, 1)),
+            Some(&CellContent::empty_air())
+        );
+        assert_eq!(
+            level.grid.get(SideIPos::new(-2, -1)),
+            Some(&CellContent::dirt(255))
+        );
+        assert_eq!(
+            level.grid.get(SideIPos::new(0, -1)),
+            Some(&CellContent::dirt(141))
+        );
+        assert_eq!(
+            level.grid.get(SideIPos::new(-1, -1)),
+            Some(&CellContent::empty_underground())
+        );
+        assert_eq!(
+            level.grid.get(SideIPos::new(2, -2)),
+            Some(&CellContent::rock(false))
+        );
+        assert_eq!(
+            level.grid.get(SideIPos::new(2, -3)),
+            Some(&CellContent::rock(true))
+        );
+        assert_eq!(level.queen, SideIPos::new(0, -2));
+        assert_eq!(level.food_zones, vec![SideIPos::new(-1, -1)]);
+        assert_eq!(level.food[0].position, SideIPos::new(1, -1));
+        assert_eq!(level.exits, vec![SideIPos::new(-2, 0)]);
+    }
+
+    #[test]
+    fn unknown_cell_reports_line_and_column() {
+        let source = LEVEL.replace("9___=", "9_x_=");
+        let err = Level::load_str("test", &source).unwrap_err();
+        assert!(
+            err.to_string().starts_with("test:6:5: unknown cell 'x'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn uneven_row_reports_line() {
+        let source = LEVEL.replace("9_5_9", "9_5_99");
+        let err = Level::load_str("test", &source).unwrap_err();
+        assert!(
+            err.to_string().starts_with("test:5:3: row is 6 cells wide"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn yaml_error_reports_line_and_column() {
+        let source = LEVEL.replace("amount: 5", "amount: five");
+        let err = Level::load_str("test", &source).unwrap_err();
+        assert!(
+            err.to_string().starts_with("test:12:13: food[0].amount"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn queen_must_be_on_an_empty_cell() {
+        let source = LEVEL.replace("queen: [0, -2]", "queen: [0, -3]");
+        let err = Level::load_str("test", &source).unwrap_err();
+        assert!(err.to_string().starts_with("test:8: queen"), "{err}");
+    }
+}
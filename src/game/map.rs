@@ -1,8 +1,10 @@
-use crate::game::food::{CarryingFood, FoodState};
 use crate::game::food_types::FoodId;
-use crate::game::pathfinding::{SideMapGraph, VisitedNodeEvent};
+use crate::game::movement::{movement_weight, update_edges_around};
+use crate::game::pathfinding::{ChangedCells, SideMapGraph, VisitedNodeEvent};
 use crate::game::plugin::{PlayerState, FOOD_Z};
 use crate::game::positions::SideIPos;
+use crate::game::water::WaterLevels;
+use crate::game::zones::SpoilZones;
 use bevy::prelude::*;
 use bevy::utils::petgraph::prelude::EdgeRef;
 use bevy::utils::petgraph::visit::IntoEdgeReferences;
@@ -38,19 +40,69 @@ impl From<HashMap<SideIPos, Entity>> for SideMapPosToEntities {
     }
 }
 
+/// What the dirt in a cell is made of.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Hash)]
+pub enum CellMaterial {
+    #[default]
+    Dirt,
+    /// Quick to dig but falls in easily.
+    Sand,
+    Gravel,
+    /// Slow to dig but holds up wide chambers.
+    Clay,
+    /// Found near the surface. Needed for fungus chambers.
+    FertileSoil,
+}
+
+impl CellMaterial {
+    /// How much harder than dirt it is to dig and to walk through.
+    pub fn hardness(&self) -> f32 {
+        match self {
+            CellMaterial::Dirt => 1.0,
+            CellMaterial::Sand => 0.5,
+            CellMaterial::Gravel => 1.5,
+            CellMaterial::Clay => 2.5,
+            CellMaterial::FertileSoil => 0.8,
+        }
+    }
+
+    /// How well it holds up a ceiling compared to dirt. Sand never does.
+    pub fn strength(&self) -> f32 {
+        match self {
+            CellMaterial::Dirt => 1.0,
+            CellMaterial::Sand => 0.0,
+            CellMaterial::Gravel => 0.9,
+            CellMaterial::Clay => 2.0,
+            CellMaterial::FertileSoil => 0.85,
+        }
+    }
+
+    /// Multiplied with the dirt textures.
+    pub fn tint(&self) -> Color {
+        match self {
+            CellMaterial::Dirt => Color::WHITE,
+            CellMaterial::Sand => Color::rgb(1.0, 0.9, 0.6),
+            CellMaterial::Gravel => Color::rgb(0.7, 0.7, 0.75),
+            CellMaterial::Clay => Color::rgb(0.9, 0.55, 0.45),
+            CellMaterial::FertileSoil => Color::rgb(0.6, 0.5, 0.4),
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum CellType {
     // 0 means there's still one amount of dirt left before it's empty.
-    Dirt(u8),
+    Dirt(CellMaterial, u8),
     Empty,
     // Impassable
     Rock,
 }
 
 /// A dirt block. The u8 is the amount of dirt. 0 is empty.
-#[derive(Component, Copy, Clone, Debug)]
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
 pub struct CellContent {
-    /// Underground means creatures can walk on walls.
+    /// Underground means creatures can climb the walls. Above ground they need something to stand
+    /// on. See [crate::game::movement].
     underground: bool,
     cell_type: CellType,
 }
@@ -70,17 +122,14 @@ impl CellContent {
         }
     }
 
-    pub fn random_dirt() -> Self {
-        Self {
-            underground: true,
-            cell_type: CellType::Dirt(rand::random::<u8>()),
-        }
+    pub fn dirt(amount: u8) -> Self {
+        Self::dirt_of(CellMaterial::Dirt, amount)
     }
 
-    pub fn dirt(amount: u8) -> Self {
+    pub fn dirt_of(material: CellMaterial, amount: u8) -> Self {
         Self {
             underground: true,
-            cell_type: CellType::Dirt(amount),
+            cell_type: CellType::Dirt(material, amount),
         }
     }
 
@@ -91,19 +140,58 @@ impl CellContent {
         }
     }
 
-    pub fn dig(&mut self, amount: u8) {
-        if let CellType::Dirt(current) = self.cell_type {
-            if current > 0 {
-                self.cell_type = CellType::Dirt(current.saturating_sub(amount));
+    /// Returns how much dirt was removed.
+    pub fn dig(&mut self, amount: u8) -> u8 {
+        let CellType::Dirt(material, current) = self.cell_type else {
+            return 0;
+        };
+
+        let removed = current.min(amount);
+        self.cell_type = if current == removed {
+            // We just dug the last bit of dirt.
+            CellType::Empty
+        } else {
+            CellType::Dirt(material, current - removed)
+        };
+
+        removed
+    }
+
+    /// How much of `amount` can be dug at once, given how hard the material is.
+    pub fn dig_amount(&self, amount: f32) -> u8 {
+        match self.material() {
+            Some(material) => (amount / material.hardness()).round().max(1.0) as u8,
+            None => 0,
+        }
+    }
+
+    /// Returns how much dirt was added. Rock can't take any dirt and a cell can't hold more than
+    /// `u8::MAX`. Dirt packed into an existing wall takes on the material of the wall.
+    pub fn add_dirt(&mut self, material: CellMaterial, amount: u8) -> u8 {
+        let (material, current) = match self.cell_type {
+            CellType::Dirt(existing, current) => (existing, current),
+            CellType::Empty => (material, 0),
+            CellType::Rock => return 0,
+        };
 
-                if self.cell_type == CellType::Dirt(0) {
-                    // We just dug the last bit of dirt.
-                    self.cell_type = CellType::Empty;
-                }
-            } else {
-                self.cell_type = CellType::Empty;
-            }
+        let added = amount.min(u8::MAX - current);
+        if added > 0 {
+            self.cell_type = CellType::Dirt(material, current + added);
         }
+
+        added
+    }
+
+    /// None unless there's dirt in the cell.
+    pub fn material(&self) -> Option<CellMaterial> {
+        match self.cell_type {
+            CellType::Dirt(material, _) => Some(material),
+            _ => None,
+        }
+    }
+
+    pub fn is_underground(&self) -> bool {
+        self.underground
     }
 
     pub fn is_empty(&self) -> bool {
@@ -115,7 +203,7 @@ impl CellContent {
     }
 
     pub fn amount_left(&self) -> u8 {
-        if let CellType::Dirt(amount) = self.cell_type {
+        if let CellType::Dirt(_, amount) = self.cell_type {
             amount
         } else {
             0
@@ -129,153 +217,429 @@ impl CellContent {
         } else if self.is_rock() {
             None
         } else {
-            Some(self.amount_left() as u16 + 1)
+            let hardness = self.material().unwrap_or_default().hardness();
+            Some((self.amount_left() as f32 * hardness) as u16 + 1)
         }
     }
 
-    pub fn texture_path(&self) -> Option<String> {
+    /// The weight of the graph edge between two neighbouring cells. None if either is impassable.
+    pub fn edge_weight(&self, other: &CellContent) -> Option<u64> {
+        Some(self.weight()? as u64 + other.weight()? as u64)
+    }
+
+    pub fn tint(&self) -> Color {
+        self.material()
+            .map(|material| material.tint())
+            .unwrap_or(Color::WHITE)
+    }
+
+    pub fn texture_path(&self) -> Option<&'static str> {
         if self.is_empty() {
             None
         } else if self.is_rock() {
-            Some("cell/rock.png".to_string())
+            Some("cell/rock.png")
         } else if self.amount_left() > 127 {
-            Some("cell/full.png".to_string())
+            Some("cell/full.png")
         } else {
-            Some("cell/half.png".to_string())
+            Some("cell/half.png")
         }
     }
 }
 
+/// A rectangle of cells that isn't attached to the ECS, e.g. the output of the map generator.
+///
+/// Stored row by row from the bottom left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellGrid {
+    min: IVec2,
+    size: IVec2,
+    cells: Vec<CellContent>,
+}
+
+impl CellGrid {
+    pub fn new(min: IVec2, size: IVec2, fill: CellContent) -> Self {
+        Self {
+            min,
+            size,
+            cells: vec![fill; (size.x * size.y) as usize],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn contains(&self, pos: SideIPos) -> bool {
+        self.index(pos).is_some()
+    }
+
+    fn index(&self, pos: SideIPos) -> Option<usize> {
+        let local = *pos - self.min;
+        if local.x < 0 || local.y < 0 || local.x >= self.size.x || local.y >= self.size.y {
+            return None;
+        }
+
+        Some((local.y * self.size.x + local.x) as usize)
+    }
+
+    pub fn get(&self, pos: SideIPos) -> Option<&CellContent> {
+        self.index(pos).map(|index| &self.cells[index])
+    }
+
+    pub fn get_mut(&mut self, pos: SideIPos) -> Option<&mut CellContent> {
+        self.index(pos).map(|index| &mut self.cells[index])
+    }
+
+    /// Panics if the position is outside of the grid.
+    pub fn set(&mut self, pos: SideIPos, cell: CellContent) {
+        let index = self.index(pos).expect("Position outside of the grid");
+        self.cells[index] = cell;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SideIPos, &CellContent)> {
+        self.cells.iter().enumerate().map(|(index, cell)| {
+            let index = index as i32;
+            let pos = SideIPos::new(
+                self.min.x + index % self.size.x,
+                self.min.y + index / self.size.x,
+            );
+            (pos, cell)
+        })
+    }
+}
+
+/// How much dirt is dug out each time an ant walks through a cell, before hardness.
+const DIG_AMOUNT: f32 = 10.0;
+
+/// How much dirt an ant can carry. Once full it has to drop some before it can dig again.
+pub const SOIL_CAPACITY: u8 = 50;
+
+/// Dirt an ant has dug out and still has to put somewhere. An ant can only carry one material at
+/// a time.
+#[derive(Component, Debug, Default)]
+pub struct SoilLoad {
+    material: CellMaterial,
+    amount: u8,
+}
+
+impl SoilLoad {
+    pub fn is_empty(&self) -> bool {
+        self.amount == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.amount >= SOIL_CAPACITY
+    }
+
+    /// How much more of this material can be picked up. Nothing if carrying something else.
+    pub fn room_for(&self, material: CellMaterial) -> u8 {
+        if self.is_empty() || self.material == material {
+            SOIL_CAPACITY.saturating_sub(self.amount)
+        } else {
+            0
+        }
+    }
+
+    /// Check [Self::room_for] first, otherwise the material is replaced.
+    pub fn pick_up(&mut self, material: CellMaterial, amount: u8) {
+        self.material = material;
+        self.amount += amount;
+    }
+
+    /// Returns how much was dropped into the cell.
+    pub fn drop_into(&mut self, cell: &mut CellContent) -> u8 {
+        let added = cell.add_dirt(self.material, self.amount);
+        self.amount -= added;
+        added
+    }
+}
+
+/// Ants dig a little out of each underground cell they walk through and carry the dirt with them.
+///
+/// The dirt is dropped:
+///  * On a spoil zone, filling it back in.
+///  * On the surface, growing a mound above y = 0.
+///  * Packed into a neighbouring wall when the ant can't carry any more, or is carrying a different
+///    material to the one it's walking through.
 pub fn passive_dig_when_visiting_a_cell(
     side_map_pos_to_entities: Res<SideMapPosToEntities>,
-    mut query: Query<&mut CellContent>,
+    spoil_zones: Res<SpoilZones>,
+    mut cells: Query<&mut CellContent>,
+    mut soil_loads: Query<&mut SoilLoad>,
     mut visited_node_reader: EventReader<VisitedNodeEvent>,
     mut update_tile_rendering_writer: EventWriter<UpdateTileDirtAmountEvent>,
 ) {
     for event in visited_node_reader.iter() {
+        let Ok(mut soil_load) = soil_loads.get_mut(event.creature_entity) else {
+            continue;
+        };
+
         let Some(entity) = side_map_pos_to_entities.get(&event.position) else {
             warn!(?event, "No CellContent in side_map_pos_to_entities.");
             continue;
         };
 
-        let Ok(mut cell_content) = query.get_mut(*entity) else {
+        let Ok(mut cell_content) = cells.get_mut(*entity) else {
             warn!(?event, "Entity doesn't have cell content");
             continue;
         };
 
-        cell_content.dig(10);
+        // Only dig underground, otherwise ants would flatten the mounds they've built.
+        let is_underground = cell_content.is_underground();
+        let mut can_not_dig = soil_load.is_full();
+        if let (true, Some(material)) = (is_underground, cell_content.material()) {
+            let room = soil_load.room_for(material);
+            can_not_dig = room == 0;
+
+            let amount = cell_content.dig_amount(DIG_AMOUNT).min(room);
+            let dug = cell_content.dig(amount);
+            if dug > 0 {
+                soil_load.pick_up(material, dug);
+                update_tile_rendering_writer.send(UpdateTileDirtAmountEvent(*entity));
+            }
+        }
+
+        if soil_load.is_empty() {
+            continue;
+        }
+
+        let targets = if spoil_zones.contains(&event.position) {
+            vec![event.position]
+        } else if !is_underground {
+            mound_top(&side_map_pos_to_entities, &cells, event.position.x)
+                .into_iter()
+                .collect()
+        } else if can_not_dig || soil_load.is_full() {
+            event.position.sides().to_vec()
+        } else {
+            continue;
+        };
+
+        for target in targets {
+            let Some(target_entity) = side_map_pos_to_entities.get(&target) else {
+                continue;
+            };
+
+            let Ok(mut target_cell) = cells.get_mut(*target_entity) else {
+                continue;
+            };
+
+            // Walls only, so we don't fill in the tunnel we're walking through.
+            if target != event.position && target_cell.is_empty() {
+                continue;
+            }
+
+            if soil_load.drop_into(&mut target_cell) > 0 {
+                update_tile_rendering_writer.send(UpdateTileDirtAmountEvent(*target_entity));
+            }
+
+            if soil_load.is_empty() {
+                break;
+            }
+        }
+    }
+}
 
-        // TODO: "Move" the amount removed the the previous cell (and overflow outwards if that's not possible).
+/// The lowest cell above ground in this column that can still take some dirt.
+fn mound_top(
+    side_map_pos_to_entities: &SideMapPosToEntities,
+    cells: &Query<&mut CellContent>,
+    x: i32,
+) -> Option<SideIPos> {
+    let mut pos = SideIPos::new(x, 0);
+    loop {
+        let entity = side_map_pos_to_entities.get(&pos)?;
+        let cell = cells.get(*entity).ok()?;
+        if !cell.is_rock() && cell.amount_left() < u8::MAX {
+            return Some(pos);
+        }
 
-        update_tile_rendering_writer.send(UpdateTileDirtAmountEvent(*entity));
+        pos = SideIPos::new(x, pos.y + 1);
     }
 }
 
 pub fn detect_cell_content_changes_and_update_graph(
     mut debug_lines: ResMut<DebugLines>,
     mut graph: ResMut<SideMapGraph>,
+    mut changed_cells: ResMut<ChangedCells>,
     mut side_map_pos_to_entities: ResMut<SideMapPosToEntities>,
+    water_levels: Res<WaterLevels>,
     mut query: Query<(&CellContent, &SideIPos)>,
     mut update_tile_rendering_event: EventReader<UpdateTileDirtAmountEvent>,
 ) {
     for UpdateTileDirtAmountEvent(entity) in update_tile_rendering_event.iter() {
         // Grab the CellContent for this entity.
-        let Ok((cell, pos)) = query.get(*entity) else {
+        let Ok((_, pos)) = query.get(*entity) else {
             warn!(?entity, "Could not find CellContent for entity");
             continue;
         };
 
-        let Some(a_weight) = cell.weight() else {
-            warn!(?entity, ?pos, "Could not find weight for cell content");
-            continue;
+        let cell_at = |pos: SideIPos| {
+            let entity = side_map_pos_to_entities.get(&pos)?;
+            query.get(*entity).ok().map(|(cell, _)| *cell)
         };
 
-        // Work out the neighbours and update the edge values
-        for neighbour in pos.sides() {
-            let Some(other_entity) = side_map_pos_to_entities.get(&neighbour) else {
-                continue;
-            };
+        // Digging out a cell can leave its neighbours with nothing to hold on to, so their edges
+        // are worked out again too. Most changes, like a little dirt being added to a wall, leave
+        // the edges as they were, so the graph is only marked changed when one of them moved.
+        let changed = update_edges_around(graph.bypass_change_detection(), *pos, |from, to| {
+            let weight = movement_weight(from, to, &cell_at)?;
+            Some(weight + water_levels.extra_weight(&from) + water_levels.extra_weight(&to))
+        });
+        if !changed.is_empty() {
+            graph.set_changed();
+            changed_cells.extend(changed);
+        }
+    }
+}
 
-            let Ok((other_cell, _)) = query.get(*other_entity) else {
-                continue;
-            };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digging_empties_the_cell() {
+        let mut cell = CellContent::dirt_of(CellMaterial::Clay, 15);
+        assert_eq!(cell.dig(10), 10);
+        assert_eq!(cell, CellContent::dirt_of(CellMaterial::Clay, 5));
+        assert_eq!(cell.dig(10), 5);
+        assert!(cell.is_empty());
+        assert_eq!(cell.dig(10), 0);
+
+        let mut rock = CellContent::rock(true);
+        assert_eq!(rock.dig(10), 0);
+        assert!(rock.is_rock());
+    }
 
-            let Some(b_weight) = other_cell.weight() else {
-                continue;
-            };
+    #[test]
+    fn added_dirt_takes_on_the_wall_material() {
+        let mut tunnel = CellContent::empty_underground();
+        assert_eq!(tunnel.add_dirt(CellMaterial::Sand, 20), 20);
+        assert_eq!(tunnel.material(), Some(CellMaterial::Sand));
 
-            // TODO: This is similar to the map generation code, so we should probably factor it out.
-            let weight = a_weight as u64 + b_weight as u64;
-            // graph.add_edge(*pos, neighbour, weight as u64);
-            graph.edge_weight_mut(*pos, neighbour).map(|w| *w = weight);
-        }
+        let mut wall = CellContent::dirt_of(CellMaterial::Clay, 250);
+        assert_eq!(wall.add_dirt(CellMaterial::Sand, 20), 5);
+        assert_eq!(wall, CellContent::dirt_of(CellMaterial::Clay, u8::MAX));
+
+        let mut rock = CellContent::rock(true);
+        assert_eq!(rock.add_dirt(CellMaterial::Dirt, 20), 0);
     }
-}
 
-pub fn update_tile_rendering(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    query: Query<(Entity, &CellContent)>,
-    mut update_tile_rendering_reader: EventReader<UpdateTileDirtAmountEvent>,
-) {
-    for UpdateTileDirtAmountEvent(entity) in update_tile_rendering_reader.iter() {
-        let Ok((entity, cell_content)) = query.get(*entity) else {
-            error!(?entity, "Could not find entity in query");
-            continue;
-        };
+    #[test]
+    fn soil_load_carries_one_material() {
+        let mut load = SoilLoad::default();
+        assert_eq!(load.room_for(CellMaterial::Sand), SOIL_CAPACITY);
 
-        let mut entity = commands.entity(entity);
+        load.pick_up(CellMaterial::Sand, 40);
+        assert_eq!(load.room_for(CellMaterial::Sand), SOIL_CAPACITY - 40);
+        assert_eq!(load.room_for(CellMaterial::Clay), 0);
 
-        if let Some(texture_path) = cell_content.texture_path() {
-            let image_handle: Handle<Image> = asset_server.load(texture_path);
-            entity.insert(image_handle);
-        } else {
-            entity.remove::<Handle<Image>>();
-        }
+        let mut wall = CellContent::dirt(250);
+        assert_eq!(load.drop_into(&mut wall), 5);
+        assert_eq!(load.room_for(CellMaterial::Sand), SOIL_CAPACITY - 35);
+
+        let mut tunnel = CellContent::empty_underground();
+        assert_eq!(load.drop_into(&mut tunnel), 35);
+        assert!(load.is_empty());
+        assert_eq!(load.room_for(CellMaterial::Clay), SOIL_CAPACITY);
     }
-}
 
-/// A tag to indicate that this entity is a child of a food cell.
-#[derive(Component)]
-pub struct ChildCellForFood;
-
-pub fn update_food_tile_rendering(
-    mut commands: Commands,
-    food_state: Res<FoodState>,
-    side_map_to_entities: Res<SideMapPosToEntities>,
-    asset_server: Res<AssetServer>,
-    tiles: Query<(Entity, &SideIPos, Option<&Children>), Changed<TileNeedsFoodRenderingUpdate>>,
-    child_food_cells: Query<&ChildCellForFood>,
-) {
-    for (entity, pos, maybe_children) in &tiles {
-        // Remove the child cells because we might re-add them.
-        info!("Removing child food cells");
-        if let Some(child_food_cell) = maybe_children {
-            info!("Removing child food cells 1");
-            for child in child_food_cell.iter() {
-                info!("Removing child food cells 2");
-                if let Ok(_) = child_food_cells.get(*child) {
-                    info!("Removing child food cells 3");
-                    commands.entity(*child).despawn_recursive();
-                }
-            }
+    /// Runs [passive_dig_when_visiting_a_cell] once for an ant walking into `position`, and returns
+    /// the cells afterwards.
+    fn visit(
+        cells: &[(SideIPos, CellContent)],
+        spoil: &[SideIPos],
+        load: SoilLoad,
+        position: SideIPos,
+    ) -> (HashMap<SideIPos, CellContent>, SoilLoad) {
+        let mut world = World::new();
+        let mut schedule = Schedule::new();
+        schedule.add_system(passive_dig_when_visiting_a_cell);
+
+        let mut pos_to_entities = HashMap::new();
+        for (pos, cell) in cells {
+            pos_to_entities.insert(*pos, world.spawn(*cell).id());
         }
+        let mut spoil_zones = SpoilZones::default();
+        for pos in spoil {
+            spoil_zones.add(*pos);
+        }
+        world.insert_resource(SideMapPosToEntities::from(pos_to_entities.clone()));
+        world.insert_resource(spoil_zones);
+        world.init_resource::<Events<VisitedNodeEvent>>();
+        world.init_resource::<Events<UpdateTileDirtAmountEvent>>();
+
+        let ant = world.spawn(load).id();
+        world
+            .resource_mut::<Events<VisitedNodeEvent>>()
+            .send(VisitedNodeEvent {
+                creature_entity: ant,
+                position,
+                is_final: false,
+            });
+        schedule.run(&mut world);
+
+        let cells = pos_to_entities
+            .into_iter()
+            .map(|(pos, entity)| (pos, *world.get::<CellContent>(entity).unwrap()))
+            .collect();
+        let load = world.entity_mut(ant).take::<SoilLoad>().unwrap();
+        (cells, load)
+    }
 
-        let Some(food_cell) = food_state.info_at_position(pos) else {
-            info!("No food cell at {:?}", pos);
-            continue;
-        };
-
-        info!("Adding child food cell");
+    #[test]
+    fn ants_dig_out_the_cells_they_walk_through() {
+        let pos = SideIPos::new(0, -2);
+        let (cells, load) = visit(
+            &[(pos, CellContent::dirt(100))],
+            &[],
+            SoilLoad::default(),
+            pos,
+        );
+
+        assert_eq!(cells[&pos], CellContent::dirt(100 - DIG_AMOUNT as u8));
+        assert_eq!(
+            load.room_for(CellMaterial::Dirt),
+            SOIL_CAPACITY - DIG_AMOUNT as u8
+        );
+    }
 
-        let child = commands
-            .spawn(SpriteBundle {
-                texture: asset_server.load("food/food.png"),
-                ..Default::default()
-            })
-            .insert(ChildCellForFood)
-            .id();
+    #[test]
+    fn full_ants_pack_dirt_into_the_walls() {
+        let pos = SideIPos::new(0, -2);
+        let mut load = SoilLoad::default();
+        load.pick_up(CellMaterial::Sand, SOIL_CAPACITY);
+        let (cells, load) = visit(
+            &[
+                (pos, CellContent::empty_underground()),
+                (SideIPos::new(-1, -2), CellContent::dirt(250)),
+                (SideIPos::new(1, -2), CellContent::empty_underground()),
+            ],
+            &[],
+            load,
+            pos,
+        );
+
+        // The wall is topped up, and the tunnel on the other side is left open.
+        assert_eq!(cells[&SideIPos::new(-1, -2)], CellContent::dirt(u8::MAX));
+        assert!(cells[&SideIPos::new(1, -2)].is_empty());
+        assert!(cells[&pos].is_empty());
+        assert_eq!(load.room_for(CellMaterial::Sand), 5);
+    }
 
-        commands.entity(entity).push_children(&[child]);
+    #[test]
+    fn ants_drop_everything_on_a_spoil_zone() {
+        let pos = SideIPos::new(0, -2);
+        let mut load = SoilLoad::default();
+        load.pick_up(CellMaterial::Clay, 20);
+        let (cells, load) = visit(
+            &[(pos, CellContent::empty_underground())],
+            &[pos],
+            load,
+            pos,
+        );
+
+        assert_eq!(cells[&pos], CellContent::dirt_of(CellMaterial::Clay, 20));
+        assert!(load.is_empty());
     }
 }
@@ -0,0 +1,414 @@
+//! Procedural side map generation.
+//!
+//! The generator only produces a [CellGrid] of [CellContent]s so it can run without spawning
+//! anything. [crate::game::setup::setup_map] turns the grid into entities and the graph.
+//!
+//! The map is generated one chunk at a time, each with its own random number generator, so more of
+//! the map can be generated later as the colony grows. The same seed and params will always
+//! generate the same map, no matter which order the chunks are generated in.
+
+use crate::game::chunks::{chunk_min, chunk_of, CHUNK_SIZE};
+use crate::game::map::{CellContent, CellGrid, CellMaterial};
+use crate::game::positions::SideIPos;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Set this environment variable to reproduce a map someone else has seen.
+const MAP_SEED_ENV: &str = "QUEEN_MAP_SEED";
+
+/// Set this environment variable to start on a much bigger map, to check rendering performance.
+const STRESS_ENV: &str = "QUEEN_STRESS";
+
+#[derive(Resource, Deref, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MapSeed(u64);
+
+impl MapSeed {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Use the seed from `QUEEN_MAP_SEED` if it is set, otherwise a random one.
+    pub fn from_env_or_random() -> Self {
+        let from_env = std::env::var(MAP_SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse::<u64>().ok());
+
+        Self::new(from_env.unwrap_or_else(rand::random))
+    }
+}
+
+/// How the amount of dirt changes away from the surface origin.
+///
+/// We want a V shape around the origin so that ants are initially biased towards the middle and
+/// not dig new holes.
+#[derive(Debug, Clone)]
+pub struct DirtGradient {
+    /// Dirt added for every row below the surface.
+    pub per_depth: f32,
+
+    /// Dirt added for every column away from x = 0.
+    pub per_distance: f32,
+
+    /// A random amount of dirt from 0 to this is added to each cell.
+    pub noise: f32,
+
+    /// Added to the noise, so a negative value can make some cells lighter than the gradient.
+    pub noise_offset: f32,
+
+    /// Rows below the surface that are still mostly packed when the gradient is saturated.
+    pub topsoil_depth: i32,
+}
+
+impl Default for DirtGradient {
+    fn default() -> Self {
+        Self {
+            per_depth: 10.0,
+            per_distance: 30.0,
+            noise: 50.0,
+            noise_offset: -20.0,
+            topsoil_depth: 5,
+        }
+    }
+}
+
+/// A layer of one material, from the band above it down to `bottom` rows below the surface.
+#[derive(Debug, Clone)]
+pub struct MaterialBand {
+    pub material: CellMaterial,
+    pub bottom: i32,
+}
+
+impl MaterialBand {
+    pub fn new(material: CellMaterial, bottom: i32) -> Self {
+        Self { material, bottom }
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct MapGeneratorParams {
+    /// Number of columns generated at the start, centred on x = 0. Rounded out to whole chunks.
+    pub width: i32,
+
+    /// Number of underground rows generated at the start, i.e. y < 0. Rounded out to whole chunks.
+    pub depth: i32,
+
+    /// Number of rows of air from the surface upwards, i.e. y >= 0. Chunks entirely above this are
+    /// never generated.
+    pub surface_height: i32,
+
+    pub dirt_gradient: DirtGradient,
+
+    /// From the surface down. Anything below the last band is the same material as it.
+    pub material_bands: Vec<MaterialBand>,
+
+    /// Chance of a deep dirt cell starting a rock vein. 0 - 1.
+    pub rock_vein_frequency: f32,
+
+    /// How many cells a rock vein wanders through, including where it starts.
+    pub rock_vein_size: u32,
+
+    pub queen_position: SideIPos,
+    pub queen_chamber_size: IVec2,
+}
+
+impl MapGeneratorParams {
+    /// Use [Self::stress] if `QUEEN_STRESS` is set, otherwise the defaults.
+    pub fn from_env() -> Self {
+        if std::env::var(STRESS_ENV).is_ok() {
+            Self::stress()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// A 256 x 256 map.
+    pub fn stress() -> Self {
+        Self {
+            width: 256,
+            depth: 236,
+            ..Default::default()
+        }
+    }
+
+    pub fn min_x(&self) -> i32 {
+        -self.width / 2
+    }
+
+    pub fn max_x(&self) -> i32 {
+        self.min_x() + self.width - 1
+    }
+
+    pub fn material_at(&self, y: i32) -> CellMaterial {
+        let depth = -y;
+        self.material_bands
+            .iter()
+            .find(|band| depth <= band.bottom)
+            .or(self.material_bands.last())
+            .map(|band| band.material)
+            .unwrap_or_default()
+    }
+
+    fn is_queen_chamber(&self, pos: SideIPos) -> bool {
+        let queen = self.queen_position;
+        let half_width = self.queen_chamber_size.x / 2;
+
+        pos.x >= queen.x - half_width
+            && pos.x <= queen.x + half_width
+            && pos.y >= queen.y
+            && pos.y < queen.y + self.queen_chamber_size.y
+    }
+}
+
+impl Default for MapGeneratorParams {
+    fn default() -> Self {
+        Self {
+            width: 41,
+            depth: 30,
+            surface_height: 20,
+            dirt_gradient: DirtGradient::default(),
+            material_bands: vec![
+                MaterialBand::new(CellMaterial::FertileSoil, 5),
+                MaterialBand::new(CellMaterial::Dirt, 15),
+                MaterialBand::new(CellMaterial::Gravel, 25),
+                MaterialBand::new(CellMaterial::Sand, 35),
+                MaterialBand::new(CellMaterial::Clay, 50),
+            ],
+            rock_vein_frequency: 5.0 / 256.0,
+            rock_vein_size: 3,
+            queen_position: SideIPos::new(0, -20),
+            queen_chamber_size: IVec2::new(3, 3),
+        }
+    }
+}
+
+pub struct MapGenerator {
+    seed: MapSeed,
+    params: MapGeneratorParams,
+}
+
+impl MapGenerator {
+    pub fn new(seed: MapSeed, params: MapGeneratorParams) -> Self {
+        Self { seed, params }
+    }
+
+    /// The chunks covering the starting area.
+    pub fn initial_chunks(&self) -> impl Iterator<Item = SideIPos> {
+        let params = &self.params;
+        let min = chunk_of(SideIPos::new(params.min_x(), -params.depth));
+        let max = chunk_of(SideIPos::new(params.max_x(), params.surface_height - 1));
+
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| SideIPos::new(x, y)))
+    }
+
+    /// The starting area, made up of all of the [Self::initial_chunks].
+    pub fn generate(&self) -> CellGrid {
+        let chunks: Vec<SideIPos> = self.initial_chunks().collect();
+        let min = chunk_min(chunks[0]);
+        let max = chunk_min(chunks[chunks.len() - 1]) + CHUNK_SIZE;
+        let mut grid = CellGrid::new(min, max - min, CellContent::empty_air());
+
+        for chunk in chunks {
+            for (pos, cell) in self.generate_chunk(chunk).iter() {
+                grid.set(pos, *cell);
+            }
+        }
+
+        grid
+    }
+
+    /// Whether this chunk should ever be generated, i.e. it isn't only sky.
+    pub fn can_generate(&self, chunk: SideIPos) -> bool {
+        chunk_min(chunk).y < self.params.surface_height
+    }
+
+    pub fn generate_chunk(&self, chunk: SideIPos) -> CellGrid {
+        let params = &self.params;
+        let mut rng = StdRng::seed_from_u64(self.chunk_seed(chunk));
+
+        let min = chunk_min(chunk);
+        let mut grid = CellGrid::new(min, IVec2::splat(CHUNK_SIZE), CellContent::empty_air());
+
+        let mut vein_starts = Vec::new();
+
+        // Iterate in a fixed order so the random number sequence is the same for each seed.
+        for y in min.y..(min.y + CHUNK_SIZE).min(0) {
+            for x in min.x..min.x + CHUNK_SIZE {
+                let pos = SideIPos::new(x, y);
+
+                let cell_content = if params.is_queen_chamber(pos) {
+                    CellContent::empty_underground()
+                } else {
+                    self.dirt_at(&mut rng, pos, &mut vein_starts)
+                };
+
+                grid.set(pos, cell_content);
+            }
+        }
+
+        for start in vein_starts {
+            self.grow_rock_vein(&mut rng, &mut grid, start);
+        }
+
+        grid
+    }
+
+    fn chunk_seed(&self, chunk: SideIPos) -> u64 {
+        let key = ((chunk.x as u32 as u64) << 32) | chunk.y as u32 as u64;
+        *self.seed ^ key.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn dirt_at(
+        &self,
+        rng: &mut StdRng,
+        pos: SideIPos,
+        vein_starts: &mut Vec<SideIPos>,
+    ) -> CellContent {
+        let gradient = &self.params.dirt_gradient;
+        let material = self.params.material_at(pos.y);
+
+        // e.g. the top row (at x == 0, y = -1) should have very light dirt, getting harder further
+        // down and further away from the middle.
+        let forced_dirt_amount =
+            pos.y.abs() as f32 * gradient.per_depth + pos.x.abs() as f32 * gradient.per_distance;
+        let forced_dirt_amount =
+            forced_dirt_amount + rng.gen::<f32>() * gradient.noise + gradient.noise_offset;
+        let forced_dirt_amount = forced_dirt_amount.max(0.0) as u64;
+
+        if forced_dirt_amount > 0u64 && forced_dirt_amount < 255u64 {
+            CellContent::dirt_of(material, forced_dirt_amount as u8)
+        } else if pos.y >= -gradient.topsoil_depth {
+            let amount = 255f32
+                - rng.gen::<f32>() * (255.0 / gradient.topsoil_depth as f32) * pos.y.abs() as f32;
+            CellContent::dirt_of(material, amount as u8)
+        } else {
+            if rng.gen::<f32>() < self.params.rock_vein_frequency {
+                vein_starts.push(pos);
+            }
+            CellContent::dirt_of(material, rng.gen::<u8>())
+        }
+    }
+
+    /// Random walk from the start, turning dirt into rock. Veins never replace empty cells so the
+    /// queen chamber stays open, and stay inside the chunk.
+    fn grow_rock_vein(&self, rng: &mut StdRng, grid: &mut CellGrid, start: SideIPos) {
+        let mut pos = start;
+        for _ in 0..self.params.rock_vein_size {
+            if let Some(cell) = grid.get_mut(pos) {
+                if !cell.is_empty() {
+                    *cell = CellContent::rock(true);
+                }
+            }
+
+            let sides = pos.sides();
+            let next = sides[rng.gen_range(0..sides.len())];
+            if next.y >= -self.params.dirt_gradient.topsoil_depth || !grid.contains(next) {
+                continue;
+            }
+            pos = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(seed: u64) -> CellGrid {
+        MapGenerator::new(MapSeed::new(seed), MapGeneratorParams::default()).generate()
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        assert_eq!(generate(1234), generate(1234));
+    }
+
+    #[test]
+    fn different_seed_different_map() {
+        assert_ne!(generate(1234), generate(4321));
+    }
+
+    #[test]
+    fn size_covers_params_in_whole_chunks() {
+        let grid = generate(1);
+
+        // 41 x 50 around the origin, rounded out to 16 x 16 chunks.
+        assert_eq!(grid.len(), 64 * 64);
+        assert!(grid.contains(SideIPos::new(-20, -30)));
+        assert!(grid.contains(SideIPos::new(20, 19)));
+        assert!(grid.contains(SideIPos::new(-32, -32)));
+        assert!(grid.contains(SideIPos::new(31, 31)));
+        assert!(!grid.contains(SideIPos::new(32, 0)));
+        assert!(!grid.contains(SideIPos::new(0, -33)));
+    }
+
+    #[test]
+    fn chunks_match_the_initial_map() {
+        let generator = MapGenerator::new(MapSeed::new(7), MapGeneratorParams::default());
+        let grid = generator.generate();
+
+        for chunk in [
+            SideIPos::new(-1, -1),
+            SideIPos::new(1, -2),
+            SideIPos::new(0, 1),
+        ] {
+            for (pos, cell) in generator.generate_chunk(chunk).iter() {
+                assert_eq!(grid.get(pos), Some(cell), "{pos:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_can_be_generated_outside_the_initial_map() {
+        let generator = MapGenerator::new(MapSeed::new(7), MapGeneratorParams::default());
+        let chunk = generator.generate_chunk(SideIPos::new(-5, -9));
+
+        assert_eq!(chunk.len(), 256);
+        assert!(chunk.iter().all(|(_, cell)| !cell.is_empty()));
+        assert!(generator.can_generate(SideIPos::new(100, -100)));
+        assert!(!generator.can_generate(SideIPos::new(0, 2)));
+    }
+
+    #[test]
+    fn surface_and_queen_chamber_are_empty() {
+        let params = MapGeneratorParams::default();
+        let grid = generate(99);
+
+        for (pos, cell) in grid.iter() {
+            if pos.y >= 0 || params.is_queen_chamber(pos) {
+                assert!(cell.is_empty(), "{pos:?} should be empty");
+            }
+        }
+        assert!(grid.get(params.queen_position).unwrap().is_empty());
+    }
+
+    #[test]
+    fn materials_follow_depth_bands() {
+        let params = MapGeneratorParams::default();
+        let grid = generate(3);
+
+        let material = |y| {
+            grid.get(SideIPos::new(10, y))
+                .and_then(|cell| cell.material())
+        };
+        assert_eq!(material(-1), Some(CellMaterial::FertileSoil));
+        assert_eq!(material(-5), Some(CellMaterial::FertileSoil));
+        assert_eq!(material(-6), Some(CellMaterial::Dirt));
+        assert_eq!(params.material_at(-30), CellMaterial::Sand);
+        assert_eq!(params.material_at(-500), CellMaterial::Clay);
+    }
+
+    #[test]
+    fn rock_veins_follow_frequency() {
+        let mut params = MapGeneratorParams {
+            rock_vein_frequency: 0.0,
+            ..Default::default()
+        };
+        let grid = MapGenerator::new(MapSeed::new(5), params.clone()).generate();
+        assert!(grid.iter().all(|(_, cell)| !cell.is_rock()));
+
+        params.rock_vein_frequency = 1.0;
+        let grid = MapGenerator::new(MapSeed::new(5), params).generate();
+        assert!(grid.iter().any(|(_, cell)| cell.is_rock()));
+    }
+}
@@ -0,0 +1,308 @@
+//! Picks what each ant does next, replacing a fixed [Idea] with a choice between several.
+//!
+//! A [Mind] holds a [Choice] for each thing an ant could do, each scored 0 - 1 every frame by its
+//! [Scorer]. When the ant's idea finishes, the best scoring choice becomes its next idea. While an
+//! idea runs, another choice scoring more than [MindSettings::preempt_margin] above it aborts it, so
+//! a hungry or endangered ant drops what it's doing.
+
+use crate::game::ants::AntType;
+use crate::game::behaviours::Behaviours;
+use crate::game::food::FoodState;
+use crate::game::hunger::Hunger;
+use crate::game::jobs::JobBoard;
+use crate::game::pheromones::{PheromoneChannel, Pheromones};
+use crate::game::positions::SideIPos;
+use crate::game::simple_brain::Idea;
+use crate::game::time::GameTime;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Resource, Debug)]
+pub struct MindSettings {
+    /// How much higher another choice has to score to abort the running idea.
+    pub preempt_margin: f32,
+
+    /// Working scores this for ants that have a job on the board or can start their own work, and
+    /// jobs from the board keep it while they run. It scores 0 when there's nothing to do.
+    pub work_score: f32,
+
+    /// Resting scores this when there's nothing better to do.
+    pub idle_rest_score: f32,
+
+    /// Resting scores this for a while after an idea was given up on, so ants don't retry straight
+    /// away.
+    pub rest_after_abort_score: f32,
+    pub rest_after_abort: Duration,
+
+    /// Danger pheromone at this strength or more in the ant's cell scores 1.
+    pub full_danger_strength: f32,
+}
+
+impl Default for MindSettings {
+    fn default() -> Self {
+        Self {
+            preempt_margin: 0.3,
+            work_score: 0.45,
+            idle_rest_score: 0.1,
+            rest_after_abort_score: 0.8,
+            rest_after_abort: Duration::from_secs(5),
+            full_danger_strength: 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum Scorer {
+    Hunger,
+    Work,
+    Rest,
+    Danger,
+}
+
+/// Something an ant could do, and how much it wants to.
+#[derive(Debug)]
+pub struct Choice {
+    scorer: Scorer,
+
+    /// The name of a sequence in [Behaviours].
+    sequence: String,
+    score: f32,
+}
+
+impl Choice {
+    pub fn new(scorer: Scorer, sequence: String) -> Self {
+        Self {
+            scorer,
+            sequence,
+            score: 0.0,
+        }
+    }
+}
+
+/// Why the running idea was started.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Running {
+    /// One of the mind's own choices, scored every frame.
+    Choice(Scorer),
+
+    /// Started outside of the mind, like a job from the board, with a fixed score.
+    Outside(f32),
+}
+
+#[derive(Component)]
+pub struct Mind {
+    choices: Vec<Choice>,
+
+    /// Why the running idea was started. Nothing for the empty idea an ant spawns with.
+    current: Option<Running>,
+
+    /// The running idea is being aborted for something better, rather than given up on.
+    preempting: bool,
+
+    /// When the ant last gave up on an idea.
+    last_abort: Option<Duration>,
+}
+
+impl Mind {
+    pub fn new(choices: Vec<Choice>) -> Self {
+        Self {
+            choices,
+            current: None,
+            preempting: false,
+            last_abort: None,
+        }
+    }
+
+    /// Everything an ant of this type could do, from its caste in [Behaviours].
+    pub fn for_ant(behaviours: &Behaviours, ant_type: AntType) -> Self {
+        Self::new(behaviours.choices(ant_type))
+    }
+
+    fn started(&mut self, running: Running) {
+        self.current = Some(running);
+        self.preempting = false;
+    }
+
+    /// Note an idea that was started outside of the mind, and how much the ant wants to do it.
+    pub fn started_outside(&mut self, score: f32) {
+        self.started(Running::Outside(score));
+    }
+
+    pub fn score(&self, scorer: Scorer) -> f32 {
+        self.choices
+            .iter()
+            .find(|choice| choice.scorer == scorer)
+            .map(|choice| choice.score)
+            .unwrap_or_default()
+    }
+
+    fn best(&self) -> Option<&Choice> {
+        self.choices
+            .iter()
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+    }
+
+    /// A choice that scores enough higher than the running idea to abort it.
+    fn preempting_choice(&self, margin: f32) -> Option<Scorer> {
+        let best = self.best()?;
+        let current = match self.current {
+            Some(Running::Choice(scorer)) if scorer == best.scorer => return None,
+            Some(Running::Choice(scorer)) => self.score(scorer),
+            Some(Running::Outside(score)) => score,
+            None => 0.0,
+        };
+        if best.score <= current + margin {
+            return None;
+        }
+
+        Some(best.scorer)
+    }
+}
+
+/// Score every choice of every ant.
+pub fn score_choices(
+    time: Res<GameTime>,
+    settings: Res<MindSettings>,
+    behaviours: Res<Behaviours>,
+    job_board: Res<JobBoard>,
+    food_state: Res<FoodState>,
+    pheromones: Res<Pheromones>,
+    mut query: Query<(&AntType, &Transform, &Hunger, &mut Mind)>,
+) {
+    let now = time.since_startup();
+
+    for (ant_type, transform, hunger, mut mind) in &mut query {
+        let pos = SideIPos::from(transform);
+        let has_job = job_board.next_for(*ant_type, pos).is_some();
+
+        let danger = pheromones.strength(pos, PheromoneChannel::Danger);
+        let just_aborted = mind
+            .last_abort
+            .map(|last_abort| now < last_abort + settings.rest_after_abort)
+            .unwrap_or(false);
+
+        for choice in &mut mind.choices {
+            choice.score = match choice.scorer {
+                Scorer::Hunger => hunger.hunger_score(),
+                Scorer::Work
+                    if has_job || behaviours.can_start(&choice.sequence, &food_state, hunger) =>
+                {
+                    settings.work_score
+                }
+                Scorer::Work => 0.0,
+                Scorer::Rest if just_aborted => settings.rest_after_abort_score,
+                Scorer::Rest => settings.idle_rest_score,
+                Scorer::Danger => (danger / settings.full_danger_strength).min(1.0),
+            };
+        }
+    }
+}
+
+/// Give ants that have finished their idea the best scoring choice, and abort ideas when something
+/// else scores much higher.
+pub fn think(
+    time: Res<GameTime>,
+    settings: Res<MindSettings>,
+    behaviours: Res<Behaviours>,
+    mut query: Query<(Entity, &mut Mind, &mut Idea)>,
+) {
+    for (entity, mut mind, mut idea) in &mut query {
+        if idea.is_finished() {
+            if idea.did_abort() && !mind.preempting {
+                mind.last_abort = Some(time.since_startup());
+            }
+
+            let Some(best) = mind.best() else {
+                continue;
+            };
+
+            let scorer = best.scorer;
+            *idea = Idea::from(behaviours.sequence(&best.sequence));
+            mind.started(Running::Choice(scorer));
+            debug!(?entity, ?scorer, "Picked a new idea");
+            continue;
+        }
+
+        if mind.preempting {
+            continue;
+        }
+
+        if let Some(scorer) = mind.preempting_choice(settings.preempt_margin) {
+            info!(?entity, ?scorer, current = ?mind.current, "Dropping idea for something better");
+            idea.request_cancel();
+            mind.preempting = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_score(mind: &mut Mind, scorer: Scorer, score: f32) {
+        for choice in &mut mind.choices {
+            if choice.scorer == scorer {
+                choice.score = score;
+            }
+        }
+    }
+
+    fn mind() -> Mind {
+        Mind::new(vec![
+            Choice::new(Scorer::Hunger, "EatFood".to_string()),
+            Choice::new(Scorer::Work, "Dig".to_string()),
+            Choice::new(Scorer::Rest, "Rest".to_string()),
+        ])
+    }
+
+    #[test]
+    fn best_choice_is_picked() {
+        let mut mind = mind();
+        set_score(&mut mind, Scorer::Hunger, 0.2);
+        set_score(&mut mind, Scorer::Work, 0.45);
+        set_score(&mut mind, Scorer::Rest, 0.1);
+        assert_eq!(mind.best().unwrap().scorer, Scorer::Work);
+
+        set_score(&mut mind, Scorer::Hunger, 0.6);
+        assert_eq!(mind.best().unwrap().scorer, Scorer::Hunger);
+    }
+
+    #[test]
+    fn only_clearly_better_choices_preempt() {
+        let margin = MindSettings::default().preempt_margin;
+        let mut mind = mind();
+        mind.started(Running::Choice(Scorer::Work));
+        set_score(&mut mind, Scorer::Work, 0.45);
+
+        set_score(&mut mind, Scorer::Hunger, 0.6);
+        assert_eq!(mind.preempting_choice(margin), None);
+
+        set_score(&mut mind, Scorer::Hunger, 0.9);
+        assert_eq!(mind.preempting_choice(margin), Some(Scorer::Hunger));
+
+        mind.current = None;
+        assert_eq!(mind.preempting_choice(margin), Some(Scorer::Hunger));
+        mind.started(Running::Choice(Scorer::Hunger));
+        assert_eq!(mind.preempting_choice(margin), None);
+    }
+
+    #[test]
+    fn nurse_jobs_keep_their_score() {
+        let source = include_str!("../../assets/behaviours.yaml");
+        let behaviours = Behaviours::load_str("behaviours.yaml", source).unwrap();
+        let settings = MindSettings::default();
+        let mut mind = Mind::for_ant(&behaviours, AntType::Nurse);
+        assert_eq!(mind.score(Scorer::Work), 0.0);
+
+        mind.started_outside(settings.work_score);
+        set_score(&mut mind, Scorer::Hunger, 0.6);
+        assert_eq!(mind.preempting_choice(settings.preempt_margin), None);
+
+        set_score(&mut mind, Scorer::Hunger, 0.9);
+        assert_eq!(
+            mind.preempting_choice(settings.preempt_margin),
+            Some(Scorer::Hunger)
+        );
+    }
+}
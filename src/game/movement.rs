@@ -0,0 +1,237 @@
+//! Where creatures can move on the side map, which decides the edges of the [SideMapGraph].
+//!
+//! Dirt can always be dug through, but empty cells need something to hold on to:
+//!  * Above ground a creature needs support beneath it, so there's no flying through the sky.
+//!  * Underground a creature can climb any wall, so any solid cell next to it will do.
+//!
+//! Moving up or down costs more than walking along.
+
+use crate::game::map::{CellContent, CellGrid};
+use crate::game::pathfinding::SideMapGraph;
+use crate::game::positions::SideIPos;
+use bevy::utils::HashSet;
+
+/// Extra weight for moving up or down between two cells.
+pub const CLIMB_WEIGHT: u64 = 3;
+
+/// Cells outside of the map, e.g. chunks that haven't been generated yet, count as solid.
+pub fn is_solid(cell: Option<CellContent>) -> bool {
+    cell.map(|cell| !cell.is_empty()).unwrap_or(true)
+}
+
+/// Whether a creature can be in this cell.
+pub fn can_stand_in(pos: SideIPos, cell_at: &impl Fn(SideIPos) -> Option<CellContent>) -> bool {
+    let Some(cell) = cell_at(pos) else {
+        return false;
+    };
+
+    if cell.is_rock() {
+        return false;
+    }
+
+    if !cell.is_empty() {
+        return true;
+    }
+
+    if cell.is_underground() {
+        pos.sides().into_iter().any(|side| is_solid(cell_at(side)))
+    } else {
+        // Diagonals too, so creatures can hang on to the lip of a hole.
+        (-1..=1).any(|dx| is_solid(cell_at(SideIPos::new(pos.x + dx, pos.y - 1))))
+    }
+}
+
+/// The weight of the graph edge between two neighbouring cells. None if a creature can't move
+/// between them.
+pub fn movement_weight(
+    from: SideIPos,
+    to: SideIPos,
+    cell_at: &impl Fn(SideIPos) -> Option<CellContent>,
+) -> Option<u64> {
+    if !can_stand_in(from, cell_at) || !can_stand_in(to, cell_at) {
+        return None;
+    }
+
+    let weight = cell_at(from)?.edge_weight(&cell_at(to)?)?;
+    if from.y != to.y {
+        Some(weight + CLIMB_WEIGHT)
+    } else {
+        Some(weight)
+    }
+}
+
+/// Add the cells of a grid to the graph, with edges between them. Cells outside of the grid count as
+/// solid, so the edges along its border may need updating once its neighbours are known.
+pub fn add_grid_to_graph(graph: &mut SideMapGraph, grid: &CellGrid) {
+    let cell_at = |pos| grid.get(pos).copied();
+    for (pos, _) in grid.iter() {
+        graph.add_node(pos);
+    }
+
+    for (pos, _) in grid.iter() {
+        for neighbour in pos.sides() {
+            if !grid.contains(neighbour) {
+                continue;
+            }
+
+            if let Some(weight) = movement_weight(pos, neighbour, &cell_at) {
+                graph.add_edge(pos, neighbour, weight);
+            }
+        }
+    }
+}
+
+/// Work out the edges again for a cell that changed and the cells around it, since whether they
+/// can be stood in depends on their neighbours. `weight` is usually [movement_weight].
+///
+/// Gives the cells at either end of any edge that was added, removed or had its weight changed.
+pub fn update_edges_around(
+    graph: &mut SideMapGraph,
+    pos: SideIPos,
+    weight: impl Fn(SideIPos, SideIPos) -> Option<u64>,
+) -> HashSet<SideIPos> {
+    let mut changed = HashSet::new();
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let from = SideIPos::new(pos.x + dx, pos.y + dy);
+            if !graph.contains_node(from) {
+                continue;
+            }
+
+            for to in from.sides() {
+                if !graph.contains_node(to) {
+                    continue;
+                }
+
+                let weight = weight(from, to);
+                if graph.edge_weight(from, to).copied() == weight {
+                    continue;
+                }
+
+                match weight {
+                    Some(weight) => {
+                        graph.add_edge(from, to, weight);
+                    }
+                    None => {
+                        graph.remove_edge(from, to);
+                    }
+                }
+                changed.insert(from);
+                changed.insert(to);
+            }
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashMap;
+
+    /// `.` is air, `_` is an empty tunnel and `1` is dirt. The top row is y = 0.
+    fn cells(rows: &[&str]) -> HashMap<SideIPos, CellContent> {
+        let mut cells = HashMap::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    '.' => CellContent::empty_air(),
+                    '_' => CellContent::empty_underground(),
+                    '1' => CellContent::dirt(10),
+                    _ => unreachable!(),
+                };
+                cells.insert(SideIPos::new(x as i32, -(y as i32)), cell);
+            }
+        }
+        cells
+    }
+
+    fn graph_for(cells: &HashMap<SideIPos, CellContent>) -> SideMapGraph {
+        let cell_at = |pos| cells.get(&pos).copied();
+        let mut graph = SideMapGraph::new();
+        for pos in cells.keys() {
+            graph.add_node(*pos);
+        }
+        for pos in cells.keys() {
+            for to in pos.sides() {
+                if !cells.contains_key(&to) {
+                    continue;
+                }
+
+                if let Some(weight) = movement_weight(*pos, to, &cell_at) {
+                    graph.add_edge(*pos, to, weight);
+                }
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn no_flying_above_ground() {
+        let cells = cells(&["....", "....", "1111"]);
+        let cell_at = |pos| cells.get(&pos).copied();
+
+        assert!(can_stand_in(SideIPos::new(1, -1), &cell_at));
+        assert!(!can_stand_in(SideIPos::new(1, 0), &cell_at));
+        assert!(movement_weight(SideIPos::new(1, -1), SideIPos::new(2, -1), &cell_at).is_some());
+        assert!(movement_weight(SideIPos::new(1, -1), SideIPos::new(1, 0), &cell_at).is_none());
+    }
+
+    #[test]
+    fn can_hang_on_to_the_lip_of_a_hole() {
+        let cells = cells(&["...", "1_1", "1_1"]);
+        let cell_at = |pos| cells.get(&pos).copied();
+
+        assert!(can_stand_in(SideIPos::new(1, 0), &cell_at));
+        assert!(movement_weight(SideIPos::new(1, 0), SideIPos::new(1, -1), &cell_at).is_some());
+    }
+
+    #[test]
+    fn climbing_walls_costs_more_than_walking() {
+        let cells = cells(&["1111", "1__1", "1__1", "1111"]);
+        let cell_at = |pos| cells.get(&pos).copied();
+
+        let walk = movement_weight(SideIPos::new(1, -2), SideIPos::new(2, -2), &cell_at).unwrap();
+        let climb = movement_weight(SideIPos::new(1, -2), SideIPos::new(1, -1), &cell_at).unwrap();
+        assert_eq!(climb, walk + CLIMB_WEIGHT);
+    }
+
+    #[test]
+    fn middle_of_a_chamber_is_out_of_reach() {
+        let cells = cells(&["11111", "1___1", "1___1", "1___1", "11111"]);
+        let cell_at = |pos| cells.get(&pos).copied();
+
+        assert!(!can_stand_in(SideIPos::new(2, -2), &cell_at));
+        assert!(can_stand_in(SideIPos::new(2, -3), &cell_at));
+    }
+
+    #[test]
+    fn updating_edges_gives_the_cells_that_changed() {
+        let mut cells = cells(&["11111", "1_111", "1_111", "11111"]);
+        let before = graph_for(&cells);
+        let mut graph = before.clone();
+
+        let dug = SideIPos::new(2, -2);
+        cells.insert(dug, CellContent::empty_underground());
+        let cell_at = |pos| cells.get(&pos).copied();
+        let weight = |from, to| movement_weight(from, to, &cell_at);
+        let changed = update_edges_around(&mut graph, dug, weight);
+
+        let after = graph_for(&cells);
+        let mut expected = HashSet::new();
+        for pos in cells.keys() {
+            for to in pos.sides() {
+                assert_eq!(graph.edge_weight(*pos, to), after.edge_weight(*pos, to));
+                if before.edge_weight(*pos, to) != after.edge_weight(*pos, to) {
+                    expected.insert(*pos);
+                }
+            }
+        }
+        assert!(!changed.is_empty());
+        assert_eq!(changed, expected);
+
+        // Nothing changes the second time around.
+        assert!(update_edges_around(&mut graph, dug, weight).is_empty());
+    }
+}
@@ -1,37 +1,129 @@
-use crate::game::food::{FeedEvent, FoodState};
-use crate::game::map::{CellType, SideMapPosToEntities, TileNeedsFoodRenderingUpdate};
-use crate::game::pathfinding::Path;
+use crate::game::calendar::Calendar;
+use crate::game::eggs::{CarriedEgg, Egg, EggLocation};
+use crate::game::flow_field::FlowDestination;
+use crate::game::food::{
+    AddFoodForAntToCarryEvent, AssignedFoodId, CarryingDiscoveredFood, CarryingFood,
+    DiscoveredFood, FeedEvent, FoodState,
+};
+use crate::game::hunger::Hunger;
+use crate::game::jobs::{ClaimedJob, JobBoard, JobKind};
+use crate::game::map::{
+    CellContent, SideMapPosToEntities, SoilLoad, TileNeedsFoodRenderingUpdate,
+    UpdateTileDirtAmountEvent,
+};
+use crate::game::pathfinding::{Path, SideMapGraph};
+use crate::game::pheromones::{PheromoneChannel, Pheromones, Scent};
+use crate::game::plugin::{PlayerState, QueensChoice, EGG_Z};
 use crate::game::positions::SideIPos;
-use crate::game::simple_brain::{Idea, Sequence};
+use crate::game::queen::Queen;
+use crate::game::simple_brain::Idea;
+use crate::game::skill::SkillMode;
 use crate::game::time::GameTime;
+use crate::game::zones::NurseryZones;
 use bevy::ecs::system::EntityCommands;
+use bevy::ecs::world::EntityMut;
 use bevy::log::{error, info, warn};
 use bevy::prelude::*;
+use bevy::utils::HashSet;
+use serde::Deserialize;
+use std::collections::VecDeque;
 use std::time::Duration;
 
-pub fn new_eat_food_steps() -> Sequence {
-    let mut steps = Sequence::new();
-    steps.push(Action::SetPathToStoredFoodAction);
-    steps.push(Action::PathfindingAction);
-    steps.push(Action::EatAction);
-    steps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionDirection {
+    Enter,
+    Exit,
 }
 
-#[derive(Debug)]
+/// Something a [Action::CheckAction] step makes sure of before the rest of the sequence runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Condition {
+    /// There's food in storage to take.
+    StoredFood,
+
+    /// A scout has found food outside that can be gathered.
+    DiscoveredFood,
+
+    /// There's somewhere to store food other than next to the queen.
+    FoodStorage,
+
+    /// The ant is hungry.
+    Hungry,
+}
+
+impl Condition {
+    pub fn holds(&self, food_state: &FoodState, hunger: &Hunger) -> bool {
+        match self {
+            Condition::StoredFood => food_state.find_destination_to_take_food().is_some(),
+            Condition::DiscoveredFood => food_state.random_food_source().is_some(),
+            Condition::FoodStorage => !food_state.food_zones.is_empty(),
+            Condition::Hungry => hunger.current >= hunger.hungry_at,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum Action {
     SetPathToStoredFoodAction,
     PathfindingAction,
-    EatAction,
-    // SetPAthToRandomOutsideAction,
-    // MapTransitionAction,
+    /// Eat up to this much of the food where the ant is standing.
+    EatAction(f32),
+    SetPathToJobAction,
+    DigAction,
+    SetPathToRandomOutsideAction,
+    SetPathToQueenAction,
+    SetPathToDiscoveredFoodAction,
+    SetPathToFoodStorageAction,
+    MapTransitionAction(TransitionDirection),
+    OutsideMapDiscoveringNewFoodAction,
+    OutsideMapGatheringExistingFoodAction,
+    OfferFoodDiscoveryToQueenAction,
+    PlaceFoodIfPossibleAction,
+    /// Pick up to this much of the food where the ant is standing.
+    PickUpFoodAction(f32),
+    FeedQueenAction,
+    SetPathAwayFromDangerAction,
+    CheckAction(Condition),
+    WaitAction(Duration),
+    SetPathToNurseryAction,
+    PickUpEggAction,
+    PutDownEggAction,
+    /// Feed the carried food to the hungriest egg where the ant is standing.
+    FeedBroodAction,
 }
 
 impl Action {
     pub fn insert(&self, ec: &mut EntityCommands) {
         match self {
             Action::SetPathToStoredFoodAction => ec.insert(SetPathToStoredFoodAction2),
-            Action::EatAction => ec.insert(EatAction2::default()),
+            Action::EatAction(amount) => ec.insert(EatAction2::new(*amount)),
             Action::PathfindingAction => ec.insert(PathfindingAction2),
+            Action::SetPathToJobAction => ec.insert(SetPathToJobAction2),
+            Action::DigAction => ec.insert(DigAction2::default()),
+            Action::SetPathToRandomOutsideAction => ec.insert(SetPathToRandomOutsideAction2),
+            Action::SetPathToQueenAction => ec.insert(SetPathToQueenAction2),
+            Action::SetPathToDiscoveredFoodAction => ec.insert(SetPathToDiscoveredFoodAction2),
+            Action::SetPathToFoodStorageAction => ec.insert(SetPathToFoodStorageAction2),
+            Action::MapTransitionAction(direction) => ec.insert(MapTransitionAction2(*direction)),
+            Action::OutsideMapDiscoveringNewFoodAction => {
+                ec.insert(OutsideMapDiscoveringNewFoodAction2::default())
+            }
+            Action::OutsideMapGatheringExistingFoodAction => {
+                ec.insert(OutsideMapGatheringExistingFoodAction2::default())
+            }
+            Action::OfferFoodDiscoveryToQueenAction => {
+                ec.insert(OfferFoodDiscoveryToQueenAction2::default())
+            }
+            Action::PlaceFoodIfPossibleAction => ec.insert(PlaceFoodIfPossibleAction2),
+            Action::PickUpFoodAction(amount) => ec.insert(PickUpFoodAction2(*amount)),
+            Action::FeedQueenAction => ec.insert(FeedQueenAction2),
+            Action::SetPathAwayFromDangerAction => ec.insert(SetPathAwayFromDangerAction2),
+            Action::CheckAction(condition) => ec.insert(CheckAction2(*condition)),
+            Action::WaitAction(duration) => ec.insert(WaitAction2::new(*duration)),
+            Action::SetPathToNurseryAction => ec.insert(SetPathToNurseryAction2),
+            Action::PickUpEggAction => ec.insert(PickUpEggAction2),
+            Action::PutDownEggAction => ec.insert(PutDownEggAction2),
+            Action::FeedBroodAction => ec.insert(FeedBroodAction2),
         };
         ()
     }
@@ -39,15 +131,138 @@ impl Action {
     pub fn remove(&self, ec: &mut EntityCommands) {
         match self {
             Action::SetPathToStoredFoodAction => ec.remove::<SetPathToStoredFoodAction2>(),
-            Action::EatAction => ec.remove::<EatAction2>(),
+            Action::EatAction(_) => ec.remove::<EatAction2>(),
             Action::PathfindingAction => ec.remove::<PathfindingAction2>(),
+            Action::SetPathToJobAction => ec.remove::<SetPathToJobAction2>(),
+            Action::DigAction => ec.remove::<DigAction2>(),
+            Action::SetPathToRandomOutsideAction => ec.remove::<SetPathToRandomOutsideAction2>(),
+            Action::SetPathToQueenAction => ec.remove::<SetPathToQueenAction2>(),
+            Action::SetPathToDiscoveredFoodAction => ec.remove::<SetPathToDiscoveredFoodAction2>(),
+            Action::SetPathToFoodStorageAction => ec.remove::<SetPathToFoodStorageAction2>(),
+            Action::MapTransitionAction(_) => ec.remove::<MapTransitionAction2>(),
+            Action::OutsideMapDiscoveringNewFoodAction => {
+                ec.remove::<OutsideMapDiscoveringNewFoodAction2>()
+            }
+            Action::OutsideMapGatheringExistingFoodAction => {
+                ec.remove::<OutsideMapGatheringExistingFoodAction2>()
+            }
+            Action::OfferFoodDiscoveryToQueenAction => {
+                ec.remove::<OfferFoodDiscoveryToQueenAction2>()
+            }
+            Action::PlaceFoodIfPossibleAction => ec.remove::<PlaceFoodIfPossibleAction2>(),
+            Action::PickUpFoodAction(_) => ec.remove::<PickUpFoodAction2>(),
+            Action::FeedQueenAction => ec.remove::<FeedQueenAction2>(),
+            Action::SetPathAwayFromDangerAction => ec.remove::<SetPathAwayFromDangerAction2>(),
+            Action::CheckAction(_) => ec.remove::<CheckAction2>(),
+            Action::WaitAction(_) => ec.remove::<WaitAction2>(),
+            Action::SetPathToNurseryAction => ec.remove::<SetPathToNurseryAction2>(),
+            Action::PickUpEggAction => ec.remove::<PickUpEggAction2>(),
+            Action::PutDownEggAction => ec.remove::<PutDownEggAction2>(),
+            Action::FeedBroodAction => ec.remove::<FeedBroodAction2>(),
         };
         ()
     }
 }
 
-#[derive(Component, Default, Deref, DerefMut)]
-pub struct EatAction2(Option<EatActionInner>);
+/// How many cells an ant looks through for somewhere out of danger.
+const MAX_FLEE_SEARCH: usize = 500;
+
+impl Action {
+    /// Undo whatever this action leaves half done when its idea is aborted. Also runs when the idea
+    /// is aborted just after this action finished, so it has to be safe to run then too.
+    pub fn clean_up(self, entity: Entity, world: &mut World) {
+        let Some(mut ant) = world.get_entity_mut(entity) else {
+            return;
+        };
+
+        // An ant that gives up drops its egg where it is, see carry_eggs.
+        ant.remove::<CarriedEgg>();
+
+        match self {
+            Action::SetPathToStoredFoodAction
+            | Action::PathfindingAction
+            | Action::SetPathToJobAction
+            | Action::SetPathToRandomOutsideAction
+            | Action::SetPathToQueenAction
+            | Action::SetPathToFoodStorageAction
+            | Action::SetPathAwayFromDangerAction
+            | Action::SetPathToNurseryAction => stop_walking(&mut ant),
+            Action::SetPathToDiscoveredFoodAction => {
+                stop_walking(&mut ant);
+                forget_assigned_food(&mut ant);
+            }
+            Action::MapTransitionAction(_) | Action::OutsideMapDiscoveringNewFoodAction => {
+                ant.insert(Visibility::Visible);
+            }
+            Action::OutsideMapGatheringExistingFoodAction => {
+                ant.insert(Visibility::Visible);
+                forget_assigned_food(&mut ant);
+            }
+            Action::OfferFoodDiscoveryToQueenAction => {
+                let offered = ant
+                    .get::<OfferFoodDiscoveryToQueenAction2>()
+                    .map(|offered| **offered)
+                    .unwrap_or(false);
+                if offered {
+                    world.resource_mut::<GameTime>().system_pause(false);
+                    world.resource_mut::<PlayerState>().queens_choice = QueensChoice::None;
+                }
+            }
+            // Food that was eaten, placed, picked up or fed stays that way, and dig jobs are released
+            // in settle_jobs.
+            Action::EatAction(_)
+            | Action::DigAction
+            | Action::PlaceFoodIfPossibleAction
+            | Action::PickUpFoodAction(_)
+            | Action::FeedQueenAction
+            | Action::PickUpEggAction
+            | Action::PutDownEggAction
+            | Action::FeedBroodAction
+            | Action::CheckAction(_)
+            | Action::WaitAction(_) => {}
+        }
+    }
+}
+
+fn stop_walking(ant: &mut EntityMut) {
+    if let Some(mut path) = ant.get_mut::<Path>() {
+        path.cancel();
+    }
+    if let Some(mut scent) = ant.get_mut::<Scent>() {
+        **scent = None;
+    }
+}
+
+fn forget_assigned_food(ant: &mut EntityMut) {
+    if let Some(mut assigned_food_id) = ant.get_mut::<AssignedFoodId>() {
+        **assigned_food_id = None;
+    }
+}
+
+/// The food of type `T` the ant is carrying, and the child entity it's on.
+fn carried<T: Component + Clone>(
+    children: Option<&Children>,
+    food: &Query<&T>,
+) -> Option<(Entity, T)> {
+    children?
+        .iter()
+        .find_map(|child| Some((*child, food.get(*child).ok()?.clone())))
+}
+
+#[derive(Component)]
+pub struct EatAction2 {
+    amount: f32,
+    eating: Option<EatActionInner>,
+}
+
+impl EatAction2 {
+    fn new(amount: f32) -> Self {
+        Self {
+            amount,
+            eating: None,
+        }
+    }
+}
 
 pub struct EatActionInner {
     finish_eating_at: Duration,
@@ -58,9 +273,9 @@ pub struct SetPathToStoredFoodAction2;
 
 pub fn set_path_to_stored_food_action_2(
     food_state: Res<FoodState>,
-    mut query: Query<(&mut Idea, &mut Path), With<SetPathToStoredFoodAction2>>,
+    mut query: Query<(&mut Idea, &mut Path, &mut Scent), With<SetPathToStoredFoodAction2>>,
 ) {
-    for (mut idea, mut path) in &mut query {
+    for (mut idea, mut path, mut scent) in &mut query {
         let Some(target) = food_state.find_destination_to_take_food() else {
             warn!("No food to take");
             idea.abort();
@@ -68,6 +283,7 @@ pub fn set_path_to_stored_food_action_2(
         };
 
         path.set_target(target);
+        **scent = Some(PheromoneChannel::Food);
 
         idea.next_step();
     }
@@ -82,10 +298,10 @@ pub fn eat_action_2(
     mut feed_writer: EventWriter<FeedEvent>,
 ) {
     for (entity, mut idea, mut action, transform) in &mut query {
-        if action.is_none() {
+        if action.eating.is_none() {
             let pos = SideIPos::from(transform);
 
-            let Some(carrying_food) = food_state.take_food_from_position(pos, 1f32) else {
+            let Some(carrying_food) = food_state.take_food_from_position(pos, action.amount) else {
                 warn!("Tried to eat food but there was none.");
                 idea.abort();
                 continue;
@@ -106,12 +322,12 @@ pub fn eat_action_2(
                 .entity(*tile_entity)
                 .insert(TileNeedsFoodRenderingUpdate);
 
-            **action = Some(EatActionInner {
+            action.eating = Some(EatActionInner {
                 finish_eating_at: time.since_startup() + Duration::from_secs(5),
             });
         };
 
-        let inner = action.0.as_ref().unwrap();
+        let inner = action.eating.as_ref().unwrap();
         if time.since_startup() >= inner.finish_eating_at {
             info!("Finished eating food");
             idea.next_step();
@@ -137,3 +353,795 @@ pub fn pathfinding_action_2(mut query: Query<(&mut Idea, &mut Path), With<Pathfi
         }
     }
 }
+
+#[derive(Component)]
+pub struct SetPathToJobAction2;
+
+/// Walk to the claimed job. For dig jobs, walk next to the cell, from the side that is easiest to
+/// get to.
+pub fn set_path_to_job_action_2(
+    job_board: Res<JobBoard>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    cells: Query<&CellContent>,
+    mut query: Query<
+        (Entity, &mut Idea, &mut Path, &mut Scent, &ClaimedJob),
+        With<SetPathToJobAction2>,
+    >,
+) {
+    for (entity, mut idea, mut path, mut scent, claimed) in &mut query {
+        let Some(job) = job_board
+            .get(**claimed)
+            .filter(|job| job.claimed_by == Some(entity))
+        else {
+            info!(?claimed, "Job was cancelled");
+            idea.abort();
+            continue;
+        };
+
+        let target = if job.kind == JobKind::Dig {
+            job.pos
+                .sides()
+                .into_iter()
+                .filter_map(|side| {
+                    let entity = side_map_pos_to_entities.get(&side)?;
+                    let weight = cells.get(*entity).ok()?.weight()?;
+                    Some((side, weight))
+                })
+                .min_by_key(|(_, weight)| *weight)
+                .map(|(side, _)| side)
+        } else {
+            Some(job.pos)
+        };
+
+        let Some(target) = target else {
+            warn!(?job, "Nowhere to stand next to the dig job");
+            idea.abort();
+            continue;
+        };
+
+        path.set_target(target);
+        **scent = Some(PheromoneChannel::Home);
+        idea.next_step();
+    }
+}
+
+/// How much dirt an ant digs out of a designated cell every second, before hardness.
+const DIG_RATE: f32 = 40f32;
+
+#[derive(Component, Default)]
+pub struct DigAction2 {
+    /// Dirt dug but not yet taken out of the cell, because cells only hold whole amounts.
+    progress: f32,
+}
+
+/// Dig the claimed cell until it's empty. When the ant can't carry any more of the cell's material,
+/// it packs what it has into the walls around it.
+pub fn dig_action_2(
+    time: Res<GameTime>,
+    mut job_board: ResMut<JobBoard>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    mut cells: Query<&mut CellContent>,
+    mut query: Query<(
+        Entity,
+        &mut Idea,
+        &mut DigAction2,
+        &ClaimedJob,
+        &mut SoilLoad,
+        &Transform,
+    )>,
+    mut update_tile_rendering_writer: EventWriter<UpdateTileDirtAmountEvent>,
+) {
+    for (entity, mut idea, mut action, claimed, mut soil_load, transform) in &mut query {
+        let Some(target) = job_board
+            .get(**claimed)
+            .filter(|job| job.kind == JobKind::Dig && job.claimed_by == Some(entity))
+            .map(|job| job.pos)
+        else {
+            info!(?claimed, "Dig job was cancelled");
+            idea.abort();
+            continue;
+        };
+
+        let Some(target_entity) = side_map_pos_to_entities.get(&target) else {
+            idea.abort();
+            continue;
+        };
+
+        let Ok(target_cell) = cells.get(*target_entity) else {
+            idea.abort();
+            continue;
+        };
+
+        if target_cell.is_empty() {
+            info!(?target, "Finished dig job");
+            job_board.remove(**claimed);
+            idea.next_step();
+            continue;
+        }
+
+        let Some(material) = target_cell.material() else {
+            warn!(?target, "Can't dig this cell");
+            job_board.remove(**claimed);
+            idea.abort();
+            continue;
+        };
+
+        if soil_load.room_for(material) == 0 {
+            let here = SideIPos::from(transform);
+            for side in here.sides() {
+                if side == target || job_board.find(JobKind::Dig, side).is_some() {
+                    continue;
+                }
+
+                let Some(side_entity) = side_map_pos_to_entities.get(&side) else {
+                    continue;
+                };
+
+                let Ok(mut side_cell) = cells.get_mut(*side_entity) else {
+                    continue;
+                };
+
+                // Walls only, so we don't fill in the tunnel we're standing in.
+                if side_cell.is_empty() {
+                    continue;
+                }
+
+                if soil_load.drop_into(&mut side_cell) > 0 {
+                    update_tile_rendering_writer.send(UpdateTileDirtAmountEvent(*side_entity));
+                }
+            }
+
+            if soil_load.room_for(material) == 0 {
+                warn!(?target, "Nowhere to put the dirt");
+                idea.abort();
+                continue;
+            }
+        }
+
+        action.progress += DIG_RATE / material.hardness() * time.delta_seconds();
+        let amount = (action.progress as u8).min(soil_load.room_for(material));
+        if amount == 0 {
+            continue;
+        }
+
+        let Ok(mut target_cell) = cells.get_mut(*target_entity) else {
+            continue;
+        };
+
+        let dug = target_cell.dig(amount);
+        action.progress -= amount as f32;
+        soil_load.pick_up(material, dug);
+        update_tile_rendering_writer.send(UpdateTileDirtAmountEvent(*target_entity));
+    }
+}
+
+/// A scout or cargo ant heads to the nearest exit.
+#[derive(Component)]
+pub struct SetPathToRandomOutsideAction2;
+
+pub fn set_path_to_outside_action_2(
+    mut query: Query<(&mut Idea, &mut Path, &mut Scent), With<SetPathToRandomOutsideAction2>>,
+) {
+    for (mut idea, mut path, mut scent) in &mut query {
+        // TODO: Have an optional exit position to go to for cargo ants.
+        path.follow_field(FlowDestination::Exits);
+        **scent = Some(PheromoneChannel::Food);
+
+        idea.next_step();
+    }
+}
+
+/// Move to The Queen!
+#[derive(Component)]
+pub struct SetPathToQueenAction2;
+
+pub fn set_path_to_queen_action_2(
+    mut query: Query<(&mut Idea, &mut Path, &mut Scent), With<SetPathToQueenAction2>>,
+) {
+    for (mut idea, mut path, mut scent) in &mut query {
+        path.follow_field(FlowDestination::Queen);
+        **scent = Some(PheromoneChannel::Home);
+
+        idea.next_step();
+    }
+}
+
+/// A cargo ant picks a discovered food source and heads to the exit it's through.
+#[derive(Component)]
+pub struct SetPathToDiscoveredFoodAction2;
+
+pub fn set_path_to_discovered_food_action_2(
+    food_state: Res<FoodState>,
+    mut query: Query<
+        (&mut Idea, &mut Path, &mut Scent, &mut AssignedFoodId),
+        With<SetPathToDiscoveredFoodAction2>,
+    >,
+) {
+    for (mut idea, mut path, mut scent, mut assigned_food_id) in &mut query {
+        let Some(food_id) = food_state.random_food_source() else {
+            warn!("No random food source found");
+            idea.abort();
+            continue;
+        };
+
+        let Some(exit_position) = food_state.position_of_food_source(food_id) else {
+            warn!(?food_id, "No position for food");
+            idea.abort();
+            continue;
+        };
+
+        **assigned_food_id = Some(food_id);
+        path.set_target(exit_position);
+        **scent = Some(PheromoneChannel::Food);
+
+        idea.next_step();
+    }
+}
+
+#[derive(Component)]
+pub struct SetPathToFoodStorageAction2;
+
+pub fn set_path_to_food_storage_action_2(
+    food_state: Res<FoodState>,
+    mut query: Query<(&mut Idea, &mut Path, &mut Scent), With<SetPathToFoodStorageAction2>>,
+) {
+    for (mut idea, mut path, mut scent) in &mut query {
+        // Without any food zones, food goes next to the queen.
+        if !food_state.food_zones.is_empty() {
+            path.follow_field(FlowDestination::FoodStorage);
+        } else {
+            path.follow_field(FlowDestination::Queen);
+        }
+        **scent = Some(PheromoneChannel::Home);
+
+        idea.next_step();
+    }
+}
+
+/// When an ant has hit the map exit, we make them invisible, or vice versa.
+#[derive(Component, Deref)]
+pub struct MapTransitionAction2(TransitionDirection);
+
+pub fn map_transition_action_2(
+    mut query: Query<(&mut Idea, &mut Visibility, &MapTransitionAction2)>,
+) {
+    for (mut idea, mut visibility, transition) in &mut query {
+        *visibility = if **transition == TransitionDirection::Enter {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+
+        idea.next_step();
+    }
+}
+
+/// The ant is off the map going to get new food.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct OutsideMapDiscoveringNewFoodAction2(Option<OutsideMapInner>);
+
+pub struct OutsideMapInner {
+    initial_time: Duration,
+    time_left: Duration,
+}
+
+pub fn outside_map_discovering_food_action_2(
+    time: Res<GameTime>,
+    calendar: Res<Calendar>,
+    skill_mode: Res<SkillMode>,
+    mut food_state: ResMut<FoodState>,
+    mut query: Query<(
+        Entity,
+        &mut Idea,
+        &mut OutsideMapDiscoveringNewFoodAction2,
+        &Transform,
+    )>,
+    mut carry_food_writer: EventWriter<AddFoodForAntToCarryEvent>,
+) {
+    for (entity, mut idea, mut action, transform) in &mut query {
+        let inner = action.get_or_insert_with(|| {
+            let time_left = food_state
+                .next_discover_time
+                .get_and_increase(calendar.food_discovery_time_scale());
+            OutsideMapInner {
+                initial_time: time_left,
+                time_left,
+            }
+        });
+
+        inner.time_left = inner.time_left.saturating_sub(time.delta());
+        if inner.time_left != Duration::ZERO {
+            continue;
+        }
+
+        let food_info = skill_mode.next_food(time.since_startup());
+
+        // Give the ant some food to carry.
+        carry_food_writer.send(AddFoodForAntToCarryEvent::discovered(
+            entity,
+            DiscoveredFood {
+                food_info,
+                position: SideIPos::from(transform),
+                time_to_discover: inner.initial_time,
+                stash_remaining: 1000f32,
+            },
+        ));
+
+        idea.next_step();
+    }
+}
+
+/// The ant is off the map going to get existing food.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct OutsideMapGatheringExistingFoodAction2(Option<Duration>);
+
+pub fn outside_map_gathering_existing_food_action_2(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    calendar: Res<Calendar>,
+    mut food_state: ResMut<FoodState>,
+    mut query: Query<(
+        Entity,
+        &mut Idea,
+        &mut OutsideMapGatheringExistingFoodAction2,
+        &mut AssignedFoodId,
+    )>,
+    mut carry_food_writer: EventWriter<AddFoodForAntToCarryEvent>,
+) {
+    for (entity, mut idea, mut action, mut assigned_food_id) in &mut query {
+        let Some(food_id) = **assigned_food_id else {
+            warn!(?entity, "No food assigned");
+            idea.abort();
+            continue;
+        };
+
+        if action.is_none() {
+            let Some(time_left) = food_state.eta(&food_id) else {
+                warn!(?food_id, "No food left");
+                idea.abort();
+                continue;
+            };
+            **action = Some(time_left);
+        }
+
+        let time_left = action.0.as_mut().unwrap();
+        *time_left = time_left.saturating_sub(time.delta());
+        if *time_left != Duration::ZERO {
+            continue;
+        }
+
+        if rand::random::<f32>() < calendar.forager_risk() {
+            info!(?entity, "Forager didn't make it back");
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let Some(carrying_food) = food_state.take_food_from_discovered_source(&food_id) else {
+            warn!(?food_id, "No food left");
+            idea.abort();
+            continue;
+        };
+
+        // Give the ant the food to carry.
+        carry_food_writer.send(AddFoodForAntToCarryEvent::food(entity, carrying_food));
+
+        // Remove the food assignment.
+        **assigned_food_id = None;
+
+        idea.next_step();
+    }
+}
+
+/// The scout ant is offering new food to the queen. True once the offer has been made.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct OfferFoodDiscoveryToQueenAction2(bool);
+
+/// Offer the food to the queen.
+///
+/// * Pause the game using a special system pause in GameTime.
+/// * Show a dialog that looks modal with a summary of the food.
+/// * Wait for player to accept or reject the food.
+/// * If accepted:
+///   * The food is added to the food state as approved food.
+///   * The queen eats it even if she is full.
+/// * If rejected, the food is added to the food state as rejected food.
+///   * The food vanishes (for now)
+/// * The game is unpaused.
+pub fn offer_food_discovery_to_queen_action_2(
+    mut commands: Commands,
+    mut time: ResMut<GameTime>,
+    mut food_state: ResMut<FoodState>,
+    mut player_state: ResMut<PlayerState>,
+    carrying_discovered_food: Query<&CarryingDiscoveredFood>,
+    mut query: Query<(
+        Entity,
+        &mut Idea,
+        &mut OfferFoodDiscoveryToQueenAction2,
+        Option<&Children>,
+    )>,
+    queen: Query<Entity, With<Queen>>,
+    mut feed_writer: EventWriter<FeedEvent>,
+) {
+    let Ok(queen_entity) = queen.get_single() else {
+        return;
+    };
+
+    for (entity, mut idea, mut offered, children) in &mut query {
+        // TODO: Check if we are at the queen's position!
+
+        let Some((child_food_entity, carrying)) = carried(children, &carrying_discovered_food)
+        else {
+            error!(?entity, "No discovered food found in children.");
+            idea.abort();
+            continue;
+        };
+
+        if !**offered {
+            // Another scout is already offering food, so wait for the queen to decide on that.
+            if matches!(player_state.queens_choice, QueensChoice::Undecided(_)) {
+                continue;
+            }
+
+            time.system_pause(true);
+            player_state.queens_choice = QueensChoice::Undecided(carrying.food_info.clone());
+            **offered = true;
+            continue;
+        }
+
+        match player_state.queens_choice {
+            QueensChoice::None => {
+                warn!("Not in a state to offer food to the queen.");
+                time.system_pause(false);
+                idea.abort();
+                continue;
+            }
+            QueensChoice::Undecided(_) => {
+                // Still waiting
+                continue;
+            }
+            QueensChoice::Approve => {
+                // Add the food to the food state.
+                food_state.approve_food((*carrying).clone());
+
+                feed_writer.send(FeedEvent {
+                    target: queen_entity,
+                    carrying_food: CarryingFood {
+                        food_id: carrying.food_info.food_id,
+                        amount: 10f32,
+                    },
+                });
+            }
+            QueensChoice::Deny => {
+                // TODO: The queen eats the ant even if she is full.
+                // Food just vanishes.
+                food_state.reject_food(carrying.food_info.food_id);
+            }
+        }
+
+        commands.entity(child_food_entity).despawn_recursive();
+        player_state.queens_choice = QueensChoice::None;
+        time.system_pause(false);
+        idea.next_step();
+    }
+}
+
+/// Will attempt to place food where the ant is standing.
+///
+/// TODO: This will work all the time for now.
+#[derive(Component)]
+pub struct PlaceFoodIfPossibleAction2;
+
+pub fn place_food_if_possible_action_2(
+    mut commands: Commands,
+    mut food_state: ResMut<FoodState>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    carrying_food: Query<&CarryingFood>,
+    mut query: Query<
+        (Entity, &mut Idea, Option<&Children>, &Transform),
+        With<PlaceFoodIfPossibleAction2>,
+    >,
+) {
+    for (entity, mut idea, children, transform) in &mut query {
+        let Some((child_food_entity, carrying_food)) = carried(children, &carrying_food) else {
+            error!(?entity, "No CarryingFood found in children.");
+            idea.abort();
+            continue;
+        };
+
+        // Remove food from ant.
+        commands.entity(child_food_entity).despawn_recursive();
+
+        // Put the food on the ground. The sprites for the food update elsewhere.
+        let pos = SideIPos::from(transform);
+        food_state.add_food_at_position(pos, &carrying_food);
+
+        if let Some(tile_entity) = side_map_pos_to_entities.get(&pos) {
+            commands
+                .entity(*tile_entity)
+                .insert(TileNeedsFoodRenderingUpdate);
+        }
+
+        idea.next_step();
+    }
+}
+
+#[derive(Component, Deref)]
+pub struct PickUpFoodAction2(f32);
+
+pub fn pick_up_food_action_2(
+    mut commands: Commands,
+    mut food_state: ResMut<FoodState>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    mut query: Query<(Entity, &mut Idea, &PickUpFoodAction2, &Transform)>,
+    mut carry_food_writer: EventWriter<AddFoodForAntToCarryEvent>,
+) {
+    for (entity, mut idea, amount, transform) in &mut query {
+        let pos = SideIPos::from(transform);
+
+        // Make sure there's still food here.
+        let Some(carrying_food) = food_state.take_food_from_position(pos, **amount) else {
+            warn!(?pos, "No food left to pick up");
+            idea.abort();
+            continue;
+        };
+
+        info!(?pos, "Picked up food");
+        carry_food_writer.send(AddFoodForAntToCarryEvent::food(entity, carrying_food));
+
+        if let Some(tile_entity) = side_map_pos_to_entities.get(&pos) {
+            commands
+                .entity(*tile_entity)
+                .insert(TileNeedsFoodRenderingUpdate);
+        }
+
+        idea.next_step();
+    }
+}
+
+#[derive(Component)]
+pub struct FeedQueenAction2;
+
+pub fn feed_queen_action_2(
+    mut commands: Commands,
+    carrying_food: Query<&CarryingFood>,
+    queen: Query<Entity, With<Queen>>,
+    mut query: Query<(Entity, &mut Idea, Option<&Children>), With<FeedQueenAction2>>,
+    mut feed_writer: EventWriter<FeedEvent>,
+) {
+    let Ok(queen_entity) = queen.get_single() else {
+        return;
+    };
+
+    for (entity, mut idea, children) in &mut query {
+        let Some((child_food_entity, carrying_food)) = carried(children, &carrying_food) else {
+            error!(?entity, "No CarryingFood found in children.");
+            idea.abort();
+            continue;
+        };
+
+        // Feed the queen and remove the food.
+        commands.entity(child_food_entity).despawn_recursive();
+
+        feed_writer.send(FeedEvent {
+            target: queen_entity,
+            carrying_food,
+        });
+
+        idea.next_step();
+    }
+}
+
+/// Walk to somewhere in the nursery.
+#[derive(Component)]
+pub struct SetPathToNurseryAction2;
+
+pub fn set_path_to_nursery_action_2(
+    nursery_zones: Res<NurseryZones>,
+    mut query: Query<(&mut Idea, &mut Path, &mut Scent), With<SetPathToNurseryAction2>>,
+) {
+    for (mut idea, mut path, mut scent) in &mut query {
+        let Some(target) = nursery_zones.random() else {
+            warn!("No nursery to go to");
+            idea.abort();
+            continue;
+        };
+
+        path.set_target(target);
+        **scent = Some(PheromoneChannel::Home);
+
+        idea.next_step();
+    }
+}
+
+/// Pick up a loose egg where the ant is standing.
+#[derive(Component)]
+pub struct PickUpEggAction2;
+
+pub fn pick_up_egg_action_2(
+    mut commands: Commands,
+    mut eggs: Query<(Entity, &mut Egg, &Transform)>,
+    mut query: Query<(Entity, &mut Idea, &Transform), With<PickUpEggAction2>>,
+) {
+    for (entity, mut idea, transform) in &mut query {
+        let pos = SideIPos::from(transform);
+        let egg = eggs.iter_mut().find(|(_, egg, egg_transform)| {
+            egg.location == EggLocation::Loose && SideIPos::from(*egg_transform) == pos
+        });
+        let Some((egg_entity, mut egg, _)) = egg else {
+            warn!(?pos, "No loose egg to pick up");
+            idea.abort();
+            continue;
+        };
+
+        info!(?pos, "Picked up egg");
+        egg.location = EggLocation::Carried(entity);
+        commands.entity(entity).insert(CarriedEgg(egg_entity));
+
+        idea.next_step();
+    }
+}
+
+/// Put down the carried egg where the ant is standing.
+#[derive(Component)]
+pub struct PutDownEggAction2;
+
+type EggCarriers<'a> = (Entity, &'a mut Idea, Option<&'a CarriedEgg>, &'a Transform);
+
+pub fn put_down_egg_action_2(
+    mut commands: Commands,
+    nursery_zones: Res<NurseryZones>,
+    mut eggs: Query<(&mut Egg, &mut Transform)>,
+    mut query: Query<EggCarriers, (With<PutDownEggAction2>, Without<Egg>)>,
+) {
+    for (entity, mut idea, carried_egg, transform) in &mut query {
+        let Some((mut egg, mut egg_transform)) = carried_egg.and_then(|c| eggs.get_mut(**c).ok())
+        else {
+            warn!(?entity, "No egg to put down");
+            idea.abort();
+            continue;
+        };
+
+        let pos = SideIPos::from(transform);
+        info!(?pos, "Put down egg");
+        egg.put_down(pos, &nursery_zones);
+        *egg_transform = pos.to_transform(EGG_Z);
+        commands.entity(entity).remove::<CarriedEgg>();
+
+        idea.next_step();
+    }
+}
+
+#[derive(Component)]
+pub struct FeedBroodAction2;
+
+pub fn feed_brood_action_2(
+    mut commands: Commands,
+    mut food_state: ResMut<FoodState>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    carrying_food: Query<&CarryingFood>,
+    mut eggs: Query<(&mut Egg, &Transform)>,
+    mut query: Query<(Entity, &mut Idea, Option<&Children>, &Transform), With<FeedBroodAction2>>,
+) {
+    for (entity, mut idea, children, transform) in &mut query {
+        let Some((child_food_entity, carrying_food)) = carried(children, &carrying_food) else {
+            error!(?entity, "No CarryingFood found in children.");
+            idea.abort();
+            continue;
+        };
+
+        commands.entity(child_food_entity).despawn_recursive();
+
+        let pos = SideIPos::from(transform);
+        let hungriest = eggs
+            .iter_mut()
+            .filter(|(egg, egg_transform)| {
+                egg.location == EggLocation::Nursery && SideIPos::from(*egg_transform) == pos
+            })
+            .min_by(|(a, _), (b, _)| a.care.total_cmp(&b.care));
+        let Some((mut egg, _)) = hungriest else {
+            // Nobody to feed, so leave the food here rather than carry it around forever.
+            warn!(?pos, "No egg to feed");
+            food_state.add_food_at_position(pos, &carrying_food);
+            if let Some(tile_entity) = side_map_pos_to_entities.get(&pos) {
+                commands
+                    .entity(*tile_entity)
+                    .insert(TileNeedsFoodRenderingUpdate);
+            }
+            idea.abort();
+            continue;
+        };
+
+        info!(?pos, "Fed egg");
+        egg.care = 1.0;
+
+        idea.next_step();
+    }
+}
+
+/// Walk to the nearest cell without any danger pheromone.
+#[derive(Component)]
+pub struct SetPathAwayFromDangerAction2;
+
+pub fn set_path_away_from_danger_action_2(
+    graph: Res<SideMapGraph>,
+    pheromones: Res<Pheromones>,
+    mut query: Query<(&mut Idea, &mut Path, &Transform), With<SetPathAwayFromDangerAction2>>,
+) {
+    for (mut idea, mut path, transform) in &mut query {
+        let start = SideIPos::from(transform);
+        let is_safe = |pos: SideIPos| pheromones.strength(pos, PheromoneChannel::Danger) == 0.0;
+
+        let mut seen = HashSet::from_iter([start]);
+        let mut queue = VecDeque::from([start]);
+        let mut safe = None;
+        while let Some(pos) = queue.pop_front() {
+            if is_safe(pos) {
+                safe = Some(pos);
+                break;
+            }
+
+            if seen.len() >= MAX_FLEE_SEARCH || !graph.contains_node(pos) {
+                continue;
+            }
+
+            for neighbour in graph.neighbors(pos) {
+                if seen.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        let Some(safe) = safe else {
+            warn!(?start, "Nowhere safe to go");
+            idea.abort();
+            continue;
+        };
+
+        path.set_target(safe);
+        idea.next_step();
+    }
+}
+
+/// Carry on with the sequence only if the condition holds, otherwise abort so the fallback runs.
+#[derive(Component, Deref)]
+pub struct CheckAction2(Condition);
+
+pub fn check_action_2(
+    food_state: Res<FoodState>,
+    mut query: Query<(&mut Idea, &CheckAction2, &Hunger)>,
+) {
+    for (mut idea, condition, hunger) in &mut query {
+        if condition.holds(&food_state, hunger) {
+            idea.next_step();
+        } else {
+            debug!(condition = ?**condition, "Condition doesn't hold");
+            idea.abort();
+        }
+    }
+}
+
+/// Do nothing for a while.
+#[derive(Component)]
+pub struct WaitAction2 {
+    duration: Duration,
+    until: Option<Duration>,
+}
+
+impl WaitAction2 {
+    fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            until: None,
+        }
+    }
+}
+
+pub fn wait_action_2(time: Res<GameTime>, mut query: Query<(&mut Idea, &mut WaitAction2)>) {
+    for (mut idea, mut action) in &mut query {
+        let until = time.since_startup() + action.duration;
+        let until = *action.until.get_or_insert(until);
+        if time.since_startup() >= until {
+            idea.next_step();
+        }
+    }
+}
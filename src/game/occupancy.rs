@@ -0,0 +1,333 @@
+//! Which creatures are in each cell, and how many fit.
+//!
+//! A moving creature takes its place in a cell as it starts walking into it, and only if there's
+//! room. Cells in tunnels one cell wide hold fewer than open cells. A creature that can't get in
+//! waits, and once it has waited long enough it squeezes past anyway.
+//!
+//! Two ants meeting head on, e.g. in a tunnel one cell wide, would each wait for the other to move
+//! out of the way. Instead the one that has waited longer goes first, and the other gives way until
+//! there's room where it was going.
+//!
+//! Path searches find crowded cells dearer, so ants go around crowds when there's another way.
+
+use crate::game::map::CellContent;
+use crate::game::movement::is_solid;
+use crate::game::pathfinding::{CellCosts, Path};
+use crate::game::plugin::Crawler;
+use crate::game::positions::SideIPos;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::time::Duration;
+
+#[derive(Resource, Debug)]
+pub struct OccupancySettings {
+    pub open_capacity: usize,
+
+    /// How many fit in a cell of a tunnel one cell wide.
+    pub tunnel_capacity: usize,
+
+    /// Added to the weight of walking into a crowded cell for each creature in it.
+    pub congestion_weight: u64,
+
+    /// Cells with at least this many creatures in them are crowded. More than fit in a tunnel, so
+    /// a couple of ants passing each other don't count.
+    pub crowded: usize,
+
+    /// How long a creature waits to get into a full cell before squeezing in anyway.
+    pub max_wait: Duration,
+}
+
+impl Default for OccupancySettings {
+    fn default() -> Self {
+        Self {
+            open_capacity: 4,
+            tunnel_capacity: 2,
+            congestion_weight: 5,
+            crowded: 3,
+            max_wait: Duration::from_secs(2),
+        }
+    }
+}
+
+impl OccupancySettings {
+    pub fn capacity(
+        &self,
+        pos: SideIPos,
+        cell_at: &impl Fn(SideIPos) -> Option<CellContent>,
+    ) -> usize {
+        if is_narrow(pos, cell_at) {
+            self.tunnel_capacity
+        } else {
+            self.open_capacity
+        }
+    }
+}
+
+/// An underground cell with walls on both sides, or above and below.
+fn is_narrow(pos: SideIPos, cell_at: &impl Fn(SideIPos) -> Option<CellContent>) -> bool {
+    let Some(cell) = cell_at(pos) else {
+        return false;
+    };
+
+    if !cell.is_underground() {
+        return false;
+    }
+
+    let solid = |dx: i32, dy: i32| is_solid(cell_at(SideIPos::new(pos.x + dx, pos.y + dy)));
+    (solid(-1, 0) && solid(1, 0)) || (solid(0, -1) && solid(0, 1))
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Occupancy {
+    cells: HashMap<SideIPos, Vec<Entity>>,
+    by_creature: HashMap<Entity, SideIPos>,
+
+    /// The full cell each creature is waiting to get into, and since when.
+    waiting: HashMap<Entity, (SideIPos, Duration)>,
+}
+
+impl Occupancy {
+    pub fn creatures_in(&self, pos: SideIPos) -> &[Entity] {
+        self.cells.get(&pos).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn cell_of(&self, entity: Entity) -> Option<SideIPos> {
+        self.by_creature.get(&entity).copied()
+    }
+
+    pub fn move_to(&mut self, entity: Entity, pos: SideIPos) {
+        if self.cell_of(entity) == Some(pos) {
+            return;
+        }
+
+        self.remove(entity);
+        self.cells.entry(pos).or_default().push(entity);
+        self.by_creature.insert(entity, pos);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        self.waiting.remove(&entity);
+        let Some(pos) = self.by_creature.remove(&entity) else {
+            return;
+        };
+
+        let Some(creatures) = self.cells.get_mut(&pos) else {
+            return;
+        };
+
+        creatures.retain(|creature| *creature != entity);
+        if creatures.is_empty() {
+            self.cells.remove(&pos);
+        }
+    }
+
+    /// Move a creature into a cell if there's room, if it has waited long enough, or if it goes first
+    /// past a creature coming the other way. Returns whether it moved in.
+    pub fn try_enter(
+        &mut self,
+        entity: Entity,
+        pos: SideIPos,
+        capacity: usize,
+        now: Duration,
+        settings: &OccupancySettings,
+    ) -> bool {
+        if self.cell_of(entity) == Some(pos) {
+            return true;
+        }
+
+        let waiting_since = match self.waiting.get(&entity) {
+            Some((target, since)) if *target == pos => *since,
+            _ => {
+                self.waiting.insert(entity, (pos, now));
+                now
+            }
+        };
+
+        let is_full = self.creatures_in(pos).len() >= capacity;
+        let has_waited = now >= waiting_since + settings.max_wait;
+        if is_full && !has_waited && !self.goes_first(entity, pos) {
+            return false;
+        }
+
+        self.waiting.remove(&entity);
+        self.move_to(entity, pos);
+        true
+    }
+
+    /// Whether a creature waiting to get into a cell meets one in it waiting to come the other way,
+    /// and goes first. The one that has waited longer goes first, or the older one if they started
+    /// waiting together.
+    fn goes_first(&self, entity: Entity, pos: SideIPos) -> bool {
+        let Some(here) = self.cell_of(entity) else {
+            return false;
+        };
+        let Some((_, since)) = self.waiting.get(&entity) else {
+            return false;
+        };
+
+        self.creatures_in(pos)
+            .iter()
+            .any(|other| match self.waiting.get(other) {
+                Some((target, other_since)) if *target == here => {
+                    (*since, entity.index()) < (*other_since, other.index())
+                }
+                _ => false,
+            })
+    }
+
+    /// Make crowded cells dearer to walk into.
+    pub fn add_congestion_costs(&self, costs: &mut CellCosts, settings: &OccupancySettings) {
+        for (pos, creatures) in &self.cells {
+            if creatures.len() < settings.crowded {
+                continue;
+            }
+
+            costs.add(*pos, creatures.len() as u64 * settings.congestion_weight);
+        }
+    }
+}
+
+/// Put creatures that aren't walking anywhere in the cell they're standing in, and forget the ones
+/// that are gone.
+pub fn update_occupancy(
+    mut occupancy: ResMut<Occupancy>,
+    creatures: Query<(Entity, &Transform, &Path), With<Crawler>>,
+    mut removed: RemovedComponents<Crawler>,
+) {
+    for entity in removed.iter() {
+        occupancy.remove(entity);
+    }
+
+    for (entity, transform, path) in &creatures {
+        let is_walking = matches!(path, Path::Progress(_) | Path::FollowField(_));
+        if is_walking && occupancy.cell_of(entity).is_some() {
+            continue;
+        }
+
+        occupancy.move_to(entity, SideIPos::from(transform));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_wide_tunnels_hold_fewer() {
+        let settings = OccupancySettings::default();
+        let tunnel = |pos: SideIPos| {
+            if pos.y == -1 {
+                Some(CellContent::empty_underground())
+            } else {
+                Some(CellContent::dirt(10))
+            }
+        };
+        let chamber = |pos: SideIPos| {
+            if (-3..=-1).contains(&pos.y) {
+                Some(CellContent::empty_underground())
+            } else {
+                Some(CellContent::dirt(10))
+            }
+        };
+
+        let pos = SideIPos::new(0, -1);
+        assert_eq!(settings.capacity(pos, &tunnel), settings.tunnel_capacity);
+        let pos = SideIPos::new(0, -2);
+        assert_eq!(settings.capacity(pos, &chamber), settings.open_capacity);
+    }
+
+    #[test]
+    fn full_cells_wait_then_squeeze() {
+        let settings = OccupancySettings::default();
+        let mut occupancy = Occupancy::default();
+        let (a, b, c) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        let (here, there) = (SideIPos::new(0, -1), SideIPos::new(1, -1));
+        occupancy.move_to(a, there);
+        occupancy.move_to(b, there);
+        occupancy.move_to(c, here);
+
+        let start = Duration::from_secs(10);
+        assert!(!occupancy.try_enter(c, there, 2, start, &settings));
+        assert!(!occupancy.try_enter(c, there, 2, start + Duration::from_secs(1), &settings));
+        assert_eq!(occupancy.creatures_in(here), &[c]);
+
+        assert!(occupancy.try_enter(c, there, 2, start + settings.max_wait, &settings));
+        assert_eq!(occupancy.creatures_in(there), &[a, b, c]);
+        assert!(occupancy.creatures_in(here).is_empty());
+
+        let mut costs = CellCosts::default();
+        occupancy.add_congestion_costs(&mut costs, &settings);
+        assert!(!costs.is_empty());
+    }
+
+    #[test]
+    fn waits_start_again_for_a_different_cell() {
+        let settings = OccupancySettings::default();
+        let mut occupancy = Occupancy::default();
+        let (a, b, c) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(3),
+        );
+        let (here, left, right) = (
+            SideIPos::new(0, -1),
+            SideIPos::new(-1, -1),
+            SideIPos::new(1, -1),
+        );
+        occupancy.move_to(a, left);
+        occupancy.move_to(b, right);
+        occupancy.move_to(c, here);
+
+        let start = Duration::from_secs(10);
+        let half_wait = settings.max_wait / 2;
+        assert!(!occupancy.try_enter(c, left, 1, start, &settings));
+        assert!(!occupancy.try_enter(c, right, 1, start + half_wait, &settings));
+        assert!(!occupancy.try_enter(c, right, 1, start + settings.max_wait, &settings));
+        let waited = start + half_wait + settings.max_wait;
+        assert!(occupancy.try_enter(c, right, 1, waited, &settings));
+    }
+
+    /// Two ants each side of a meeting point in a tunnel, all trying to get to the other side.
+    #[test]
+    fn ants_meeting_head_on_take_turns() {
+        let settings = OccupancySettings::default();
+        let capacity = settings.tunnel_capacity;
+        let mut occupancy = Occupancy::default();
+        let (west, east) = (SideIPos::new(0, -1), SideIPos::new(1, -1));
+        let eastbound = [Entity::from_raw(1), Entity::from_raw(2)];
+        let westbound = [Entity::from_raw(3), Entity::from_raw(4)];
+        for ant in eastbound {
+            occupancy.move_to(ant, west);
+        }
+        for ant in westbound {
+            occupancy.move_to(ant, east);
+        }
+
+        // Step every ant that isn't through yet once a frame, well short of the longest wait.
+        let start = Duration::from_secs(10);
+        for frame in 0..10 {
+            let now = start + Duration::from_millis(frame * 50);
+            for ant in eastbound {
+                if occupancy.cell_of(ant) == Some(west) {
+                    occupancy.try_enter(ant, east, capacity, now, &settings);
+                }
+            }
+            for ant in westbound {
+                if occupancy.cell_of(ant) == Some(east) {
+                    occupancy.try_enter(ant, west, capacity, now, &settings);
+                }
+            }
+        }
+
+        let mut through_east = occupancy.creatures_in(east).to_vec();
+        through_east.sort();
+        let mut through_west = occupancy.creatures_in(west).to_vec();
+        through_west.sort();
+        assert_eq!(through_east, eastbound);
+        assert_eq!(through_west, westbound);
+    }
+}
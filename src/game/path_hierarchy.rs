@@ -0,0 +1,597 @@
+//! Hierarchical pathfinding (HPA*) over the [SideMapGraph], so long paths don't have to search the
+//! whole map.
+//!
+//! The map is split into square clusters. Where two clusters touch, the crossings between them are
+//! grouped into entrances up to [MAX_ENTRANCE_WIDTH] wide and only the cheapest crossing of each is
+//! kept. The abstract graph links each kept crossing to the cell on the other side, and to every
+//! other entrance of its cluster with the cost of the cheapest path that stays inside the cluster.
+//!
+//! A path is found over the abstract graph first, linking the start and goal to the entrances of
+//! the clusters around them. Each step of it is then filled in with a search inside one cluster, or
+//! inside the clusters around the start or goal for the first and last steps. Since paths can only
+//! cross at the kept entrances they may cost a little more than the best path, up to a quarter more
+//! than plain A* on generated maps. Short paths, and any the abstract graph can't find, use plain A*.
+//!
+//! Pheromone trails and crowds change the weights of a search with [CellCosts]. Across the abstract
+//! graph they only change the edges by the cells at either end, since the cells in between aren't
+//! known, but each step is filled in with them applied to every edge.
+//!
+//! When edges change, the clusters of the cells at their ends are marked dirty and only those are
+//! rebuilt.
+
+use crate::game::pathfinding::{CellCosts, ChangedCells, SideMapGraph};
+use crate::game::positions::SideIPos;
+use bevy::prelude::*;
+use bevy::utils::petgraph::algo::astar;
+use bevy::utils::petgraph::prelude::{EdgeRef, UnGraphMap};
+use bevy::utils::{HashMap, HashSet};
+use pathfinding::prelude::{astar as astar_with, dijkstra_all};
+
+/// Width and height of a cluster in cells.
+pub const CLUSTER_SIZE: i32 = 8;
+
+/// Longest run of crossings that share one entrance.
+const MAX_ENTRANCE_WIDTH: usize = 3;
+
+pub fn cluster_of(pos: SideIPos) -> SideIPos {
+    SideIPos::new(
+        pos.x.div_euclid(CLUSTER_SIZE),
+        pos.y.div_euclid(CLUSTER_SIZE),
+    )
+}
+
+fn neighbour_clusters(cluster: SideIPos) -> [SideIPos; 4] {
+    cluster.sides()
+}
+
+/// Borders are keyed by the cluster on the left or below first.
+fn border_key(a: SideIPos, b: SideIPos) -> (SideIPos, SideIPos) {
+    if (a.x, a.y) < (b.x, b.y) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// The pairs of cells facing each other across a border.
+fn border_pairs((a, b): (SideIPos, SideIPos)) -> impl Iterator<Item = (SideIPos, SideIPos)> {
+    let min_a = *a * CLUSTER_SIZE;
+    let min_b = *b * CLUSTER_SIZE;
+    let horizontal = b.x != a.x;
+
+    (0..CLUSTER_SIZE).map(move |i| {
+        if horizontal {
+            (
+                SideIPos::new(min_a.x + CLUSTER_SIZE - 1, min_a.y + i),
+                SideIPos::new(min_b.x, min_b.y + i),
+            )
+        } else {
+            (
+                SideIPos::new(min_a.x + i, min_a.y + CLUSTER_SIZE - 1),
+                SideIPos::new(min_b.x + i, min_b.y),
+            )
+        }
+    })
+}
+
+/// The cheapest crossing of each entrance along a border. Ties go to the middle of the entrance.
+fn border_crossings(graph: &SideMapGraph, key: (SideIPos, SideIPos)) -> Vec<(SideIPos, SideIPos)> {
+    let mut kept = Vec::new();
+    let mut entrance: Vec<(SideIPos, SideIPos, u64)> = Vec::new();
+
+    let mut flush = |entrance: &mut Vec<(SideIPos, SideIPos, u64)>| {
+        let middle = entrance.len() as i32 - 1;
+        let best = entrance
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, (_, _, weight))| (*weight, (*i as i32 * 2 - middle).abs()))
+            .map(|(_, (a, b, _))| (*a, *b));
+        kept.extend(best);
+        entrance.clear();
+    };
+
+    for (a, b) in border_pairs(key) {
+        match graph.edge_weight(a, b) {
+            Some(weight) => {
+                entrance.push((a, b, *weight));
+                if entrance.len() == MAX_ENTRANCE_WIDTH {
+                    flush(&mut entrance);
+                }
+            }
+            None => flush(&mut entrance),
+        }
+    }
+    flush(&mut entrance);
+
+    kept
+}
+
+/// A cluster and the eight around it.
+fn cluster_block(cluster: SideIPos) -> Vec<SideIPos> {
+    (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| SideIPos::new(cluster.x + dx, cluster.y + dy)))
+        .collect()
+}
+
+/// Edges from a cell that stay inside the given clusters.
+fn successors_within(
+    graph: &SideMapGraph,
+    costs: &CellCosts,
+    clusters: &[SideIPos],
+    pos: SideIPos,
+) -> Vec<(SideIPos, u64)> {
+    graph
+        .edges(pos)
+        .filter(|(_, to, _)| clusters.contains(&cluster_of(*to)))
+        .map(|(from, to, weight)| (to, costs.edge_weight(from, to, *weight)))
+        .collect()
+}
+
+fn heuristic(pos: SideIPos, goal: SideIPos) -> u64 {
+    (*pos - *goal).as_vec2().length() as u64
+}
+
+/// The total weight of the edges along a path. None if any of them are missing.
+pub fn path_cost(graph: &SideMapGraph, costs: &CellCosts, path: &[SideIPos]) -> Option<u64> {
+    path.windows(2)
+        .map(|w| Some(costs.edge_weight(w[0], w[1], *graph.edge_weight(w[0], w[1])?)))
+        .sum()
+}
+
+/// Plain A* over the whole graph.
+pub fn astar_path(
+    graph: &SideMapGraph,
+    start: SideIPos,
+    goal: SideIPos,
+) -> Option<(Vec<SideIPos>, u64)> {
+    let (cost, path) = astar(
+        &**graph,
+        start,
+        |finish| finish == goal,
+        |e| *e.weight(),
+        |z| heuristic(z, goal),
+    )?;
+    Some((path, cost))
+}
+
+/// A* over the whole graph with the costs applied.
+fn astar_with_costs(
+    graph: &SideMapGraph,
+    costs: &CellCosts,
+    start: SideIPos,
+    goal: SideIPos,
+) -> Option<(Vec<SideIPos>, u64)> {
+    if costs.is_empty() {
+        return astar_path(graph, start, goal);
+    }
+
+    astar_with(
+        &start,
+        |pos| {
+            graph
+                .edges(*pos)
+                .map(|(from, to, weight)| (to, costs.edge_weight(from, to, *weight)))
+                .collect::<Vec<_>>()
+        },
+        |pos| heuristic(*pos, goal),
+        |pos| *pos == goal,
+    )
+}
+
+#[derive(Resource, Default, Clone)]
+pub struct PathHierarchy {
+    /// The kept crossings between each pair of touching clusters. See [border_key].
+    borders: HashMap<(SideIPos, SideIPos), Vec<(SideIPos, SideIPos)>>,
+
+    /// The cells of each cluster that are part of a kept crossing.
+    entrances: HashMap<SideIPos, Vec<SideIPos>>,
+
+    /// Entrances linked across borders and to each other within their cluster.
+    graph: UnGraphMap<SideIPos, u64>,
+
+    dirty: HashSet<SideIPos>,
+}
+
+impl PathHierarchy {
+    /// Mark the cluster of a cell whose edges changed.
+    pub fn mark_dirty(&mut self, pos: SideIPos) {
+        self.dirty.insert(cluster_of(pos));
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// Rebuild the dirty clusters. Their neighbours are rebuilt too since the entrances along their
+    /// shared borders may have moved.
+    pub fn rebuild(&mut self, graph: &SideMapGraph) {
+        let dirty: Vec<SideIPos> = self.dirty.drain().collect();
+        if dirty.is_empty() {
+            return;
+        }
+
+        for cluster in &dirty {
+            for neighbour in neighbour_clusters(*cluster) {
+                let key = border_key(*cluster, neighbour);
+                self.borders.insert(key, border_crossings(graph, key));
+            }
+        }
+
+        let rebuild: HashSet<SideIPos> = dirty
+            .iter()
+            .flat_map(|cluster| std::iter::once(*cluster).chain(neighbour_clusters(*cluster)))
+            .collect();
+
+        for cluster in &rebuild {
+            for entrance in self.entrances.remove(cluster).unwrap_or_default() {
+                self.graph.remove_node(entrance);
+            }
+        }
+
+        for cluster in &rebuild {
+            let mut entrances = Vec::new();
+            for neighbour in neighbour_clusters(*cluster) {
+                let key = border_key(*cluster, neighbour);
+                let crossings = self
+                    .borders
+                    .entry(key)
+                    .or_insert_with(|| border_crossings(graph, key));
+
+                for (a, b) in crossings.iter() {
+                    entrances.push(if cluster_of(*a) == *cluster { *a } else { *b });
+                }
+            }
+            entrances.sort_by_key(|pos| (pos.x, pos.y));
+            entrances.dedup();
+
+            for entrance in &entrances {
+                self.graph.add_node(*entrance);
+            }
+
+            // Entrances are linked by the plain weights. Costs are applied when searching.
+            let plain = CellCosts::default();
+            for (i, entrance) in entrances.iter().enumerate() {
+                let costs = dijkstra_all(entrance, |pos| {
+                    successors_within(graph, &plain, &[*cluster], *pos)
+                });
+
+                for other in &entrances[i + 1..] {
+                    if let Some((_, cost)) = costs.get(other) {
+                        self.graph.add_edge(*entrance, *other, *cost);
+                    }
+                }
+            }
+
+            self.entrances.insert(*cluster, entrances);
+        }
+
+        for cluster in &rebuild {
+            for neighbour in neighbour_clusters(*cluster) {
+                let Some(crossings) = self.borders.get(&border_key(*cluster, neighbour)) else {
+                    continue;
+                };
+
+                for (a, b) in crossings {
+                    let Some(weight) = graph.edge_weight(*a, *b) else {
+                        continue;
+                    };
+
+                    if self.graph.contains_node(*a) && self.graph.contains_node(*b) {
+                        self.graph.add_edge(*a, *b, *weight);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The entrances of the clusters around a cell, with the cost of getting to each from the cell.
+    ///
+    /// Looking further than the cell's own cluster means paths don't have to leave it through one
+    /// of its entrances, which would be a detour for cells near its edge.
+    fn links(
+        &self,
+        graph: &SideMapGraph,
+        costs: &CellCosts,
+        pos: SideIPos,
+    ) -> Vec<(SideIPos, u64)> {
+        let block = cluster_block(cluster_of(pos));
+        let found = dijkstra_all(&pos, |pos| successors_within(graph, costs, &block, *pos));
+
+        block
+            .iter()
+            .filter_map(|cluster| self.entrances.get(cluster))
+            .flatten()
+            .filter_map(|entrance| Some((*entrance, found.get(entrance)?.1)))
+            .collect()
+    }
+
+    fn abstract_path(
+        &self,
+        graph: &SideMapGraph,
+        costs: &CellCosts,
+        start: SideIPos,
+        goal: SideIPos,
+    ) -> Option<Vec<SideIPos>> {
+        let start_links = self.links(graph, costs, start);
+        let goal_links: HashMap<SideIPos, u64> =
+            self.links(graph, costs, goal).into_iter().collect();
+
+        let (route, _) = astar_with(
+            &start,
+            |pos| {
+                let mut successors: Vec<(SideIPos, u64)> = self
+                    .graph
+                    .edges(*pos)
+                    .map(|(from, to, weight)| (to, costs.edge_weight(from, to, *weight)))
+                    .collect();
+
+                if *pos == start {
+                    successors.extend(start_links.iter().copied());
+                }
+
+                if let Some(cost) = goal_links.get(pos) {
+                    successors.push((goal, *cost));
+                }
+
+                successors
+            },
+            |pos| heuristic(*pos, goal),
+            |pos| *pos == goal,
+        )?;
+
+        // Fill in each step of the route.
+        let mut path = vec![start];
+        for step in route.windows(2) {
+            let (from, to) = (step[0], step[1]);
+            let clusters = if from == start {
+                cluster_block(cluster_of(start))
+            } else if to == goal {
+                cluster_block(cluster_of(goal))
+            } else if cluster_of(from) != cluster_of(to) {
+                path.push(to);
+                continue;
+            } else {
+                vec![cluster_of(from)]
+            };
+
+            let (segment, _) = astar_with(
+                &from,
+                |pos| successors_within(graph, costs, &clusters, *pos),
+                |pos| heuristic(*pos, to),
+                |pos| *pos == to,
+            )?;
+            path.extend(segment.into_iter().skip(1));
+        }
+
+        Some(path)
+    }
+
+    /// Find a path and its cost with the costs applied. Falls back to plain A* for short paths, or
+    /// when the hierarchy can't find one, e.g. if it's out of date.
+    pub fn find_path(
+        &self,
+        graph: &SideMapGraph,
+        start: SideIPos,
+        goal: SideIPos,
+        costs: &CellCosts,
+    ) -> Option<(Vec<SideIPos>, u64)> {
+        if !graph.contains_node(start) || !graph.contains_node(goal) {
+            return None;
+        }
+
+        let distance = *cluster_of(start) - *cluster_of(goal);
+        if distance.x.abs() <= 1 && distance.y.abs() <= 1 {
+            return astar_with_costs(graph, costs, start, goal);
+        }
+
+        let hierarchical = self
+            .abstract_path(graph, costs, start, goal)
+            .and_then(|path| {
+                let cost = path_cost(graph, costs, &path)?;
+                Some((path, cost))
+            });
+
+        hierarchical.or_else(|| astar_with_costs(graph, costs, start, goal))
+    }
+}
+
+/// Mark the clusters of any cells whose edges changed.
+pub fn mark_dirty_path_clusters(
+    mut hierarchy: ResMut<PathHierarchy>,
+    changed_cells: Res<ChangedCells>,
+) {
+    for pos in changed_cells.iter() {
+        hierarchy.mark_dirty(*pos);
+    }
+}
+
+pub fn update_path_hierarchy(graph: Res<SideMapGraph>, mut hierarchy: ResMut<PathHierarchy>) {
+    if hierarchy.is_dirty() {
+        hierarchy.rebuild(&graph);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::{CellContent, CellGrid};
+    use crate::game::map_generator::{MapGenerator, MapGeneratorParams, MapSeed};
+    use crate::game::movement::{add_grid_to_graph, movement_weight, update_edges_around};
+    use rand::prelude::*;
+
+    /// Hierarchical paths cost at most this fraction more than the best path.
+    const COST_TOLERANCE: f32 = 0.25;
+
+    /// A generated map with some random tunnels dug out of it.
+    fn random_map(seed: u64) -> CellGrid {
+        let mut grid =
+            MapGenerator::new(MapSeed::new(seed), MapGeneratorParams::default()).generate();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let cells: Vec<SideIPos> = grid
+            .iter()
+            .filter(|(pos, cell)| pos.y < 0 && !cell.is_rock())
+            .map(|(pos, _)| pos)
+            .collect();
+
+        for _ in 0..20 {
+            let mut pos = *cells.choose(&mut rng).unwrap();
+            for _ in 0..30 {
+                if grid.get(pos).map(|cell| cell.is_rock()).unwrap_or(true) || pos.y >= 0 {
+                    break;
+                }
+                grid.set(pos, CellContent::empty_underground());
+                pos = *pos.sides().choose(&mut rng).unwrap();
+            }
+        }
+
+        grid
+    }
+
+    fn graph_for(grid: &CellGrid) -> SideMapGraph {
+        let mut graph = SideMapGraph::new();
+        add_grid_to_graph(&mut graph, grid);
+        graph
+    }
+
+    fn hierarchy_for(graph: &SideMapGraph) -> PathHierarchy {
+        let mut hierarchy = PathHierarchy::default();
+        for pos in graph.nodes() {
+            hierarchy.mark_dirty(pos);
+        }
+        hierarchy.rebuild(graph);
+        hierarchy
+    }
+
+    /// Compare against plain A* for random pairs of far apart cells.
+    fn assert_close_to_astar(graph: &SideMapGraph, hierarchy: &PathHierarchy, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let nodes: Vec<SideIPos> = graph.nodes().collect();
+        let mut compared = 0;
+
+        while compared < 50 {
+            let start = *nodes.choose(&mut rng).unwrap();
+            let goal = *nodes.choose(&mut rng).unwrap();
+            let Some((_, best)) = astar_path(graph, start, goal) else {
+                continue;
+            };
+
+            let (path, cost) = hierarchy
+                .find_path(graph, start, goal, &CellCosts::default())
+                .unwrap();
+            assert_eq!(path.first(), Some(&start));
+            assert_eq!(path.last(), Some(&goal));
+            assert_eq!(path_cost(graph, &CellCosts::default(), &path), Some(cost));
+            assert!(cost >= best);
+            assert!(
+                cost as f32 <= best as f32 * (1.0 + COST_TOLERANCE),
+                "{start:?} to {goal:?} cost {cost}, A* {best}"
+            );
+            compared += 1;
+        }
+    }
+
+    #[test]
+    fn paths_cost_about_the_same_as_astar() {
+        for seed in 0..5 {
+            let graph = graph_for(&random_map(seed));
+            let hierarchy = hierarchy_for(&graph);
+            assert_close_to_astar(&graph, &hierarchy, seed);
+        }
+    }
+
+    fn sorted_edges(hierarchy: &PathHierarchy) -> Vec<(i32, i32, i32, i32, u64)> {
+        let mut edges: Vec<_> = hierarchy
+            .graph
+            .all_edges()
+            .map(|(a, b, weight)| {
+                let (a, b) = border_key(a, b);
+                (a.x, a.y, b.x, b.y, *weight)
+            })
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn changes_only_rebuild_nearby_clusters() {
+        let mut grid = random_map(7);
+        let mut graph = graph_for(&grid);
+        let mut hierarchy = hierarchy_for(&graph);
+
+        // Dig a long tunnel across the map.
+        let tunnel: Vec<SideIPos> = grid
+            .iter()
+            .map(|(pos, _)| pos)
+            .filter(|pos| pos.y == -10)
+            .collect();
+        for pos in tunnel {
+            grid.set(pos, CellContent::empty_underground());
+            let cell_at = |pos| grid.get(pos).copied();
+            for changed in update_edges_around(&mut graph, pos, |from, to| {
+                movement_weight(from, to, &cell_at)
+            }) {
+                hierarchy.mark_dirty(changed);
+            }
+        }
+
+        // Only the row of clusters holding the changed edges is dirty.
+        let all: HashSet<SideIPos> = hierarchy.entrances.keys().copied().collect();
+        let dirty_rows: HashSet<i32> = hierarchy.dirty.iter().map(|cluster| cluster.y).collect();
+        assert_eq!(dirty_rows, HashSet::from_iter([-2]));
+        assert!(hierarchy.dirty.len() < all.len());
+
+        // A cluster further away than the neighbours of the dirty ones isn't touched.
+        let far = *all
+            .iter()
+            .find(|cluster| cluster.y < -3)
+            .expect("The map is deep enough to have a far away cluster");
+        let marker = vec![SideIPos::new(i32::MAX, i32::MAX)];
+        hierarchy.entrances.insert(far, marker.clone());
+
+        hierarchy.rebuild(&graph);
+        assert_eq!(hierarchy.entrances[&far], marker);
+        hierarchy.entrances.remove(&far);
+
+        let full = hierarchy_for(&graph);
+        assert_eq!(hierarchy.borders, full.borders);
+        assert_eq!(sorted_edges(&hierarchy), sorted_edges(&full));
+
+        assert_close_to_astar(&graph, &hierarchy, 7);
+    }
+
+    #[test]
+    fn searches_with_trails_go_through_the_hierarchy() {
+        // Two tunnels of the same length between a pair of shafts, far enough apart that the
+        // search goes over the clusters.
+        let mut grid = CellGrid::new(
+            IVec2::new(0, -9),
+            IVec2::new(41, 9),
+            CellContent::rock(true),
+        );
+        for x in 0..=40 {
+            grid.set(SideIPos::new(x, -2), CellContent::empty_underground());
+            grid.set(SideIPos::new(x, -8), CellContent::empty_underground());
+        }
+        for y in -8..=-2 {
+            grid.set(SideIPos::new(0, y), CellContent::empty_underground());
+            grid.set(SideIPos::new(40, y), CellContent::empty_underground());
+        }
+        let graph = graph_for(&grid);
+        let hierarchy = hierarchy_for(&graph);
+        let (start, goal) = (SideIPos::new(0, -5), SideIPos::new(40, -5));
+
+        for trail in [-2, -8] {
+            let mut costs = CellCosts::default();
+            for x in 1..40 {
+                costs.multiply(SideIPos::new(x, trail), 0.5);
+            }
+
+            let route = hierarchy
+                .abstract_path(&graph, &costs, start, goal)
+                .expect("The hierarchy finds a way with trails");
+            let (path, _) = hierarchy.find_path(&graph, start, goal, &costs).unwrap();
+            assert_eq!(path, route);
+            assert!(path.contains(&SideIPos::new(20, trail)));
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Check paths that ants are already following when the map changes under them.
+//!
+//! Paths with a remaining step near a changed cell are flagged, then checked against the graph. A
+//! path is searched for again if one of its steps can't be walked any more, or if the rest of it
+//! costs a lot more than planned. With [PathfindingSettings::repath_for_shortcuts], changes near a
+//! path also cause a search in case they opened up a cheaper way.
+//!
+//! Each ant searches again at most once every [PathfindingSettings::min_repath_interval]. Flagged
+//! paths stay flagged until they can be searched for again, and an ant whose path lost a step waits
+//! where it is until then.
+
+use crate::game::pathfinding::{
+    ChangedCells, Path, PathProgress, PathfindingSettings, SideMapGraph,
+};
+use crate::game::positions::SideIPos;
+use crate::game::time::GameTime;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use std::time::Duration;
+
+/// How far around a path a change can be to open a shortcut, when looking for them.
+const SHORTCUT_MARGIN: i32 = 4;
+
+/// When the ant last searched for a path again because the map changed.
+#[derive(Component, Debug, Deref)]
+pub struct LastRepath(Duration);
+
+/// What to do with a path after checking it.
+#[derive(Debug, PartialEq)]
+enum Check {
+    Keep(Vec<u64>),
+    Repath,
+    /// A step can't be walked any more.
+    Blocked,
+}
+
+/// Whether a path near a change is still worth following. Gives the weights of its steps if so.
+fn check_path(
+    graph: &SideMapGraph,
+    settings: &PathfindingSettings,
+    progress: &PathProgress,
+) -> Check {
+    let weights: Option<Vec<u64>> = progress
+        .remaining_steps()
+        .windows(2)
+        .map(|step| graph.edge_weight(step[0], step[1]).copied())
+        .collect();
+
+    let Some(weights) = weights else {
+        return Check::Blocked;
+    };
+
+    let cost: u64 = weights.iter().sum();
+    let limit = progress.expected_cost() as f32 * (1.0 + settings.repath_cost_increase);
+    if cost as f32 > limit || settings.repath_for_shortcuts {
+        Check::Repath
+    } else {
+        Check::Keep(weights)
+    }
+}
+
+/// Whether a changed cell is close enough to a path that it might have changed it.
+fn is_near_path(
+    changed: &HashSet<SideIPos>,
+    settings: &PathfindingSettings,
+    steps: &[SideIPos],
+) -> bool {
+    if steps.iter().any(|step| changed.contains(step)) {
+        return true;
+    }
+
+    if !settings.repath_for_shortcuts {
+        return false;
+    }
+
+    let min_x = steps.iter().map(|step| step.x).min().unwrap_or_default() - SHORTCUT_MARGIN;
+    let max_x = steps.iter().map(|step| step.x).max().unwrap_or_default() + SHORTCUT_MARGIN;
+    let min_y = steps.iter().map(|step| step.y).min().unwrap_or_default() - SHORTCUT_MARGIN;
+    let max_y = steps.iter().map(|step| step.y).max().unwrap_or_default() + SHORTCUT_MARGIN;
+    changed
+        .iter()
+        .any(|pos| (min_x..=max_x).contains(&pos.x) && (min_y..=max_y).contains(&pos.y))
+}
+
+/// Flag paths near cells whose edges changed.
+pub fn flag_paths_near_changes(
+    settings: Res<PathfindingSettings>,
+    changed_cells: Res<ChangedCells>,
+    mut paths: Query<&mut Path>,
+) {
+    if changed_cells.is_empty() {
+        return;
+    }
+
+    for mut path in &mut paths {
+        let Path::Progress(progress) = &mut *path else {
+            continue;
+        };
+
+        if is_near_path(&changed_cells, &settings, progress.remaining_steps()) {
+            progress.needs_check = true;
+        }
+    }
+}
+
+/// Check flagged paths, searching for them again if they aren't worth following any more.
+pub fn validate_paths(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    graph: Res<SideMapGraph>,
+    settings: Res<PathfindingSettings>,
+    mut query: Query<(Entity, &mut Path, Option<&LastRepath>)>,
+) {
+    let now = time.since_startup();
+
+    for (entity, mut path, last_repath) in &mut query {
+        let Path::Progress(progress) = &mut *path else {
+            continue;
+        };
+
+        if !progress.needs_check {
+            continue;
+        }
+
+        let check = check_path(&graph, &settings, progress);
+        let is_cooling_down = last_repath
+            .map(|last| now < **last + settings.min_repath_interval)
+            .unwrap_or(false);
+        if is_cooling_down {
+            // Only searching again is rate limited. Walking into a gap isn't.
+            progress.is_blocked = check == Check::Blocked;
+            continue;
+        }
+
+        progress.needs_check = false;
+        let Some(goal) = progress.goal() else {
+            continue;
+        };
+
+        match check {
+            Check::Keep(weights) => progress.set_step_weights(weights),
+            Check::Repath | Check::Blocked => {
+                info!(?entity, ?goal, "Map changed under path, searching again");
+                path.set_target(goal);
+                commands.entity(entity).insert(LastRepath(now));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_graph(length: i32) -> SideMapGraph {
+        let mut graph = SideMapGraph::new();
+        for x in 1..length {
+            graph.add_edge(SideIPos::new(x - 1, 0), SideIPos::new(x, 0), 1);
+        }
+        graph
+    }
+
+    fn line_path(length: i32) -> Vec<SideIPos> {
+        (0..length).map(|x| SideIPos::new(x, 0)).collect()
+    }
+
+    #[test]
+    fn blocked_and_expensive_paths_are_searched_again() {
+        let settings = PathfindingSettings::default();
+        let mut graph = line_graph(5);
+        let progress = PathProgress::new(&graph, line_path(5));
+        assert_eq!(
+            check_path(&graph, &settings, &progress),
+            Check::Keep(vec![1; 4])
+        );
+
+        graph.add_edge(SideIPos::new(1, 0), SideIPos::new(2, 0), 2);
+        assert_eq!(
+            check_path(&graph, &settings, &progress),
+            Check::Keep(vec![1, 2, 1, 1])
+        );
+
+        graph.add_edge(SideIPos::new(1, 0), SideIPos::new(2, 0), 10);
+        assert_eq!(check_path(&graph, &settings, &progress), Check::Repath);
+
+        graph.remove_edge(SideIPos::new(1, 0), SideIPos::new(2, 0));
+        assert_eq!(check_path(&graph, &settings, &progress), Check::Blocked);
+    }
+
+    #[test]
+    fn blocked_ants_wait_until_they_can_search_again() {
+        let mut graph = line_graph(5);
+        let mut progress = PathProgress::new(&graph, line_path(5));
+        progress.needs_check = true;
+        graph.remove_edge(SideIPos::new(1, 0), SideIPos::new(2, 0));
+
+        let mut world = World::new();
+        world.insert_resource(GameTime::default());
+        world.insert_resource(graph);
+        world.insert_resource(PathfindingSettings::default());
+        let ant = world
+            .spawn((Path::Progress(progress), LastRepath(Duration::ZERO)))
+            .id();
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(validate_paths);
+        schedule.run(&mut world);
+
+        // Searched for a path too recently, so the ant stops where it is.
+        let Path::Progress(progress) = world.get::<Path>(ant).unwrap() else {
+            panic!("The path was replaced during the cooldown");
+        };
+        assert!(progress.is_blocked);
+        assert!(progress.needs_check);
+
+        world.entity_mut(ant).remove::<LastRepath>();
+        schedule.run(&mut world);
+        assert!(matches!(world.get::<Path>(ant), Some(Path::NeedsPath(_))));
+    }
+
+    #[test]
+    fn shortcuts_are_only_looked_for_when_enabled() {
+        let mut settings = PathfindingSettings::default();
+        let steps = line_path(5);
+        let changed: HashSet<SideIPos> = [SideIPos::new(2, -3)].into_iter().collect();
+        assert!(!is_near_path(&changed, &settings, &steps));
+
+        settings.repath_for_shortcuts = true;
+        assert!(is_near_path(&changed, &settings, &steps));
+    }
+}
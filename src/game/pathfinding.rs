@@ -1,18 +1,56 @@
-use crate::game::map::SIDE_CELL_SIZE;
+use crate::game::chunks::{chunk_of, nearby_chunks, LoadChunkEvent, SideMapChunks};
+use crate::game::flow_field::{FlowDestination, FlowFields};
+use crate::game::map::{CellContent, SideMapPosToEntities, SIDE_CELL_SIZE};
+use crate::game::movement::can_stand_in;
+use crate::game::occupancy::{Occupancy, OccupancySettings};
+use crate::game::path_hierarchy::PathHierarchy;
+use crate::game::pheromones::{PheromoneSettings, Pheromones, Scent, TrailCosts};
 use crate::game::plugin::Speed;
 use crate::game::positions::SideIPos;
 use crate::game::side_effects::{CalculatedSideEffects, SideEffectDiscriminants};
+use crate::game::steering::shortcut;
 use crate::game::time::GameTime;
 use crate::input::{InputAction, InputStates};
 use bevy::prelude::*;
-use bevy::utils::petgraph::algo::astar;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
 use bevy::utils::petgraph::prelude::{EdgeRef, UnGraphMap};
 use bevy::utils::petgraph::visit::IntoEdgeReferences;
+use bevy::utils::{HashMap, HashSet};
 use bevy_prototype_debug_lines::DebugLines;
+use futures_lite::future;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Resource, Default)]
 pub struct PathfindingLinesDebug(pub bool);
 
+#[derive(Resource, Debug)]
+pub struct PathfindingSettings {
+    /// Most path searches started in one frame. The rest wait for the next frame, so a batch of
+    /// eggs hatching at once doesn't stall a frame.
+    pub max_searches_per_frame: usize,
+
+    /// Shortest time between an ant's path being searched for again because the map changed.
+    pub min_repath_interval: Duration,
+
+    /// Search again when the rest of a path costs this fraction more than when it was planned.
+    pub repath_cost_increase: f32,
+
+    /// Also search again when cells change near a path, in case they opened up a shortcut.
+    pub repath_for_shortcuts: bool,
+}
+
+impl Default for PathfindingSettings {
+    fn default() -> Self {
+        Self {
+            max_searches_per_frame: 16,
+            min_repath_interval: Duration::from_secs(1),
+            repath_cost_increase: 0.5,
+            repath_for_shortcuts: false,
+        }
+    }
+}
+
 // pub fn toggle_world_inspector(
 //     input_states: Res<InputStates>,
 //     mut world_inspector_active: ResMut<WorldInspectorActive>,
@@ -66,7 +104,7 @@ pub struct VisitedNodeEvent {
     pub is_final: bool,
 }
 
-#[derive(Resource, Deref, DerefMut, Default)]
+#[derive(Resource, Deref, DerefMut, Default, Clone)]
 pub struct SideMapGraph(UnGraphMap<SideIPos, u64>);
 
 impl SideMapGraph {
@@ -81,13 +119,29 @@ impl From<UnGraphMap<SideIPos, u64>> for SideMapGraph {
     }
 }
 
+/// Cells whose edges in the [SideMapGraph] changed this frame, including newly spawned cells. The
+/// path hierarchy, flow fields and path checks all work from this one set.
+#[derive(Resource, Deref, DerefMut, Default, Debug)]
+pub struct ChangedCells(HashSet<SideIPos>);
+
+/// Runs after everything that reads [ChangedCells] has seen this frame's changes.
+pub fn clear_changed_cells(mut changed_cells: ResMut<ChangedCells>) {
+    if !changed_cells.is_empty() {
+        changed_cells.clear();
+    }
+}
+
 #[derive(Component, Debug)]
 pub enum Path {
     None,
     /// Need to calculate the path for this target destination.
     NeedsPath(SideIPos),
+    /// Searching for a path in the background. See [PathSearch].
+    Searching(SideIPos),
     /// We have a path and are progressing towards the target.
     Progress(PathProgress),
+    /// Following a flow field one cell at a time to the nearest of its targets.
+    FollowField(FieldProgress),
     /// Made it
     Completed(SideIPos),
     /// Could not create a path to the target.
@@ -99,12 +153,23 @@ impl Path {
         *self = Path::NeedsPath(target);
     }
 
+    /// Head to the nearest target of a flow field, without searching for a path.
+    pub fn follow_field(&mut self, destination: FlowDestination) {
+        *self = Path::FollowField(FieldProgress {
+            destination,
+            next_step: None,
+        });
+    }
+
     pub fn cancel(&mut self) {
         *self = Path::None;
     }
 
     pub fn is_progressing(&self) -> bool {
-        matches!(self, Path::Progress(_)) || matches!(self, Path::NeedsPath(_))
+        matches!(
+            self,
+            Path::Progress(_) | Path::NeedsPath(_) | Path::Searching(_) | Path::FollowField(_)
+        )
     }
 
     pub fn did_complete(&self) -> bool {
@@ -128,50 +193,376 @@ impl Path {
             _ => None,
         }
     }
+
+    /// Called when arriving at the next step. Returns the steps walked through to get there, and
+    /// the step to walk to after it, or None if the path is over. `is_open` is where a creature can
+    /// cut across to a later step.
+    fn step_reached(
+        &mut self,
+        step: SideIPos,
+        flow_fields: &FlowFields,
+        is_open: &impl Fn(SideIPos) -> bool,
+    ) -> (Vec<SideIPos>, Option<SideIPos>) {
+        match self {
+            Path::Progress(progress) => {
+                let visited = progress.remaining_steps.drain(..=progress.aim).collect();
+                let weights = (progress.aim + 1).min(progress.step_weights.len());
+                progress.step_weights.drain(..weights);
+
+                progress.aim = shortcut(step, &progress.remaining_steps, is_open);
+                let next_step = progress.remaining_steps.get(progress.aim).copied();
+                if next_step.is_none() {
+                    *self = Path::Completed(step);
+                }
+                (visited, next_step)
+            }
+            Path::FollowField(progress) => {
+                let Some(field) = flow_fields.get(progress.destination) else {
+                    *self = Path::Failed(step);
+                    return (vec![step], None);
+                };
+
+                if field.is_target(step) {
+                    *self = Path::Completed(step);
+                    return (vec![step], None);
+                }
+
+                let Some(next_step) = field.next_step(step) else {
+                    warn!(?progress.destination, "Flow field no longer reaches a target");
+                    *self = Path::Failed(step);
+                    return (vec![step], None);
+                };
+
+                progress.next_step = Some(next_step);
+                (vec![step], Some(next_step))
+            }
+            _ => (vec![], None),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct PathProgress {
     remaining_steps: Vec<SideIPos>,
+
+    /// The weight of the edge from each remaining step to the one after it, as of when the path
+    /// was planned or last checked.
+    step_weights: Vec<u64>,
+
+    /// The index of the step being walked to in a straight line, cutting past the ones before it.
+    aim: usize,
+
+    /// The map changed near the path, so it should be checked. See
+    /// [crate::game::path_validation].
+    pub needs_check: bool,
+
+    /// A step of the path can't be walked any more, so the ant waits until it searches again.
+    pub is_blocked: bool,
+}
+
+impl PathProgress {
+    pub fn new(graph: &SideMapGraph, steps: Vec<SideIPos>) -> Self {
+        let step_weights = steps
+            .windows(2)
+            .map(|step| {
+                graph
+                    .edge_weight(step[0], step[1])
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        Self {
+            remaining_steps: steps,
+            step_weights,
+            aim: 0,
+            needs_check: false,
+            is_blocked: false,
+        }
+    }
+
+    pub fn remaining_steps(&self) -> &[SideIPos] {
+        &self.remaining_steps
+    }
+
+    pub fn goal(&self) -> Option<SideIPos> {
+        self.remaining_steps.last().copied()
+    }
+
+    /// The cost of the rest of the path when it was planned or last checked.
+    pub fn expected_cost(&self) -> u64 {
+        self.step_weights.iter().sum()
+    }
+
+    /// Remember the weights the rest of the path has now.
+    pub fn set_step_weights(&mut self, step_weights: Vec<u64>) {
+        self.step_weights = step_weights;
+    }
 }
 
+#[derive(Debug)]
+pub struct FieldProgress {
+    destination: FlowDestination,
+
+    /// None until the first step is picked in [needs_path].
+    next_step: Option<SideIPos>,
+}
+
+/// Changes to the weights of the graph for one search, e.g. from pheromone trails and crowds.
+#[derive(Debug, Default)]
+pub struct CellCosts {
+    /// Changes these are made on top of, shared with other searches.
+    base: Option<Arc<CellCosts>>,
+
+    /// What to multiply the weight of edges into and out of each cell by.
+    multipliers: HashMap<SideIPos, f32>,
+
+    /// Added to the weight of edges into each cell.
+    extra: HashMap<SideIPos, u64>,
+}
+
+impl CellCosts {
+    pub fn on_top_of(base: Arc<CellCosts>) -> Self {
+        Self {
+            base: Some(base),
+            ..Default::default()
+        }
+    }
+
+    pub fn multiply(&mut self, pos: SideIPos, multiplier: f32) {
+        *self.multipliers.entry(pos).or_insert(1.0) *= multiplier;
+    }
+
+    pub fn add(&mut self, pos: SideIPos, cost: u64) {
+        *self.extra.entry(pos).or_default() += cost;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.multipliers.is_empty()
+            && self.extra.is_empty()
+            && self
+                .base
+                .as_ref()
+                .map(|base| base.is_empty())
+                .unwrap_or(true)
+    }
+
+    pub fn edge_weight(&self, from: SideIPos, to: SideIPos, weight: u64) -> u64 {
+        let weight = match &self.base {
+            Some(base) => base.edge_weight(from, to, weight),
+            None => weight,
+        };
+
+        let multiplier = |pos| self.multipliers.get(&pos).copied().unwrap_or(1.0);
+        let multiplier = (multiplier(from) + multiplier(to)) / 2.0;
+        let weight = ((weight as f32 * multiplier).round() as u64).max(1);
+        weight + self.extra.get(&to).copied().unwrap_or_default()
+    }
+}
+
+/// A copy of the graph and hierarchy that path searches run against, so they can run off the main
+/// thread while the map changes. Only copied again when a search starts after the graph changed.
+#[derive(Resource, Default)]
+pub struct GraphSnapshot {
+    /// Goes up every time the graph changes.
+    version: u64,
+
+    /// None if the graph changed since it was last copied.
+    copy: Option<(Arc<SideMapGraph>, Arc<PathHierarchy>)>,
+}
+
+impl GraphSnapshot {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn invalidate(&mut self) {
+        self.version += 1;
+        self.copy = None;
+    }
+
+    fn get(
+        &mut self,
+        graph: &SideMapGraph,
+        hierarchy: &PathHierarchy,
+    ) -> (Arc<SideMapGraph>, Arc<PathHierarchy>) {
+        let (graph, hierarchy) = self
+            .copy
+            .get_or_insert_with(|| (Arc::new(graph.clone()), Arc::new(hierarchy.clone())));
+        (graph.clone(), hierarchy.clone())
+    }
+}
+
+/// A path search running on the [AsyncComputeTaskPool].
+#[derive(Component)]
+pub struct PathSearch {
+    goal: SideIPos,
+
+    /// The [GraphSnapshot] version the search runs against.
+    version: u64,
+
+    task: Task<Option<(Vec<SideIPos>, u64)>>,
+}
+
+/// Whether every step of a path is still joined to the next.
+fn is_walkable(graph: &SideMapGraph, path: &[SideIPos]) -> bool {
+    path.windows(2)
+        .all(|step| graph.contains_edge(step[0], step[1]))
+}
+
+pub fn update_graph_snapshot(
+    graph: Res<SideMapGraph>,
+    hierarchy: Res<PathHierarchy>,
+    mut snapshot: ResMut<GraphSnapshot>,
+) {
+    if graph.is_changed() || hierarchy.is_changed() {
+        snapshot.invalidate();
+    }
+}
+
+/// Start searches for paths that need one, up to [PathfindingSettings::max_searches_per_frame].
+/// Paths into chunks that haven't been generated yet wait for them to load.
+///
+/// Pheromone trails and crowds change the weights the search goes by. See
+/// [crate::game::pheromones] and [crate::game::occupancy].
 pub fn needs_path(
+    mut commands: Commands,
     graph: Res<SideMapGraph>,
-    mut debug_lines: ResMut<DebugLines>,
-    mut query: Query<(&mut Path, &Transform)>,
+    hierarchy: Res<PathHierarchy>,
+    flow_fields: Res<FlowFields>,
+    settings: Res<PathfindingSettings>,
+    pheromones: Res<Pheromones>,
+    pheromone_settings: Res<PheromoneSettings>,
+    mut trail_costs: ResMut<TrailCosts>,
+    occupancy: Res<Occupancy>,
+    occupancy_settings: Res<OccupancySettings>,
+    chunks: Res<SideMapChunks>,
+    mut snapshot: ResMut<GraphSnapshot>,
+    mut query: Query<(Entity, &mut Path, &Transform, Option<&Scent>)>,
+    mut load_chunk_writer: EventWriter<LoadChunkEvent>,
 ) {
-    for (mut path, transform) in query.iter_mut() {
-        let Path::NeedsPath(goal) = &*path else {
+    let task_pool = AsyncComputeTaskPool::get();
+    let mut started = 0;
+    let mut costs_for_scent = HashMap::new();
+
+    for (entity, mut path, transform, scent) in query.iter_mut() {
+        let start = SideIPos::from(transform);
+
+        if let Path::FollowField(progress) = &mut *path {
+            if progress.next_step.is_some() {
+                continue;
+            }
+
+            let Some(field) = flow_fields.get(progress.destination) else {
+                warn!(?progress.destination, "No flow field yet");
+                continue;
+            };
+
+            if field.is_target(start) {
+                *path = Path::Completed(start);
+            } else if field.cost(start).is_some() {
+                // Step onto the middle of the cell we're in first, like a searched path does.
+                progress.next_step = Some(start);
+            } else {
+                warn!(?progress.destination, "Can't reach any target of the flow field");
+                *path = Path::Failed(start);
+            }
+            continue;
+        }
+
+        let Path::NeedsPath(goal) = *path else {
             continue;
         };
 
-        let start = SideIPos::from(transform);
+        if start == goal {
+            *path = Path::Completed(goal);
+            continue;
+        }
 
-        if start == *goal {
-            *path = Path::Completed(*goal);
+        for chunk in nearby_chunks(goal) {
+            if chunks.is_missing(chunk) {
+                load_chunk_writer.send(LoadChunkEvent(chunk));
+            }
+        }
+        if chunks.is_missing(chunk_of(goal)) {
+            load_chunk_writer.send(LoadChunkEvent(chunk_of(goal)));
             continue;
         }
 
-        let result = astar(
-            &**graph,
-            start,
-            |finish| finish == *goal,
-            |e| *e.weight(),
-            |z| (*z - **goal).as_vec2().length() as u64,
-        );
+        if started >= settings.max_searches_per_frame {
+            continue;
+        }
+        started += 1;
+
+        let scent = scent.and_then(|scent| **scent);
+        let costs = costs_for_scent
+            .entry(scent)
+            .or_insert_with(|| {
+                let trails = trail_costs.get(scent, &pheromones, &pheromone_settings);
+                let mut costs = CellCosts::on_top_of(trails);
+                occupancy.add_congestion_costs(&mut costs, &occupancy_settings);
+                Arc::new(costs)
+            })
+            .clone();
+
+        let (graph, hierarchy) = snapshot.get(&graph, &hierarchy);
+        let task = task_pool.spawn(async move { hierarchy.find_path(&graph, start, goal, &costs) });
+        commands.entity(entity).insert(PathSearch {
+            goal,
+            version: snapshot.version(),
+            task,
+        });
+        *path = Path::Searching(goal);
+    }
+}
+
+/// Pick up finished searches. A path found on an older graph is searched for again if the map
+/// changed under it so that it can't be walked any more.
+pub fn poll_path_searches(
+    mut commands: Commands,
+    graph: Res<SideMapGraph>,
+    snapshot: Res<GraphSnapshot>,
+    debug: Res<PathfindingLinesDebug>,
+    mut debug_lines: ResMut<DebugLines>,
+    mut query: Query<(Entity, &mut Path, &mut PathSearch)>,
+) {
+    for (entity, mut path, mut search) in query.iter_mut() {
+        let Some(result) = future::block_on(future::poll_once(&mut search.task)) else {
+            continue;
+        };
 
-        if let Some((_, found_path)) = result {
-            for (a, b) in found_path.windows(2).map(|w| (w[0], w[1])) {
-                let a = a.to_world_vec2() + SIDE_CELL_SIZE as f32 / 2f32;
-                let b = b.to_world_vec2() + SIDE_CELL_SIZE as f32 / 2f32;
-                debug_lines.line_colored(a.extend(0f32), b.extend(0f32), 1.0, Color::LIME_GREEN);
+        commands.entity(entity).remove::<PathSearch>();
+
+        // The path was changed or cancelled while searching.
+        if !matches!(*path, Path::Searching(goal) if goal == search.goal) {
+            continue;
+        }
+
+        let is_stale = search.version != snapshot.version();
+        match result {
+            Some((found_path, _)) if !is_stale || is_walkable(&graph, &found_path) => {
+                if debug.0 {
+                    for (a, b) in found_path.windows(2).map(|w| (w[0], w[1])) {
+                        let a = a.to_world_vec2() + SIDE_CELL_SIZE as f32 / 2f32;
+                        let b = b.to_world_vec2() + SIDE_CELL_SIZE as f32 / 2f32;
+                        debug_lines.line_colored(
+                            a.extend(0f32),
+                            b.extend(0f32),
+                            1.0,
+                            Color::LIME_GREEN,
+                        );
+                    }
+                }
+                *path = Path::Progress(PathProgress::new(&graph, found_path));
+            }
+            _ if is_stale => {
+                info!(goal = ?search.goal, "Map changed during path search, searching again");
+                *path = Path::NeedsPath(search.goal);
+            }
+            _ => {
+                warn!("No path found!");
+                *path = Path::Failed(search.goal);
             }
-            *path = Path::Progress(PathProgress {
-                remaining_steps: found_path,
-            });
-        } else {
-            warn!("No path found!");
-            *path = Path::Failed(*goal);
         }
     }
 }
@@ -185,24 +576,47 @@ pub fn update_movement_speed(mut query: Query<(&mut Speed, &CalculatedSideEffect
     }
 }
 
+/// Walk along paths. Creatures wait before walking into a cell that's full. See
+/// [crate::game::occupancy].
 pub fn move_along_path(
     time: Res<GameTime>,
+    flow_fields: Res<FlowFields>,
+    occupancy_settings: Res<OccupancySettings>,
+    mut occupancy: ResMut<Occupancy>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    cells: Query<&CellContent>,
     mut query: Query<(Entity, &mut Path, &mut Transform, &Speed)>,
     mut visited_event_writer: EventWriter<VisitedNodeEvent>,
 ) {
+    let now = time.since_startup();
+    let cell_at = |pos: SideIPos| {
+        let entity = side_map_pos_to_entities.get(&pos)?;
+        cells.get(*entity).ok().copied()
+    };
+    let mut try_enter = |entity: Entity, pos: SideIPos| {
+        let capacity = occupancy_settings.capacity(pos, &cell_at);
+        occupancy.try_enter(entity, pos, capacity, now, &occupancy_settings)
+    };
+
     for (entity, mut path, mut transform, speed) in query.iter_mut() {
-        let Path::Progress(progress) = &mut *path else {
-            continue;
+        let next_step = match &*path {
+            Path::Progress(progress) if progress.is_blocked => continue,
+            Path::Progress(progress) => progress.remaining_steps.get(progress.aim).copied(),
+            // Waiting for the first step from needs_path.
+            Path::FollowField(progress) if progress.next_step.is_none() => continue,
+            Path::FollowField(progress) => progress.next_step,
+            _ => continue,
         };
 
-        let PathProgress { remaining_steps } = progress;
-
-        let next_step = remaining_steps.get(0);
         let Some(next_step) = next_step else {
             *path = Path::None;
             continue;
         };
 
+        if !try_enter(entity, next_step) {
+            continue;
+        }
+
         let z = transform.translation.z;
         let mut step_distance = **speed * time.delta_seconds();
         let mut next_step_position = next_step.to_world_vec2();
@@ -216,23 +630,34 @@ pub fn move_along_path(
             step_distance -= distance;
             transform.translation = current_position.extend(z);
 
-            // Emit an event for the visited node.
-            let event = VisitedNodeEvent {
-                creature_entity: entity,
-                position: *next_step,
-                is_final: remaining_steps.len() == 1,
+            // Move on to the step after, or finish the path.
+            let is_open = |pos: SideIPos| {
+                cell_at(pos).map(|cell| cell.is_empty()).unwrap_or(false)
+                    && can_stand_in(pos, &cell_at)
             };
-            visited_event_writer.send(event);
+            let (visited, following_step) = path.step_reached(next_step, &flow_fields, &is_open);
+
+            // Emit an event for each visited node.
+            for position in visited {
+                let event = VisitedNodeEvent {
+                    creature_entity: entity,
+                    position,
+                    is_final: path.did_complete() && position == next_step,
+                };
+                visited_event_writer.send(event);
+            }
 
-            // Remove the current step from the path.
-            let popped_step = remaining_steps.remove(0);
-            let Some(next_step) = remaining_steps.get(0) else {
-                *path = Path::Completed(popped_step);
+            let Some(following_step) = following_step else {
                 continue;
             };
 
+            // Stop here if the next cell is full.
+            if !try_enter(entity, following_step) {
+                continue;
+            }
+
             // Recalculate the next step position.
-            next_step_position = next_step.to_world_vec2();
+            next_step_position = following_step.to_world_vec2();
         }
 
         let direction = (next_step_position - current_position).normalize_or_zero();
@@ -241,3 +666,117 @@ pub fn move_along_path(
         transform.translation = current_position.extend(z);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::{
+        detect_cell_content_changes_and_update_graph, UpdateTileDirtAmountEvent,
+    };
+    use crate::game::movement::{movement_weight, update_edges_around};
+    use crate::game::water::WaterLevels;
+
+    fn line_graph(length: i32) -> SideMapGraph {
+        let mut graph = SideMapGraph::new();
+        for x in 1..length {
+            graph.add_edge(SideIPos::new(x - 1, 0), SideIPos::new(x, 0), 1);
+        }
+        graph
+    }
+
+    #[test]
+    fn snapshot_is_only_copied_after_changes() {
+        let graph = line_graph(5);
+        let hierarchy = PathHierarchy::default();
+        let mut snapshot = GraphSnapshot::default();
+
+        let (first, _) = snapshot.get(&graph, &hierarchy);
+        let (second, _) = snapshot.get(&graph, &hierarchy);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let version = snapshot.version();
+        snapshot.invalidate();
+        let (third, _) = snapshot.get(&graph, &hierarchy);
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!(snapshot.version(), version + 1);
+    }
+
+    #[test]
+    fn stale_paths_through_removed_edges_are_not_walkable() {
+        let mut graph = line_graph(5);
+        let path: Vec<SideIPos> = (0..5).map(|x| SideIPos::new(x, 0)).collect();
+        assert!(is_walkable(&graph, &path));
+
+        graph.remove_edge(SideIPos::new(2, 0), SideIPos::new(3, 0));
+        assert!(!is_walkable(&graph, &path));
+    }
+
+    #[test]
+    fn snapshot_is_kept_when_no_edges_change() {
+        // A tunnel two cells deep in a block of dirt.
+        let mut world = World::new();
+        let mut pos_to_entities = HashMap::new();
+        let mut cells = HashMap::new();
+        for y in -3..=0 {
+            for x in 0..3 {
+                let pos = SideIPos::new(x, y);
+                let cell = if x == 1 && (y == -1 || y == -2) {
+                    CellContent::empty_underground()
+                } else {
+                    CellContent::dirt(10)
+                };
+                cells.insert(pos, cell);
+                pos_to_entities.insert(pos, world.spawn((cell, pos)).id());
+            }
+        }
+
+        let mut graph = SideMapGraph::new();
+        let cell_at = |pos| cells.get(&pos).copied();
+        for pos in cells.keys() {
+            graph.add_node(*pos);
+        }
+        for pos in cells.keys() {
+            update_edges_around(&mut graph, *pos, |from, to| {
+                movement_weight(from, to, &cell_at)
+            });
+        }
+
+        world.insert_resource(graph);
+        world.insert_resource(SideMapPosToEntities::from(pos_to_entities.clone()));
+        world.init_resource::<DebugLines>();
+        world.insert_resource(WaterLevels::default());
+        world.insert_resource(PathHierarchy::default());
+        world.insert_resource(GraphSnapshot::default());
+        world.insert_resource(ChangedCells::default());
+        world.init_resource::<Events<UpdateTileDirtAmountEvent>>();
+
+        let mut schedule = Schedule::new();
+        schedule.add_systems(
+            (
+                detect_cell_content_changes_and_update_graph,
+                update_graph_snapshot,
+            )
+                .chain(),
+        );
+        schedule.run(&mut world);
+        let version = world.resource::<GraphSnapshot>().version();
+
+        // Redrawing a cell that didn't change leaves the snapshot alone.
+        let wall = pos_to_entities[&SideIPos::new(2, -1)];
+        world.send_event(UpdateTileDirtAmountEvent(wall));
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<GraphSnapshot>().version(), version);
+        assert!(world.resource::<ChangedCells>().is_empty());
+
+        // Digging it out does change the edges.
+        world
+            .entity_mut(wall)
+            .insert(CellContent::empty_underground());
+        world.send_event(UpdateTileDirtAmountEvent(wall));
+        schedule.run(&mut world);
+        assert!(world.resource::<GraphSnapshot>().version() > version);
+        assert!(world
+            .resource::<ChangedCells>()
+            .contains(&SideIPos::new(2, -1)));
+    }
+}
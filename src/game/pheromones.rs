@@ -0,0 +1,372 @@
+//! Scent trails that ants lay as they walk, which make paths along them cheaper for other ants.
+//!
+//! Each cell holds a strength for each [PheromoneChannel]. An ant lays its [Scent] on every cell it
+//! visits, and every channel fades over game time. Searching for a path, an ant with a scent finds
+//! cells on a trail of the same channel cheaper to walk through. Danger is laid in flooded cells and
+//! makes cells dearer for every ant. The costs for each scent are shared by every search until the
+//! trails next fade.
+//!
+//! F5 cycles through an overlay of each channel.
+
+use crate::game::map::SIDE_CELL_SIZE;
+use crate::game::pathfinding::{CellCosts, VisitedNodeEvent};
+use crate::game::plugin::PHEROMONE_Z;
+use crate::game::positions::SideIPos;
+use crate::game::setup::sprite;
+use crate::game::time::GameTime;
+use crate::game::water::{WaterLevels, FLOODED_WATER};
+use crate::input::{InputAction, InputStates};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PheromoneChannel {
+    Food,
+    Home,
+    Danger,
+}
+
+impl PheromoneChannel {
+    fn index(&self) -> usize {
+        match self {
+            PheromoneChannel::Food => 0,
+            PheromoneChannel::Home => 1,
+            PheromoneChannel::Danger => 2,
+        }
+    }
+}
+
+/// The channel an ant lays as it walks, set by its current task. None lays nothing.
+#[derive(Component, Debug, Default, Clone, Copy, Deref, DerefMut)]
+pub struct Scent(pub Option<PheromoneChannel>);
+
+#[derive(Resource, Debug)]
+pub struct PheromoneSettings {
+    /// How much is laid on a cell each time an ant visits it.
+    pub deposit: f32,
+
+    /// Strength doesn't build up past this.
+    pub max_strength: f32,
+
+    /// Game time for a trail to fade to half its strength.
+    pub half_life: Duration,
+
+    /// How much game time between each fade.
+    pub step_interval: Duration,
+
+    /// Trails weaker than this are gone.
+    pub min_strength: f32,
+
+    /// How much of an edge's weight a trail at full strength takes off. 0 - 1.
+    pub trail_discount: f32,
+
+    /// How much danger at full strength adds to an edge's weight, as a fraction of it.
+    pub danger_penalty: f32,
+
+    /// Danger laid in each flooded cell every second.
+    pub flood_danger_per_second: f32,
+}
+
+impl Default for PheromoneSettings {
+    fn default() -> Self {
+        Self {
+            deposit: 1.0,
+            max_strength: 10.0,
+            half_life: Duration::from_secs(60),
+            step_interval: Duration::from_millis(500),
+            min_strength: 0.05,
+            trail_discount: 0.5,
+            danger_penalty: 2.0,
+            flood_danger_per_second: 2.0,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Pheromones(HashMap<SideIPos, [f32; 3]>);
+
+impl Pheromones {
+    pub fn strength(&self, pos: SideIPos, channel: PheromoneChannel) -> f32 {
+        self.0
+            .get(&pos)
+            .map(|strengths| strengths[channel.index()])
+            .unwrap_or_default()
+    }
+
+    pub fn deposit(
+        &mut self,
+        pos: SideIPos,
+        channel: PheromoneChannel,
+        amount: f32,
+        settings: &PheromoneSettings,
+    ) {
+        let strength = &mut self.0.entry(pos).or_default()[channel.index()];
+        *strength = (*strength + amount).min(settings.max_strength);
+    }
+
+    /// Fade every trail by the given game time, forgetting cells with nothing left.
+    pub fn evaporate(&mut self, elapsed: Duration, settings: &PheromoneSettings) {
+        let factor = 0.5f32.powf(elapsed.as_secs_f32() / settings.half_life.as_secs_f32());
+        self.0.retain(|_, strengths| {
+            for strength in strengths.iter_mut() {
+                *strength *= factor;
+                if *strength < settings.min_strength {
+                    *strength = 0.0;
+                }
+            }
+            strengths.iter().any(|strength| *strength > 0.0)
+        });
+    }
+
+    /// Change the cost of walking through each cell on a trail, for an ant with this scent.
+    pub fn add_trail_costs(
+        &self,
+        costs: &mut CellCosts,
+        scent: Option<PheromoneChannel>,
+        settings: &PheromoneSettings,
+    ) {
+        for pos in self.0.keys() {
+            let danger = self.strength(*pos, PheromoneChannel::Danger) / settings.max_strength;
+            let trail = match scent {
+                Some(channel) if channel != PheromoneChannel::Danger => {
+                    self.strength(*pos, channel) / settings.max_strength
+                }
+                _ => 0.0,
+            };
+            if danger == 0.0 && trail == 0.0 {
+                continue;
+            }
+
+            let multiplier =
+                (1.0 - settings.trail_discount * trail) * (1.0 + settings.danger_penalty * danger);
+            costs.multiply(*pos, multiplier);
+        }
+    }
+}
+
+/// The trail costs for each scent, worked out the first time a search needs them after the trails
+/// fade.
+#[derive(Resource, Debug, Default)]
+pub struct TrailCosts(HashMap<Option<PheromoneChannel>, Arc<CellCosts>>);
+
+impl TrailCosts {
+    pub fn get(
+        &mut self,
+        scent: Option<PheromoneChannel>,
+        pheromones: &Pheromones,
+        settings: &PheromoneSettings,
+    ) -> Arc<CellCosts> {
+        self.0
+            .entry(scent)
+            .or_insert_with(|| {
+                let mut costs = CellCosts::default();
+                pheromones.add_trail_costs(&mut costs, scent, settings);
+                Arc::new(costs)
+            })
+            .clone()
+    }
+}
+
+/// Lay each ant's scent on the cells it visits.
+pub fn lay_pheromones(
+    settings: Res<PheromoneSettings>,
+    mut pheromones: ResMut<Pheromones>,
+    scents: Query<&Scent>,
+    mut visited_reader: EventReader<VisitedNodeEvent>,
+) {
+    for event in visited_reader.iter() {
+        let Ok(Scent(Some(channel))) = scents.get(event.creature_entity) else {
+            continue;
+        };
+
+        pheromones.deposit(event.position, *channel, settings.deposit, &settings);
+    }
+}
+
+/// Fade the trails, and lay danger in flooded cells.
+pub fn evaporate_pheromones(
+    time: Res<GameTime>,
+    settings: Res<PheromoneSettings>,
+    water: Res<WaterLevels>,
+    mut pheromones: ResMut<Pheromones>,
+    mut trail_costs: ResMut<TrailCosts>,
+    mut since_last_step: Local<Duration>,
+) {
+    *since_last_step += time.delta();
+    if *since_last_step < settings.step_interval {
+        return;
+    }
+    let elapsed = *since_last_step;
+    *since_last_step = Duration::ZERO;
+
+    pheromones.evaporate(elapsed, &settings);
+    trail_costs.0.clear();
+
+    let danger = settings.flood_danger_per_second * elapsed.as_secs_f32();
+    for (pos, _) in water.iter().filter(|(_, level)| **level >= FLOODED_WATER) {
+        pheromones.deposit(*pos, PheromoneChannel::Danger, danger, &settings);
+    }
+}
+
+/// Which channel the debug overlay is showing. None is off.
+#[derive(Resource, Debug, Default, Deref)]
+pub struct PheromoneOverlay(Option<PheromoneChannel>);
+
+pub fn toggle_pheromone_overlay(
+    input_states: Res<InputStates>,
+    mut overlay: ResMut<PheromoneOverlay>,
+) {
+    if !input_states.just_pressed(InputAction::Debug4) {
+        return;
+    }
+
+    overlay.0 = match overlay.0 {
+        None => Some(PheromoneChannel::Food),
+        Some(PheromoneChannel::Food) => Some(PheromoneChannel::Home),
+        Some(PheromoneChannel::Home) => Some(PheromoneChannel::Danger),
+        Some(PheromoneChannel::Danger) => None,
+    };
+    info!(?overlay, "Pheromone overlay");
+}
+
+/// A box over each cell on a trail, more opaque the stronger it is. Food is green, home is blue and
+/// danger is red.
+pub fn update_pheromone_overlay(
+    mut commands: Commands,
+    settings: Res<PheromoneSettings>,
+    overlay: Res<PheromoneOverlay>,
+    pheromones: Res<Pheromones>,
+    mut overlay_sprites: Local<HashMap<SideIPos, Entity>>,
+) {
+    if !overlay.is_changed() && !pheromones.is_changed() {
+        return;
+    }
+
+    let Some(channel) = **overlay else {
+        for (_, entity) in overlay_sprites.drain() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+
+    overlay_sprites.retain(|pos, entity| {
+        if pheromones.strength(*pos, channel) > 0.0 {
+            return true;
+        }
+
+        commands.entity(*entity).despawn();
+        false
+    });
+
+    for pos in pheromones.0.keys() {
+        let strength = pheromones.strength(*pos, channel) / settings.max_strength;
+        if strength == 0.0 {
+            continue;
+        }
+
+        let alpha = 0.2 + strength * 0.6;
+        let color = match channel {
+            PheromoneChannel::Food => Color::rgba(0.2, 1.0, 0.2, alpha),
+            PheromoneChannel::Home => Color::rgba(0.2, 0.4, 1.0, alpha),
+            PheromoneChannel::Danger => Color::rgba(1.0, 0.1, 0.1, alpha),
+        };
+        let sprite = Sprite {
+            color,
+            custom_size: Some(Vec2::splat(SIDE_CELL_SIZE as f32)),
+            ..sprite()
+        };
+
+        match overlay_sprites.get(pos) {
+            Some(entity) => {
+                commands.entity(*entity).insert(sprite);
+            }
+            None => {
+                let entity = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite,
+                            transform: pos.to_transform(PHEROMONE_Z),
+                            ..Default::default()
+                        },
+                        Name::new("Pheromone"),
+                    ))
+                    .id();
+                overlay_sprites.insert(*pos, entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::path_hierarchy::PathHierarchy;
+    use crate::game::pathfinding::SideMapGraph;
+
+    #[test]
+    fn trails_fade_by_half_each_half_life() {
+        let settings = PheromoneSettings::default();
+        let mut pheromones = Pheromones::default();
+        let pos = SideIPos::new(0, -1);
+        pheromones.deposit(pos, PheromoneChannel::Food, 100.0, &settings);
+        assert_eq!(
+            pheromones.strength(pos, PheromoneChannel::Food),
+            settings.max_strength
+        );
+
+        pheromones.evaporate(settings.half_life, &settings);
+        let strength = pheromones.strength(pos, PheromoneChannel::Food);
+        assert!((strength - settings.max_strength / 2.0).abs() < 0.01);
+
+        pheromones.evaporate(settings.half_life * 20, &settings);
+        assert!(pheromones.0.is_empty());
+    }
+
+    /// Two ways around a block, the top one a step longer but with a food trail along it.
+    #[test]
+    fn ants_follow_their_own_trails_and_avoid_danger() {
+        let settings = PheromoneSettings::default();
+        let mut graph = SideMapGraph::new();
+        let top: Vec<SideIPos> = [(0, 0), (0, 1), (1, 1), (2, 1), (3, 1), (3, 0)]
+            .into_iter()
+            .map(|(x, y)| SideIPos::new(x, y))
+            .collect();
+        let bottom: Vec<SideIPos> = [(0, 0), (0, -1), (1, -1), (2, -1), (3, -1), (3, 0)]
+            .into_iter()
+            .map(|(x, y)| SideIPos::new(x, y))
+            .collect();
+        for step in top.windows(2) {
+            graph.add_edge(step[0], step[1], 4);
+        }
+        for step in bottom.windows(2) {
+            graph.add_edge(step[0], step[1], 3);
+        }
+
+        let mut pheromones = Pheromones::default();
+        for pos in &top {
+            pheromones.deposit(*pos, PheromoneChannel::Food, 100.0, &settings);
+        }
+        let (start, goal) = (SideIPos::new(0, 0), SideIPos::new(3, 0));
+        let hierarchy = PathHierarchy::default();
+        let find_path = |costs| hierarchy.find_path(&graph, start, goal, costs).unwrap().0;
+
+        let costs_for = |pheromones: &Pheromones, scent| {
+            let mut costs = CellCosts::default();
+            pheromones.add_trail_costs(&mut costs, Some(scent), &settings);
+            costs
+        };
+        let costs = costs_for(&pheromones, PheromoneChannel::Food);
+        assert_eq!(find_path(&costs), top);
+
+        // Home ants don't care about food trails.
+        assert!(costs_for(&pheromones, PheromoneChannel::Home).is_empty());
+
+        for pos in &top[1..5] {
+            pheromones.deposit(*pos, PheromoneChannel::Danger, 100.0, &settings);
+        }
+        let costs = costs_for(&pheromones, PheromoneChannel::Food);
+        assert_eq!(find_path(&costs), bottom);
+    }
+}
@@ -1,28 +1,48 @@
 use crate::game;
 use crate::game::ants::AntType;
-use crate::game::eggs::SpawnAntEvent;
+use crate::game::behaviours::Behaviours;
+use crate::game::calendar::Calendar;
+use crate::game::chunks::LoadChunkEvent;
+use crate::game::climate::{Climate, ClimateOverlay, ClimateSettings, SurfaceClimate};
+use crate::game::eggs::{BroodSettings, SpawnAntEvent};
+use crate::game::flow_field::FlowFields;
 use crate::game::food::FoodInfo;
 use crate::game::food_types::FoodId;
+use crate::game::jobs::{JobBoard, JobBoardPanel, JobSettings};
+use crate::game::level::MapSource;
 use crate::game::map::{AddFoodZoneEvent, UpdateTileDirtAmountEvent};
-use crate::game::pathfinding::{PathfindingLinesDebug, VisitedNodeEvent};
+use crate::game::map_generator::{MapGeneratorParams, MapSeed};
+use crate::game::mind::MindSettings;
+use crate::game::occupancy::{Occupancy, OccupancySettings};
+use crate::game::path_hierarchy::PathHierarchy;
+use crate::game::pathfinding::{
+    ChangedCells, GraphSnapshot, PathfindingLinesDebug, PathfindingSettings, VisitedNodeEvent,
+};
+use crate::game::pheromones::{PheromoneOverlay, PheromoneSettings, Pheromones, TrailCosts};
 use crate::game::positions::SideIPos;
 use crate::game::queen::{EggLaidEvent, Queen};
-use crate::game::setup::queen_start;
+use crate::game::setup::QueenStart;
 use crate::game::simple_brain::SimpleBrainSet;
 use crate::game::skill::SkillMode;
+use crate::game::stability::{StabilitySettings, StabilityWatch};
+use crate::game::tilemap::Tilemap;
 use crate::game::time::GameTime;
-use crate::game::zones::FoodStorageZones;
-use crate::game::{actions, brains, camera, food, mouse, new_brain, setup, simple_brain, time, ui};
+use crate::game::water::{Rain, WaterLevels, WaterSettings};
+use crate::game::zones::{NurseryZones, SpoilZones};
+use crate::game::{actions, camera, food, mouse, new_brain, setup, simple_brain, time, ui};
 use bevy::app::{App, Plugin};
 use bevy::prelude::*;
 use bevy::utils::{HashMap, HashSet};
-use big_brain::prelude::*;
 use rand::prelude::{IteratorRandom, ThreadRng};
 use rand::Rng;
 
 pub const DIRT_Z: f32 = 0f32;
 pub const QUEEN_Z: f32 = 1f32;
 pub const FOOD_Z: f32 = 1.5f32;
+pub const WATER_Z: f32 = 1.75f32;
+pub const CLIMATE_Z: f32 = 1.8f32;
+pub const PHEROMONE_Z: f32 = 1.85f32;
+pub const OVERLAY_Z: f32 = 1.9f32;
 pub const ANT_Z: f32 = 2f32;
 pub const EGG_Z: f32 = 3f32;
 
@@ -55,8 +75,6 @@ pub enum InputSet {
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(BigBrainPlugin);
-
         app.add_event::<VisitedNodeEvent>();
         app.add_event::<EggLaidEvent>();
         app.add_event::<SpawnAntEvent>();
@@ -64,13 +82,57 @@ impl Plugin for GamePlugin {
         app.add_event::<food::FeedEvent>();
         app.add_event::<UpdateTileDirtAmountEvent>();
         app.add_event::<AddFoodZoneEvent>();
+        app.add_event::<LoadChunkEvent>();
 
         app.insert_resource(GameTime::default());
+        app.insert_resource(Calendar::from_env());
         app.insert_resource(ui::IsHoveringOverUi::default());
         app.insert_resource(PlayerState::default());
         app.insert_resource(food::FoodState::default());
+        app.insert_resource(SpoilZones::default());
+        app.insert_resource(NurseryZones::default());
+        app.insert_resource(BroodSettings::default());
+        app.insert_resource(StabilitySettings::default());
+        app.insert_resource(StabilityWatch::default());
+        app.insert_resource(WaterSettings::default());
+        app.insert_resource(WaterLevels::default());
+        app.insert_resource(Rain::default());
+        app.insert_resource(ClimateSettings::default());
+        app.insert_resource(SurfaceClimate::default());
+        app.insert_resource(Climate::default());
+        app.insert_resource(ClimateOverlay::default());
+        app.insert_resource(Tilemap::default());
+        app.insert_resource(JobBoard::default());
+        app.insert_resource(JobSettings::default());
+        app.insert_resource(JobBoardPanel::default());
         app.insert_resource(PathfindingLinesDebug::default());
+        app.insert_resource(PathfindingSettings::default());
+        app.insert_resource(ChangedCells::default());
+        app.insert_resource(GraphSnapshot::default());
+        app.insert_resource(PathHierarchy::default());
+        app.insert_resource(OccupancySettings::default());
+        app.insert_resource(MindSettings::default());
+        app.insert_resource(Occupancy::default());
+        app.insert_resource(PheromoneSettings::default());
+        app.insert_resource(Pheromones::default());
+        app.insert_resource(PheromoneOverlay::default());
+        app.insert_resource(TrailCosts::default());
+        app.insert_resource(FlowFields::default());
         app.insert_resource(SkillMode::Career);
+        app.insert_resource(MapSeed::from_env_or_random());
+
+        // Load the level and behaviours here so a broken file stops the game before anything starts.
+        let map_source = MapSource::from_env().unwrap();
+        let behaviours = Behaviours::load_str(
+            "assets/behaviours.yaml",
+            include_str!("../../assets/behaviours.yaml"),
+        )
+        .unwrap();
+        app.insert_resource(behaviours);
+        let map_params = MapGeneratorParams::from_env();
+        app.insert_resource(QueenStart(map_source.queen_start(&map_params)));
+        app.insert_resource(map_source);
+        app.insert_resource(map_params);
 
         app.add_startup_systems((
             camera::setup,
@@ -83,17 +145,32 @@ impl Plugin for GamePlugin {
 
         // Reset
         app.add_systems((time::new_frame, ui::reset_hovering_over_ui_flag).in_set(InputSet::Reset));
+        app.add_system(
+            game::calendar::advance_calendar
+                .after(time::new_frame)
+                .in_set(InputSet::Reset),
+        );
 
         // Ui
-        app.add_systems((ui::control, ui::show_queens_choice).in_set(InputSet::Ui));
+        app.add_systems(
+            (
+                ui::control,
+                ui::show_queens_choice,
+                game::jobs::show_job_board_panel,
+            )
+                .in_set(InputSet::Ui),
+        );
 
         // ProcessInput
         app.add_systems(
             (
                 camera::control,
-                actions::primary_mouse_click,
+                actions::primary_mouse_click.run_if(game::not_using_ui),
                 time::input,
                 game::pathfinding::toggle_pathfinding_debug_lines,
+                game::climate::toggle_climate_overlay,
+                game::pheromones::toggle_pheromone_overlay,
+                game::jobs::toggle_job_board_panel,
             )
                 .in_set(InputSet::ProcessInput),
         );
@@ -108,13 +185,12 @@ impl Plugin for GamePlugin {
                 game::pathfinding::needs_path,
                 game::pathfinding::move_along_path,
                 game::map::passive_dig_when_visiting_a_cell,
-                game::map::update_tile_rendering,
-                game::map::update_food_tile_rendering,
                 game::map::detect_cell_content_changes_and_update_graph,
                 game::queen::grow_and_lay_eggs,
                 game::queen::update_queen_egg_progress_speed,
                 game::eggs::spawn_eggs,
                 game::eggs::grow_eggs,
+                game::eggs::carry_eggs,
                 game::animation::animate_sprites,
                 game::ants::spawn_ants,
                 game::hunger::hunger_system,
@@ -132,9 +208,109 @@ impl Plugin for GamePlugin {
                 game::debug::check_for_f3_to_offer_queen_new_food,
                 game::food::feed_and_apply,
                 game::pathfinding::update_movement_speed,
+                game::stability::watch_changed_cells,
+                game::stability::collapse_unstable_tunnels,
+            )
+                .in_set(InputSet::Game),
+        );
+
+        app.add_systems(
+            (
+                game::water::simulate_water,
+                game::water::damage_flooded_cells,
+                game::water::update_water_rendering,
+                game::chunks::request_chunks_near_visitors,
+                game::chunks::load_chunks,
+                game::jobs::post_haul_food_jobs,
+                game::jobs::post_feed_queen_jobs,
+                game::eggs::post_brood_jobs,
+                game::dig::update_dig_overlay,
+            )
+                .in_set(InputSet::Game),
+        );
+        app.add_systems(
+            (
+                game::jobs::settle_jobs,
+                apply_system_buffers,
+                game::jobs::claim_jobs,
+            )
+                .chain()
+                .in_set(InputSet::Game),
+        );
+        // After every system that digs, floods or collapses cells, so changes show the same frame.
+        app.add_systems(
+            (
+                game::tilemap::mark_dirty_chunks,
+                game::tilemap::rebuild_dirty_chunks,
+            )
+                .chain()
+                .in_base_set(CoreSet::PostUpdate),
+        );
+        app.add_systems(
+            (
+                game::calendar::update_sky_colour,
+                game::climate::update_surface_climate,
+                game::climate::simulate_climate,
+                game::climate::update_climate_overlay,
+            )
+                .in_set(InputSet::Game),
+        );
+        app.add_systems(
+            (
+                game::pheromones::lay_pheromones.after(game::pathfinding::move_along_path),
+                game::pheromones::evaporate_pheromones,
+                game::pheromones::update_pheromone_overlay,
             )
                 .in_set(InputSet::Game),
         );
+        app.add_systems(
+            (
+                game::steering::update_heading,
+                game::steering::orient_sprites,
+            )
+                .chain()
+                .after(game::pathfinding::move_along_path)
+                .in_set(InputSet::Game),
+        );
+        app.add_systems(
+            (
+                game::path_hierarchy::mark_dirty_path_clusters,
+                game::path_hierarchy::update_path_hierarchy,
+            )
+                .chain()
+                .after(game::map::detect_cell_content_changes_and_update_graph)
+                .after(game::chunks::load_chunks)
+                .before(game::pathfinding::needs_path)
+                .in_set(InputSet::Game),
+        );
+        app.add_system(
+            game::flow_field::update_flow_fields
+                .after(game::map::detect_cell_content_changes_and_update_graph)
+                .after(game::chunks::load_chunks)
+                .before(game::pathfinding::needs_path)
+                .in_set(InputSet::Game),
+        );
+        app.add_systems(
+            (
+                game::occupancy::update_occupancy,
+                game::pathfinding::update_graph_snapshot,
+                game::pathfinding::poll_path_searches,
+                game::path_validation::flag_paths_near_changes,
+                game::path_validation::validate_paths,
+            )
+                .chain()
+                .after(game::path_hierarchy::update_path_hierarchy)
+                .before(game::pathfinding::needs_path)
+                .in_set(InputSet::Game),
+        );
+        app.add_system(
+            game::pathfinding::clear_changed_cells
+                .after(game::path_hierarchy::mark_dirty_path_clusters)
+                .after(game::flow_field::update_flow_fields)
+                .after(game::path_validation::flag_paths_near_changes)
+                .in_set(InputSet::Game),
+        );
+
         app.configure_set(InputSet::Reset.before(InputSet::Ui));
         app.configure_set(InputSet::Ui.before(InputSet::GetInput));
         app.configure_set(InputSet::GetInput.before(InputSet::ProcessInput));
@@ -148,6 +324,31 @@ impl Plugin for GamePlugin {
                 new_brain::eat_action_2,
                 new_brain::pathfinding_action_2,
                 new_brain::set_path_to_stored_food_action_2,
+                new_brain::set_path_to_job_action_2,
+                new_brain::dig_action_2,
+                new_brain::set_path_away_from_danger_action_2,
+                new_brain::check_action_2,
+                new_brain::wait_action_2,
+            )
+                .in_set(SimpleBrainSet::Actions),
+        );
+        app.add_systems(
+            (
+                new_brain::set_path_to_outside_action_2,
+                new_brain::set_path_to_queen_action_2,
+                new_brain::set_path_to_discovered_food_action_2,
+                new_brain::set_path_to_food_storage_action_2,
+                new_brain::map_transition_action_2,
+                new_brain::outside_map_discovering_food_action_2,
+                new_brain::outside_map_gathering_existing_food_action_2,
+                new_brain::offer_food_discovery_to_queen_action_2,
+                new_brain::place_food_if_possible_action_2,
+                new_brain::pick_up_food_action_2,
+                new_brain::feed_queen_action_2,
+                new_brain::set_path_to_nursery_action_2,
+                new_brain::pick_up_egg_action_2,
+                new_brain::put_down_egg_action_2,
+                new_brain::feed_brood_action_2,
             )
                 .in_set(SimpleBrainSet::Actions),
         );
@@ -158,29 +359,12 @@ impl Plugin for GamePlugin {
         );
 
         app.configure_set(SimpleBrainSet::Actions.before(SimpleBrainSet::AssignComponents));
-
-        // Brain things
-        // app.add_systems(
-        //     (
-        //         brains::pathfinding::pathfinding_action,
-        //         brains::pathfinding::set_path_to_outside_action,
-        //         brains::pathfinding::set_path_and_assign_food_to_discovered_food_action,
-        //         brains::pathfinding::set_path_to_food_storage_action,
-        //         brains::pathfinding::set_path_to_stored_food_action,
-        //         brains::pathfinding::set_path_to_queen_action,
-        //         brains::map_transition_action,
-        //         brains::outside_map_discovering_food_action,
-        //         brains::outside_map_gathering_existing_food_action,
-        //         brains::offer_food_discovery_to_queen_action,
-        //         brains::place_food_if_possible_action,
-        //         brains::pick_up_food_action,
-        //         brains::feed_queen_action,
-        //         brains::eat_action,
-        //         brains::hungry_scorer,
-        //     )
-        //         .in_set(BigBrainSet::Actions),
-        // );
-        // app.add_system_to_stage(BigBrainStage::Scorers, ());
+        app.add_systems(
+            (game::mind::score_choices, game::mind::think)
+                .chain()
+                .after(game::jobs::claim_jobs)
+                .in_set(InputSet::Game),
+        );
     }
 
     fn name(&self) -> &str {
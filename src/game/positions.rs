@@ -2,9 +2,12 @@ use crate::game;
 use crate::game::map::SIDE_CELL_SIZE;
 use bevy::math::{IVec2, Vec2, Vec3};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 /// The side position of a fixed position, e.g. a dirt cell.
-#[derive(Component, Deref, DerefMut, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[derive(
+    Component, Deref, DerefMut, Eq, PartialEq, Hash, Clone, Copy, Debug, Serialize, Deserialize,
+)]
 pub struct SideIPos(IVec2);
 
 impl SideIPos {
@@ -1,14 +1,18 @@
 use crate::game::animation::{AnimationIndices, AnimationTimer};
 use crate::game::camera::CameraFocus;
+use crate::game::chunks::{chunk_of, SideMapChunks};
 use crate::game::eggs::Egg;
 use crate::game::food::{CarryingFood, DiscoveredFood, FoodState};
 use crate::game::food_types::{FoodId, FoodType};
 use crate::game::hunger::Hunger;
+use crate::game::level::MapSource;
 use crate::game::map::{
-    AddFoodZoneEvent, CellContent, ExitPositions, SideMapPosToEntities,
-    TileNeedsFoodRenderingUpdate, SIDE_CELL_SIZE,
+    AddFoodZoneEvent, CellGrid, ExitPositions, SideMapPosToEntities, TileNeedsFoodRenderingUpdate,
+    SIDE_CELL_SIZE,
 };
-use crate::game::pathfinding::{Path, SideMapGraph};
+use crate::game::map_generator::{MapGeneratorParams, MapSeed};
+use crate::game::movement::add_grid_to_graph;
+use crate::game::pathfinding::{ChangedCells, Path, SideMapGraph};
 use crate::game::plugin::{Crawler, PlayerState, Speed, ANT_Z, DIRT_Z, QUEEN_Z};
 use crate::game::positions::SideIPos;
 use crate::game::queen::{EggLaidEvent, Queen};
@@ -16,6 +20,7 @@ use crate::game::side_effects::{
     AppliedFoodSideEffect, AppliedFoodSideEffects, CalculatedSideEffects,
 };
 use crate::game::skill::SkillMode;
+use crate::game::zones::{NurseryZones, SpoilZones};
 use bevy::asset::AssetServer;
 use bevy::core_pipeline::clear_color::ClearColorConfig;
 use bevy::prelude::*;
@@ -26,15 +31,11 @@ use bevy::utils::petgraph::visit::IntoEdgeReferences;
 use bevy::utils::HashMap;
 use bevy_prototype_debug_lines::DebugLines;
 use pathfinding::num_traits::Signed;
-use rand::random;
 use std::time::Duration;
 
-// pub fn queen_start() -> SideIPos {
-//     SideIPos::new(0, -20)
-// }
-pub fn queen_start() -> SideIPos {
-    SideIPos::new(0, -20)
-}
+/// Where the queen is placed when the map is set up. Comes from the level or the map generator.
+#[derive(Resource, Deref, Copy, Clone, Debug)]
+pub struct QueenStart(pub SideIPos);
 
 /// We want the transform position specified to be on the top left of the rendered sprite.
 pub fn sprite() -> Sprite {
@@ -53,150 +54,74 @@ pub fn texture_atlas_sprite() -> TextureAtlasSprite {
 
 pub fn setup_map(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
     mut debug_lines: ResMut<DebugLines>,
-
-    // TODO: Temporary...
     mut food_state: ResMut<FoodState>,
     skill_mode: Res<SkillMode>,
-    // TODO: Temporary...
     mut add_zone_writer: EventWriter<AddFoodZoneEvent>,
+    mut spoil_zones: ResMut<SpoilZones>,
+    mut nursery_zones: ResMut<NurseryZones>,
+    map_source: Res<MapSource>,
+    map_seed: Res<MapSeed>,
+    map_params: Res<MapGeneratorParams>,
+    mut changed_cells: ResMut<ChangedCells>,
 ) {
-    // TODO: Temporary...
-    add_zone_writer.send(AddFoodZoneEvent(SideIPos::new(10, -20)));
-    add_zone_writer.send(AddFoodZoneEvent(SideIPos::new(9, -20)));
-    add_zone_writer.send(AddFoodZoneEvent(SideIPos::new(8, -20)));
-
-    // XXX: Temporary...
-    {
-        let food_info = skill_mode.next_food(Duration::ZERO);
-        food_state.approve_food(DiscoveredFood {
-            food_info: food_info.clone(),
-            position: SideIPos::new(0, 0),
-            time_to_discover: Default::default(),
-            stash_remaining: 0.0,
-        });
-
-        let side_pos = SideIPos::new(5, -20);
-        food_state.add_food_at_position(
-            side_pos,
-            &CarryingFood {
-                food_id: food_info.food_id,
-                amount: 5f32,
-            },
-        );
+    if let MapSource::Generated = *map_source {
+        info!(?map_seed, "Generating map");
     }
+    let level = map_source.level(*map_seed, &map_params);
+    let grid = &level.grid;
 
-    let mut side_map_pos_to_entities = HashMap::with_capacity(1_000);
-
-    // This is a temporary structure to help us build the graph for this system only.
-    // Other systems will use a Query to look up the Cell entity via SideMapPosToEntities.
-    let mut side_map_pos_to_cell = HashMap::with_capacity(1_000);
-
-    let mut graph = UnGraphMap::<SideIPos, u64>::with_capacity(1_000, 4_000);
-
-    let queen_start = queen_start();
-    let queen_room_width = 3;
-    let queen_room_height = 3;
-
-    let width = 40;
-
-    for y in -30..20 {
-        for x in -width / 2..(width / 2 + 1) {
-            let cell_content = if y >= 0 {
-                CellContent::empty_air()
-            } else if x >= queen_start.x - queen_room_width / 2
-                && x <= queen_start.x + queen_room_width / 2
-                && y >= queen_start.y
-                && y < queen_start.y + queen_room_height
-            {
-                CellContent::empty_underground()
-            } else {
-                // Create dirt from Y - 1 and downwards with a width of 20.
-                // Y 0 or higher is the surface, so make Dirt::empty()
-                // We want a V shape around the origin so that ants are initially biased towards
-                // the middle and not dig new holes.
-                //
-                // e.g. the top row (at x == 0, y = -1) should have 5 very light dirt (e.g. 10).
-                // the next row should have 3 light dirt (e.g. 20), and so on.
-                //
-                // Also, x away from the origin should be light in the middle and gradually harder
-                // further away.
-                //
-                let forced_dirt_amount = (y.abs() * 10 + x.abs() * 30) as f32;
-                // Add a random amount of dirt.
-                let forced_dirt_amount = forced_dirt_amount + random::<f32>() * 50.0 - 20.0;
-
-                let forced_dirt_amount = forced_dirt_amount.max(0.0) as u64;
-
-                if forced_dirt_amount > 0u64 && forced_dirt_amount < 255u64 {
-                    CellContent::dirt(forced_dirt_amount as u8)
-                } else if y >= -5 {
-                    CellContent::dirt(
-                        (255f32 - rand::random::<f32>() * (255.0 / 5f32) * y.abs() as f32) as u8,
-                    )
-                } else {
-                    if random::<u8>() < 5 {
-                        CellContent::rock(true)
-                    } else {
-                        CellContent::random_dirt()
-                    }
-                }
-            };
-
-            let side_pos = SideIPos::new(x, y);
-            let texture_path = cell_content.texture_path();
-            let transform = side_pos.to_transform(DIRT_Z);
-
-            let mut entity = commands.spawn_empty();
-
-            if let Some(texture_path) = texture_path {
-                let sprite_bundle = SpriteBundle {
-                    sprite: sprite(),
-                    transform,
-                    texture: asset_server.load(texture_path),
-                    ..Default::default()
-                };
-                entity.insert(sprite_bundle);
-            } else {
-                entity.insert(transform);
-            }
-
-            let entity_id = entity
-                .insert((
-                    Name::from(format!("{:?}", side_pos)),
-                    cell_content,
-                    side_pos,
-                    TileNeedsFoodRenderingUpdate,
-                ))
-                .id();
-
-            side_map_pos_to_entities.insert(side_pos, entity_id);
-            side_map_pos_to_cell.insert(side_pos, cell_content);
+    for zone in &level.food_zones {
+        add_zone_writer.send(AddFoodZoneEvent(*zone));
+    }
 
-            graph.add_node(side_pos);
-        }
+    for zone in &level.spoil_zones {
+        spoil_zones.add(*zone);
     }
 
-    for (pos, cell) in side_map_pos_to_cell.iter() {
-        let Some(a_weight) = cell.weight() else {
-            continue;
-        };
+    for zone in &level.nursery_zones {
+        nursery_zones.add(*zone);
+    }
 
-        for neighbour in pos.sides() {
-            if let Some(other_cell) = side_map_pos_to_cell.get(&neighbour) {
-                let Some(b_weight) = other_cell.weight() else {
-                    continue;
-                };
+    for food in &level.food {
+        let mut food_info = skill_mode.next_food(Duration::ZERO);
+        if let Some(food_id) = food.food_id {
+            food_info.food_id = food_id;
+        }
 
-                let weight = a_weight as u64 + b_weight as u64;
-                graph.add_edge(*pos, neighbour, weight as u64);
-            }
+        // The food needs to be approved before anything can eat it.
+        if food_state.get_discovered_food(food_info.food_id).is_none() {
+            food_state.approve_food(DiscoveredFood {
+                food_info: food_info.clone(),
+                position: SideIPos::new(0, 0),
+                time_to_discover: Default::default(),
+                stash_remaining: 0.0,
+            });
         }
+
+        food_state.add_food_at_position(
+            food.position,
+            &CarryingFood {
+                food_id: food_info.food_id,
+                amount: food.amount,
+            },
+        );
     }
 
-    // Explicitly drop the temporary side_map_pos_to_cell, just as a reminder that it's temporary.
-    drop(side_map_pos_to_cell);
+    let mut side_map_pos_to_entities =
+        SideMapPosToEntities::from(HashMap::with_capacity(grid.len()));
+    let mut graph = SideMapGraph::from(UnGraphMap::<SideIPos, u64>::with_capacity(
+        grid.len(),
+        grid.len() * 4,
+    ));
+
+    spawn_cells(
+        &mut commands,
+        grid,
+        &mut side_map_pos_to_entities,
+        &mut graph,
+        &mut changed_cells,
+    );
 
     println!(
         "Graph has {} nodes and {} edges",
@@ -212,15 +137,17 @@ pub fn setup_map(
     // }
 
     // Finally own the dirt map and set the resource.
-    commands.insert_resource(SideMapPosToEntities::from(side_map_pos_to_entities));
-    commands.insert_resource(SideMapGraph::from(graph));
+    commands.insert_resource(side_map_pos_to_entities);
+    commands.insert_resource(graph);
 
-    // Add exit points on the surface
-    let mut exit_positions = Vec::new();
-    for x in (-width / 2 - 1..width / 2 + 2).step_by(4) {
-        let exit_pos = SideIPos::new(x, 0);
-        exit_positions.push(exit_pos);
+    let chunks = match *map_source {
+        MapSource::Generated => SideMapChunks::growing(grid.iter().map(|(pos, _)| chunk_of(pos))),
+        MapSource::Level(_) => SideMapChunks::fixed(),
+    };
+    commands.insert_resource(chunks);
 
+    // Add exit points on the surface
+    for exit_pos in &level.exits {
         // Draw a line from the exit point to 0, 20
         debug_lines.line_colored(
             Vec3::new(
@@ -233,12 +160,42 @@ pub fn setup_map(
             Color::rgb(0.0, 1.0, 0.0),
         );
     }
-    commands.insert_resource(ExitPositions::from(exit_positions));
+    commands.insert_resource(ExitPositions::from(level.exits.clone()));
 }
 
-pub fn setup_queen(mut commands: Commands, asset_server: Res<AssetServer>) {
+/// Spawn an entity for each cell in the grid, and add them to the graph with edges between them.
+pub fn spawn_cells(
+    commands: &mut Commands,
+    grid: &CellGrid,
+    side_map_pos_to_entities: &mut SideMapPosToEntities,
+    graph: &mut SideMapGraph,
+    changed_cells: &mut ChangedCells,
+) {
+    for (side_pos, cell_content) in grid.iter() {
+        // No sprite here, the tilemap draws the cells a chunk at a time.
+        let entity_id = commands
+            .spawn((
+                side_pos.to_transform(DIRT_Z),
+                Name::from(format!("{:?}", side_pos)),
+                *cell_content,
+                side_pos,
+                TileNeedsFoodRenderingUpdate,
+            ))
+            .id();
+
+        side_map_pos_to_entities.insert(side_pos, entity_id);
+        changed_cells.insert(side_pos);
+    }
+
+    add_grid_to_graph(graph, grid);
+}
+
+pub fn setup_queen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    queen_start: Res<QueenStart>,
+) {
     let texture = asset_server.load("creatures/queen.png");
-    let queen_start = queen_start();
     let transform = queen_start.to_transform(QUEEN_Z);
 
     let sprite_bundle = SpriteBundle {
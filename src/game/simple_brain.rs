@@ -1,5 +1,6 @@
 use crate::game::new_brain::Action;
 use bevy::prelude::*;
+use std::ops::{Deref, DerefMut};
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum SimpleBrainSet {
@@ -7,6 +8,15 @@ pub enum SimpleBrainSet {
     AssignComponents,
 }
 
+/// Where an [Idea] is in its [Sequence].
+///
+/// * `Prepare(n)` -> `Executing(n)` once step n's component is inserted, or `Done` past the last
+///   step.
+/// * `Executing(n)` -> `Prepare(n + 1)` when the step succeeds, or `Aborting(n)` when it fails or
+///   the idea is cancelled.
+/// * `Aborting(n)` -> `Aborted` once step n is cleaned up and its component removed.
+///
+/// Any other change is logged and ignored.
 #[derive(Debug, Eq, PartialEq)]
 pub enum IdeaState {
     Prepare(usize),
@@ -18,6 +28,13 @@ pub enum IdeaState {
 
 pub fn assign_step_components(mut commands: Commands, mut executing: Query<(Entity, &mut Idea)>) {
     for (entity, mut executing) in &mut executing {
+        if executing.cancel_requested {
+            executing.cancel_requested = false;
+            executing.cancelled = true;
+            info!(?entity, ?executing.state, "Cancelling idea");
+            executing.abort();
+        }
+
         match &executing.state {
             IdeaState::Prepare(step) => {
                 if *step > 0 {
@@ -40,22 +57,51 @@ pub fn assign_step_components(mut commands: Commands, mut executing: Query<(Enti
                 info!(?executing.state, "State");
             }
             IdeaState::Aborting(step) => {
-                let action = &executing.steps[*step];
-                info!(?action, "Removing component because we're aborting.");
+                let action = executing.steps[*step];
+                info!(
+                    ?action,
+                    "Cleaning up and removing component because we're aborting."
+                );
+                // Before the removal, so the clean up can still see the action's component.
+                commands.add(move |world: &mut World| action.clean_up(entity, world));
                 action.remove(&mut commands.entity(entity));
                 executing.state = IdeaState::Aborted;
+                executing.fall_back();
             }
             _ => (),
         }
     }
 }
 
-#[derive(Deref, DerefMut)]
-pub struct Sequence(Vec<Action>);
+/// Steps run one after the other, and what to do instead if one of them fails.
+#[derive(Clone, Debug, Default)]
+pub struct Sequence {
+    steps: Vec<Action>,
+    fallback: Option<Box<Sequence>>,
+}
 
 impl Sequence {
     pub fn new() -> Self {
-        Self(Vec::new())
+        Self::default()
+    }
+
+    pub fn with_fallback(mut self, fallback: Sequence) -> Self {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+}
+
+impl Deref for Sequence {
+    type Target = Vec<Action>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.steps
+    }
+}
+
+impl DerefMut for Sequence {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.steps
     }
 }
 
@@ -63,18 +109,75 @@ impl Sequence {
 pub struct Idea {
     state: IdeaState,
     steps: Sequence,
+
+    /// Set from outside of the idea's actions, and honoured in [assign_step_components].
+    cancel_requested: bool,
+
+    /// Aborted because of a cancel request rather than a failed step, so the fallback is skipped.
+    cancelled: bool,
+
+    /// A step failed and the fallback ran instead.
+    fell_back: bool,
 }
 
 impl Idea {
+    /// Done or aborted, so the creature is free to do something else.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, IdeaState::Done | IdeaState::Aborted)
+    }
+
+    pub fn did_abort(&self) -> bool {
+        self.state == IdeaState::Aborted
+    }
+
+    /// Whether a step failed, even if the idea went on to finish its fallback.
+    pub fn did_fail(&self) -> bool {
+        self.did_abort() || self.fell_back
+    }
+
+    /// Give up on the idea, cleaning up the current step. Called by the current step's action when
+    /// it fails. Other systems should use [Idea::request_cancel].
     pub fn abort(&mut self) {
-        let current_step = match self.state {
-            IdeaState::Executing(step) => step,
+        match self.state {
+            IdeaState::Executing(step) => {
+                self.state = IdeaState::Aborting(step);
+            }
+            IdeaState::Prepare(0) => {
+                self.state = IdeaState::Aborted;
+                self.fall_back();
+            }
+            IdeaState::Prepare(step) => {
+                // The previous step's component hasn't been removed yet.
+                self.state = IdeaState::Aborting(step - 1);
+            }
             _ => {
-                warn!("Invalid state: {:?}", self.state);
-                return;
+                warn!(?self.state, "Idea is already finishing, not aborting");
             }
+        }
+    }
+
+    /// Ask for the idea to be aborted the next time steps are changed, e.g. because the creature is
+    /// starving or under attack. Does nothing once the idea is finished.
+    pub fn request_cancel(&mut self) {
+        if !self.is_finished() {
+            self.cancel_requested = true;
+        }
+    }
+
+    /// After a step fails, start over on the sequence's fallback if it has one.
+    fn fall_back(&mut self) {
+        if self.state != IdeaState::Aborted || self.cancelled {
+            return;
+        }
+
+        let Some(fallback) = self.steps.fallback.take() else {
+            return;
         };
-        self.state = IdeaState::Aborting(current_step);
+
+        info!(steps = ?fallback.steps, "Falling back");
+        self.steps = *fallback;
+        self.state = IdeaState::Prepare(0);
+        self.fell_back = true;
     }
 
     pub fn next_step(&mut self) {
@@ -85,7 +188,7 @@ impl Idea {
                 debug!(?self.state, "Next step");
             }
             _ => {
-                panic!("Invalid state: {:?}", self.state);
+                error!(?self.state, "Can't move on to the next step from here");
             }
         }
     }
@@ -96,6 +199,135 @@ impl From<Sequence> for Idea {
         Self {
             state: IdeaState::Prepare(0),
             steps,
+            cancel_requested: false,
+            cancelled: false,
+            fell_back: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::new_brain::{SetPathToQueenAction2, WaitAction2};
+    use crate::game::pathfinding::Path;
+    use crate::game::pheromones::{PheromoneChannel, Scent};
+    use crate::game::positions::SideIPos;
+    use std::time::Duration;
+
+    fn idea(state: IdeaState) -> Idea {
+        let mut steps = Sequence::new();
+        steps.push(Action::SetPathToQueenAction);
+        steps.push(Action::PathfindingAction);
+        steps.push(Action::WaitAction(Duration::from_secs(5)));
+        Idea {
+            state,
+            ..Idea::from(steps)
+        }
+    }
+
+    #[test]
+    fn steps_only_move_on_while_executing() {
+        let mut executing = idea(IdeaState::Executing(1));
+        executing.next_step();
+        assert_eq!(executing.state, IdeaState::Prepare(2));
+
+        for state in [
+            IdeaState::Prepare(1),
+            IdeaState::Aborting(1),
+            IdeaState::Aborted,
+            IdeaState::Done,
+        ] {
+            let mut other = idea(state);
+            let before = format!("{:?}", other.state);
+            other.next_step();
+            assert_eq!(format!("{:?}", other.state), before);
         }
     }
+
+    #[test]
+    fn aborting_cleans_up_the_step_that_still_has_a_component() {
+        let cases = [
+            (IdeaState::Executing(1), IdeaState::Aborting(1)),
+            (IdeaState::Prepare(0), IdeaState::Aborted),
+            (IdeaState::Prepare(2), IdeaState::Aborting(1)),
+            (IdeaState::Aborting(2), IdeaState::Aborting(2)),
+            (IdeaState::Aborted, IdeaState::Aborted),
+            (IdeaState::Done, IdeaState::Done),
+        ];
+        for (from, to) in cases {
+            let mut idea = idea(from);
+            idea.abort();
+            assert_eq!(idea.state, to);
+        }
+    }
+
+    #[test]
+    fn failed_steps_fall_back_but_cancelled_ones_dont() {
+        let mut fallback = Sequence::new();
+        fallback.push(Action::WaitAction(Duration::from_secs(1)));
+        let mut steps = Sequence::new();
+        steps.push(Action::SetPathToQueenAction);
+        let steps = steps.with_fallback(fallback);
+
+        let mut failed = Idea::from(steps.clone());
+        failed.abort();
+        assert_eq!(failed.state, IdeaState::Prepare(0));
+        assert_eq!(failed.steps.len(), 1);
+        assert!(matches!(failed.steps[0], Action::WaitAction(_)));
+        assert!(failed.did_fail());
+
+        // The fallback has no fallback of its own.
+        failed.abort();
+        assert_eq!(failed.state, IdeaState::Aborted);
+
+        let mut cancelled = Idea::from(steps);
+        cancelled.cancelled = true;
+        cancelled.abort();
+        assert_eq!(cancelled.state, IdeaState::Aborted);
+    }
+
+    #[test]
+    fn cancelling_finished_ideas_does_nothing() {
+        let mut done = idea(IdeaState::Done);
+        done.request_cancel();
+        assert!(!done.cancel_requested);
+
+        let mut running = idea(IdeaState::Executing(0));
+        running.request_cancel();
+        assert!(running.cancel_requested);
+        assert_eq!(running.state, IdeaState::Executing(0));
+    }
+
+    #[test]
+    fn cancelled_ideas_clean_up_and_remove_their_step() {
+        let mut world = World::new();
+        let mut schedule = Schedule::new();
+        schedule.add_system(assign_step_components);
+
+        let ant = world
+            .spawn((
+                idea(IdeaState::Prepare(0)),
+                Path::None,
+                Scent(Some(PheromoneChannel::Home)),
+            ))
+            .id();
+        schedule.run(&mut world);
+        assert!(world.get::<SetPathToQueenAction2>(ant).is_some());
+
+        // The action sets its path, then the ant is interrupted.
+        world
+            .get_mut::<Path>(ant)
+            .unwrap()
+            .set_target(SideIPos::new(0, 0));
+        world.get_mut::<Idea>(ant).unwrap().request_cancel();
+        schedule.run(&mut world);
+
+        let idea = world.get::<Idea>(ant).unwrap();
+        assert_eq!(idea.state, IdeaState::Aborted);
+        assert!(world.get::<SetPathToQueenAction2>(ant).is_none());
+        assert!(world.get::<WaitAction2>(ant).is_none());
+        assert!(matches!(world.get::<Path>(ant), Some(Path::None)));
+        assert_eq!(world.get::<Scent>(ant).unwrap().0, None);
+    }
 }
@@ -0,0 +1,316 @@
+//! Tunnels that are dug too wide without anything holding up the ceiling can cave in.
+//!
+//! A ceiling is held up by rock or packed dirt. When a row of empty cells has too many unsupported
+//! cells in a row, the loose dirt above it can fall down and fill it back in. Any ant caught
+//! underneath is crushed.
+//!
+//! Only rows near cells that were dug or filled are checked, so a map stays as it was generated
+//! until something changes it.
+
+use crate::game::ants::AntType;
+use crate::game::map::{CellContent, SideMapPosToEntities, UpdateTileDirtAmountEvent};
+use crate::game::positions::SideIPos;
+use crate::game::time::GameTime;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use std::time::Duration;
+
+#[derive(Resource, Debug, Clone)]
+pub struct StabilitySettings {
+    /// How many empty cells in a row can be under a loose ceiling before it might collapse.
+    pub max_unsupported_span: usize,
+
+    /// Dirt with at least this much in it holds up a ceiling like rock does. Scaled by the
+    /// strength of the material, so clay holds with less and sand never does.
+    pub packed_dirt: u8,
+
+    /// Chance of a span collapsing on each check, for every cell it is over the limit. 0 - 1.
+    pub collapse_chance: f32,
+
+    /// How much game time between each check.
+    pub check_interval: Duration,
+}
+
+impl Default for StabilitySettings {
+    fn default() -> Self {
+        Self {
+            max_unsupported_span: 3,
+            packed_dirt: 200,
+            collapse_chance: 0.05,
+            check_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl StabilitySettings {
+    fn holds_up_ceiling(&self, cell: &CellContent) -> bool {
+        let strength = cell
+            .material()
+            .map(|material| material.strength())
+            .unwrap_or(0.0);
+        cell.is_rock() || cell.amount_left() as f32 * strength >= self.packed_dirt as f32
+    }
+}
+
+/// Cells to check next time: ones around changes, and spans that haven't collapsed yet.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct StabilityWatch(HashSet<SideIPos>);
+
+/// Rows of underground empty cells through any of `near` where too many of them in a row have a
+/// loose ceiling.
+///
+/// Cells outside of the map count as solid.
+pub fn unsupported_spans(
+    near: impl IntoIterator<Item = SideIPos>,
+    cell_at: impl Fn(SideIPos) -> Option<CellContent>,
+    settings: &StabilitySettings,
+) -> Vec<Vec<SideIPos>> {
+    let is_open = |pos: SideIPos| {
+        cell_at(pos)
+            .map(|cell| cell.is_empty() && cell.is_underground())
+            .unwrap_or(false)
+    };
+
+    let mut seen = HashSet::new();
+    let mut spans = Vec::new();
+
+    for pos in near {
+        if seen.contains(&pos) || !is_open(pos) {
+            continue;
+        }
+
+        // Find the start of the row of open cells this one is in.
+        let mut x = pos.x;
+        while is_open(SideIPos::new(x - 1, pos.y)) {
+            x -= 1;
+        }
+
+        let mut current: Vec<SideIPos> = Vec::new();
+        loop {
+            let pos = SideIPos::new(x, pos.y);
+            let open = is_open(pos);
+            let supported = !open
+                || cell_at(SideIPos::new(pos.x, pos.y + 1))
+                    .map(|above| settings.holds_up_ceiling(&above))
+                    .unwrap_or(true);
+
+            if supported {
+                if current.len() > settings.max_unsupported_span {
+                    spans.push(current.clone());
+                }
+                current.clear();
+            } else {
+                current.push(pos);
+            }
+
+            if !open {
+                break;
+            }
+            seen.insert(pos);
+            x += 1;
+        }
+    }
+
+    spans
+}
+
+/// Watch the cells that were dug or filled, and the ones below them whose ceiling changed.
+pub fn watch_changed_cells(
+    mut watch: ResMut<StabilityWatch>,
+    positions: Query<&SideIPos>,
+    mut update_tile_reader: EventReader<UpdateTileDirtAmountEvent>,
+) {
+    for UpdateTileDirtAmountEvent(entity) in update_tile_reader.iter() {
+        let Ok(pos) = positions.get(*entity) else {
+            continue;
+        };
+
+        watch.insert(*pos);
+        watch.insert(SideIPos::new(pos.x, pos.y - 1));
+    }
+}
+
+/// Every so often, check for unsupported spans and maybe drop the ceiling into them.
+pub fn collapse_unstable_tunnels(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    settings: Res<StabilitySettings>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    mut watch: ResMut<StabilityWatch>,
+    mut since_last_check: Local<Duration>,
+    mut cells: Query<(&SideIPos, &mut CellContent)>,
+    ants: Query<(Entity, &Transform), With<AntType>>,
+    mut update_tile_rendering_writer: EventWriter<UpdateTileDirtAmountEvent>,
+) {
+    // Uses the delta so nothing collapses while the game is paused.
+    *since_last_check += time.delta();
+    if *since_last_check < settings.check_interval {
+        return;
+    }
+    *since_last_check = Duration::ZERO;
+
+    if watch.is_empty() {
+        return;
+    }
+
+    let cell_at = |pos: SideIPos| {
+        let entity = side_map_pos_to_entities.get(&pos)?;
+        cells.get(*entity).ok().map(|(_, cell)| *cell)
+    };
+    let spans = unsupported_spans(watch.drain(), cell_at, &settings);
+
+    let mut filled = HashSet::new();
+
+    for span in spans {
+        let over_limit = (span.len() - settings.max_unsupported_span) as f32;
+        if rand::random::<f32>() >= settings.collapse_chance * over_limit {
+            // Still unsupported, so try again next time.
+            watch.extend(span);
+            continue;
+        }
+
+        info!(?span, "Tunnel collapsed");
+
+        for pos in span {
+            let above = SideIPos::new(pos.x, pos.y + 1);
+            let (Some(above_entity), Some(entity)) = (
+                side_map_pos_to_entities.get(&above),
+                side_map_pos_to_entities.get(&pos),
+            ) else {
+                continue;
+            };
+
+            let Ok((_, mut above_cell)) = cells.get_mut(*above_entity) else {
+                continue;
+            };
+
+            // The loose ceiling falls down, leaving a hole where it was.
+            let material = above_cell.material().unwrap_or_default();
+            let fallen = above_cell.dig(u8::MAX);
+            if fallen == 0 {
+                continue;
+            }
+
+            let Ok((_, mut cell)) = cells.get_mut(*entity) else {
+                continue;
+            };
+            cell.add_dirt(material, fallen);
+
+            update_tile_rendering_writer.send(UpdateTileDirtAmountEvent(*above_entity));
+            update_tile_rendering_writer.send(UpdateTileDirtAmountEvent(*entity));
+            filled.insert(pos);
+        }
+    }
+
+    if filled.is_empty() {
+        return;
+    }
+
+    for (entity, transform) in &ants {
+        if filled.contains(&SideIPos::from(transform)) {
+            info!(?entity, "Ant was crushed by a collapsing tunnel");
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map::CellMaterial;
+    use bevy::utils::HashMap;
+
+    /// `#` is packed dirt, `1` is loose dirt, `s` is packed sand, `c` is half packed clay and `_` is
+    /// an empty tunnel. The top row is y = 0.
+    fn cells(rows: &[&str]) -> HashMap<SideIPos, CellContent> {
+        let mut cells = HashMap::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    '#' => CellContent::dirt(255),
+                    '1' => CellContent::dirt(10),
+                    's' => CellContent::dirt_of(CellMaterial::Sand, 255),
+                    'c' => CellContent::dirt_of(CellMaterial::Clay, 128),
+                    '_' => CellContent::empty_underground(),
+                    _ => unreachable!(),
+                };
+                cells.insert(SideIPos::new(x as i32, -(y as i32)), cell);
+            }
+        }
+        cells
+    }
+
+    /// Every unsupported span in the cells.
+    fn spans(cells: &HashMap<SideIPos, CellContent>) -> Vec<Vec<SideIPos>> {
+        unsupported_spans(
+            cells.keys().copied(),
+            |pos| cells.get(&pos).copied(),
+            &StabilitySettings::default(),
+        )
+    }
+
+    #[test]
+    fn packed_ceiling_holds() {
+        let cells = cells(&["#########", "#_______#", "#########"]);
+        assert!(spans(&cells).is_empty());
+    }
+
+    #[test]
+    fn wide_loose_ceiling_is_unsupported() {
+        let cells = cells(&["#1111111#", "#_______#", "#########"]);
+        let spans = spans(&cells);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].len(), 7);
+        assert_eq!(spans[0][0], SideIPos::new(1, -1));
+    }
+
+    #[test]
+    fn narrow_loose_ceiling_holds() {
+        let cells = cells(&["#111#", "#___#", "#####"]);
+        assert!(spans(&cells).is_empty());
+    }
+
+    #[test]
+    fn packed_sand_is_unsupported() {
+        let cells = cells(&["#sssssss#", "#_______#", "#########"]);
+        assert_eq!(spans(&cells).len(), 1);
+    }
+
+    #[test]
+    fn half_packed_clay_holds_a_wide_chamber() {
+        let cells = cells(&["#ccccccc#", "#_______#", "#########"]);
+        assert!(spans(&cells).is_empty());
+    }
+
+    #[test]
+    fn packed_pillar_splits_a_span() {
+        let cells = cells(&["#111#111#", "#_______#", "#########"]);
+        assert!(spans(&cells).is_empty());
+    }
+
+    #[test]
+    fn only_rows_near_changes_are_checked() {
+        let cells = cells(&[
+            "#1111111#",
+            "#_______#",
+            "#########",
+            "#1111111#",
+            "#_______#",
+            "#########",
+        ]);
+        let cell_at = |pos| cells.get(&pos).copied();
+        let settings = StabilitySettings::default();
+
+        let spans = unsupported_spans([SideIPos::new(4, -4)], cell_at, &settings);
+        assert_eq!(spans.len(), 1);
+        assert!(spans[0].iter().all(|pos| pos.y == -4));
+
+        // The walls and the ceiling aren't open, so there's nothing to check there.
+        let spans = unsupported_spans(
+            [SideIPos::new(0, -1), SideIPos::new(4, 0)],
+            cell_at,
+            &settings,
+        );
+        assert!(spans.is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! How crawlers look while they walk, on top of the cell-to-cell movement in
+//! [crate::game::pathfinding].
+//!
+//! In open cells an ant cuts straight to a later step of its path when nothing is in the way,
+//! instead of walking every grid corner. Each ant also walks a little to one side of the middle of
+//! a tunnel, so a line of ants doesn't all follow the same line.
+//!
+//! Sprites turn to keep their feet on whatever surface they're walking on: upright on floors,
+//! sideways on walls and upside down on ceilings. They flip to face the way they're going.
+
+use crate::game::map::{CellContent, SideMapPosToEntities, SIDE_CELL_SIZE};
+use crate::game::movement::{can_stand_in, is_solid};
+use crate::game::positions::SideIPos;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use std::f32::consts::FRAC_PI_2;
+
+/// How many steps ahead an ant looks for a straight line to walk.
+pub const MAX_SHORTCUT_STEPS: usize = 4;
+
+/// How far to either side of the middle of a tunnel an ant can walk, in cells.
+const MAX_LATERAL_OFFSET: f32 = 0.15;
+
+/// Which way a crawler is going and which way is up for it, in world space.
+#[derive(Component, Debug)]
+pub struct Heading {
+    pub direction: Vec2,
+    pub up: Vec2,
+    last_position: Option<Vec2>,
+}
+
+impl Default for Heading {
+    fn default() -> Self {
+        Self {
+            direction: Vec2::X,
+            up: Vec2::Y,
+            last_position: None,
+        }
+    }
+}
+
+/// How far to the side of its path a crawler walks underground, in cells. Only moves the sprite.
+#[derive(Component, Debug, Default, Deref)]
+pub struct LateralOffset(f32);
+
+impl LateralOffset {
+    pub fn random() -> Self {
+        Self((rand::random::<f32>() * 2.0 - 1.0) * MAX_LATERAL_OFFSET)
+    }
+}
+
+/// The index of the furthest of the next steps that can be walked to in a straight line from
+/// `from`, through cells a creature can stand in. 0 is the next step.
+pub fn shortcut(from: SideIPos, steps: &[SideIPos], is_open: &impl Fn(SideIPos) -> bool) -> usize {
+    let mut furthest = 0;
+    for (index, step) in steps.iter().enumerate().take(MAX_SHORTCUT_STEPS + 1) {
+        if !is_open(*step) {
+            break;
+        }
+
+        if index > 0 && is_line_open(from, *step, is_open) {
+            furthest = index;
+        }
+    }
+    furthest
+}
+
+/// Whether a creature a cell wide can slide from one cell to another without touching a cell
+/// that isn't open.
+fn is_line_open(from: SideIPos, to: SideIPos, is_open: &impl Fn(SideIPos) -> bool) -> bool {
+    // Leave out the edges, so a creature can slide along a wall it's touching.
+    const EDGE: f32 = 0.01;
+
+    let (from, to) = (from.as_vec2(), to.as_vec2());
+    let samples = ((to - from).length() * 4.0).ceil() as i32;
+    (0..=samples).all(|sample| {
+        let corner = from.lerp(to, sample as f32 / samples.max(1) as f32);
+        let (min, max) = ((corner + EDGE).floor(), (corner + 1.0 - EDGE).floor());
+        (min.y as i32..=max.y as i32)
+            .all(|y| (min.x as i32..=max.x as i32).all(|x| is_open(SideIPos::new(x, y))))
+    })
+}
+
+/// Which way is up for a crawler in this cell: away from the floor, or the ceiling, or a wall.
+fn surface_up(pos: SideIPos, cell_at: &impl Fn(SideIPos) -> Option<CellContent>) -> Vec2 {
+    let solid = |dx: i32, dy: i32| is_solid(cell_at(SideIPos::new(pos.x + dx, pos.y + dy)));
+    if solid(0, -1) || !can_stand_in(pos, cell_at) {
+        Vec2::Y
+    } else if solid(0, 1) {
+        Vec2::NEG_Y
+    } else if solid(-1, 0) {
+        Vec2::X
+    } else if solid(1, 0) {
+        Vec2::NEG_X
+    } else {
+        Vec2::Y
+    }
+}
+
+/// The quarter turn that points the top of a sprite `up`.
+fn rotation_for(up: Vec2) -> Quat {
+    let quarter_turns = (-up.x).atan2(up.y) / FRAC_PI_2;
+    Quat::from_rotation_z(quarter_turns.round() * FRAC_PI_2)
+}
+
+/// The anchor that draws a sprite rotated by `rotation` over the cell its transform is in, moved by
+/// `offset` cells in world space.
+fn anchor_for(rotation: Quat, offset: Vec2) -> Vec2 {
+    let centre = Vec2::splat(0.5) + offset;
+    -(rotation.inverse() * centre.extend(0.0)).truncate()
+}
+
+/// Work out which way each crawler is going from how it moved, and which surface it's on.
+pub fn update_heading(
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    cells: Query<&CellContent>,
+    mut query: Query<(&Transform, &mut Heading)>,
+) {
+    let cell_at = |pos: SideIPos| {
+        let entity = side_map_pos_to_entities.get(&pos)?;
+        cells.get(*entity).ok().copied()
+    };
+
+    for (transform, mut heading) in &mut query {
+        let position = transform.translation.truncate();
+        if heading.last_position == Some(position) {
+            continue;
+        }
+
+        if let Some(last_position) = heading.last_position {
+            heading.direction = (position - last_position).normalize_or_zero();
+        }
+        heading.last_position = Some(position);
+
+        // The cell the middle of the sprite is in.
+        let centre =
+            SideIPos::from(&(transform.translation + Vec3::splat(SIDE_CELL_SIZE as f32 / 2.0)));
+        heading.up = surface_up(centre, &cell_at);
+    }
+}
+
+/// Turn and flip each crawler's sprite to match its heading, keeping it drawn over its cell.
+pub fn orient_sprites(
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    cells: Query<&CellContent>,
+    mut query: Query<
+        (
+            &Heading,
+            &LateralOffset,
+            &mut Transform,
+            &mut TextureAtlasSprite,
+        ),
+        Changed<Heading>,
+    >,
+) {
+    for (heading, offset, mut transform, mut sprite) in &mut query {
+        let rotation = rotation_for(heading.up);
+
+        let pos = SideIPos::from(&transform.translation);
+        let is_underground = side_map_pos_to_entities
+            .get(&pos)
+            .and_then(|entity| cells.get(*entity).ok())
+            .map(|cell| cell.is_underground())
+            .unwrap_or(false);
+        let offset = if is_underground {
+            heading.up * **offset
+        } else {
+            Vec2::ZERO
+        };
+
+        let local_direction = (rotation.inverse() * heading.direction.extend(0.0)).truncate();
+        if local_direction.x.abs() > f32::EPSILON {
+            sprite.flip_x = local_direction.x < 0.0;
+        }
+
+        transform.rotation = rotation;
+        sprite.anchor = Anchor::Custom(anchor_for(rotation, offset));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts_only_cut_through_open_cells() {
+        // A chamber from y = -4 to -1, with a pillar at (2, -2).
+        let is_open = |pos: SideIPos| {
+            (-4..=-1).contains(&pos.y) && (0..=5).contains(&pos.x) && pos != SideIPos::new(2, -2)
+        };
+
+        let along_floor: Vec<SideIPos> = (1..=5).map(|x| SideIPos::new(x, -4)).collect();
+        assert_eq!(shortcut(SideIPos::new(0, -4), &along_floor, &is_open), 4);
+
+        // The last step is behind the pillar.
+        let up_a_step =
+            [(1, -4), (1, -3), (2, -3), (3, -3), (3, -2)].map(|(x, y)| SideIPos::new(x, y));
+        assert_eq!(shortcut(SideIPos::new(0, -4), &up_a_step, &is_open), 3);
+
+        let into_pillar = [(1, -2), (2, -2), (3, -2)].map(|(x, y)| SideIPos::new(x, y));
+        assert_eq!(shortcut(SideIPos::new(0, -2), &into_pillar, &is_open), 0);
+    }
+
+    #[test]
+    fn turned_sprites_stay_over_their_cell() {
+        for up in [Vec2::Y, Vec2::X, Vec2::NEG_Y, Vec2::NEG_X] {
+            let rotation = rotation_for(up);
+            assert!((rotation * Vec3::Y).truncate().abs_diff_eq(up, 1e-5));
+
+            // Where the middle of the sprite ends up, relative to the transform, in cells.
+            let centre = rotation * (-anchor_for(rotation, Vec2::ZERO)).extend(0.0);
+            assert!(centre.truncate().abs_diff_eq(Vec2::splat(0.5), 1e-5));
+        }
+
+        assert_eq!(anchor_for(Quat::IDENTITY, Vec2::ZERO), Vec2::splat(-0.5));
+    }
+}
@@ -0,0 +1,260 @@
+//! Renders the side map as a few meshes per chunk instead of a sprite per cell.
+//!
+//! Each chunk has one mesh for each texture in it, plus a layer on top for cells with food in them.
+//! Materials share textures and are told apart by the vertex colours.
+//! Cells only hold their [CellContent], and when something changes the chunk is marked dirty and
+//! all of its meshes are rebuilt in [CoreSet::PostUpdate], after the game systems have run.
+
+use crate::game::chunks::{chunk_min, chunk_of, CHUNK_SIZE};
+use crate::game::food::FoodState;
+use crate::game::map::{
+    CellContent, SideMapPosToEntities, TileNeedsFoodRenderingUpdate, UpdateTileDirtAmountEvent,
+    SIDE_CELL_SIZE,
+};
+use crate::game::plugin::{DIRT_Z, FOOD_Z};
+use crate::game::positions::SideIPos;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::utils::{HashMap, HashSet};
+
+const FOOD_TEXTURE: &str = "food/food.png";
+
+#[derive(Resource, Default)]
+pub struct Tilemap {
+    /// The mesh entities for each chunk, so they can be replaced when it's rebuilt.
+    layers: HashMap<SideIPos, Vec<Entity>>,
+
+    dirty: HashSet<SideIPos>,
+
+    /// One material per texture, shared by all the chunks.
+    materials: HashMap<&'static str, Handle<ColorMaterial>>,
+}
+
+/// All the cells in a chunk with the same texture.
+#[derive(Debug)]
+pub struct TileLayer {
+    pub texture_path: &'static str,
+    pub z: f32,
+
+    /// Cell positions relative to the bottom left of the chunk.
+    pub tiles: Vec<IVec2>,
+
+    /// The colour of each tile, multiplied with the texture.
+    pub tints: Vec<Color>,
+}
+
+/// Work out the layers of a chunk. `cell_at` and `has_food` are given world cell positions.
+pub fn chunk_layers(
+    chunk: SideIPos,
+    cell_at: impl Fn(SideIPos) -> Option<CellContent>,
+    has_food: impl Fn(SideIPos) -> bool,
+) -> Vec<TileLayer> {
+    let min = chunk_min(chunk);
+    let mut layers: Vec<TileLayer> = Vec::new();
+    let mut add = |texture_path: &'static str, z: f32, local: IVec2, tint: Color| match layers
+        .iter_mut()
+        .find(|layer| layer.texture_path == texture_path)
+    {
+        Some(layer) => {
+            layer.tiles.push(local);
+            layer.tints.push(tint);
+        }
+        None => layers.push(TileLayer {
+            texture_path,
+            z,
+            tiles: vec![local],
+            tints: vec![tint],
+        }),
+    };
+
+    for y in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            let local = IVec2::new(x, y);
+            let pos = SideIPos::new(min.x + x, min.y + y);
+
+            if let Some(cell) = cell_at(pos) {
+                if let Some(texture_path) = cell.texture_path() {
+                    add(texture_path, DIRT_Z, local, cell.tint());
+                }
+            }
+
+            if has_food(pos) {
+                add(FOOD_TEXTURE, FOOD_Z, local, Color::WHITE);
+            }
+        }
+    }
+
+    layers
+}
+
+/// A quad for each tile, with the bottom left of the chunk at the origin.
+pub fn layer_mesh(layer: &TileLayer) -> Mesh {
+    let tiles = &layer.tiles;
+    let size = SIDE_CELL_SIZE as f32;
+    let mut positions = Vec::with_capacity(tiles.len() * 4);
+    let mut normals = Vec::with_capacity(tiles.len() * 4);
+    let mut uvs = Vec::with_capacity(tiles.len() * 4);
+    let mut colors = Vec::with_capacity(tiles.len() * 4);
+    let mut indices = Vec::with_capacity(tiles.len() * 6);
+
+    for (tile, tint) in tiles.iter().zip(&layer.tints) {
+        let x = tile.x as f32 * size;
+        let y = tile.y as f32 * size;
+        let first = positions.len() as u32;
+
+        positions.extend([
+            [x, y, 0.0],
+            [x + size, y, 0.0],
+            [x + size, y + size, 0.0],
+            [x, y + size, 0.0],
+        ]);
+        normals.extend([[0.0, 0.0, 1.0]; 4]);
+        // Textures have y going down.
+        uvs.extend([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+        colors.extend([tint.as_linear_rgba_f32(); 4]);
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Mark the chunks of any cells that changed, were spawned or had their food changed.
+pub fn mark_dirty_chunks(
+    mut tilemap: ResMut<Tilemap>,
+    positions: Query<&SideIPos>,
+    food_changed: Query<&SideIPos, Changed<TileNeedsFoodRenderingUpdate>>,
+    mut update_tile_rendering_reader: EventReader<UpdateTileDirtAmountEvent>,
+) {
+    for UpdateTileDirtAmountEvent(entity) in update_tile_rendering_reader.iter() {
+        let Ok(pos) = positions.get(*entity) else {
+            warn!(?entity, "Could not find SideIPos for entity");
+            continue;
+        };
+
+        tilemap.dirty.insert(chunk_of(*pos));
+    }
+
+    for pos in &food_changed {
+        tilemap.dirty.insert(chunk_of(*pos));
+    }
+}
+
+pub fn rebuild_dirty_chunks(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    food_state: Res<FoodState>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    cells: Query<&CellContent>,
+    mut tilemap: ResMut<Tilemap>,
+) {
+    if tilemap.dirty.is_empty() {
+        return;
+    }
+
+    let tilemap = &mut *tilemap;
+    for chunk in tilemap.dirty.drain() {
+        for entity in tilemap.layers.remove(&chunk).unwrap_or_default() {
+            commands.entity(entity).despawn();
+        }
+
+        let layers = chunk_layers(
+            chunk,
+            |pos| {
+                let entity = side_map_pos_to_entities.get(&pos)?;
+                cells.get(*entity).ok().copied()
+            },
+            |pos| food_state.info_at_position(&pos).is_some(),
+        );
+
+        let min = chunk_min(chunk);
+        let mut entities = Vec::with_capacity(layers.len());
+        for layer in layers {
+            let material = tilemap
+                .materials
+                .entry(layer.texture_path)
+                .or_insert_with(|| {
+                    materials.add(ColorMaterial::from(asset_server.load(layer.texture_path)))
+                })
+                .clone();
+
+            let transform =
+                Transform::from_translation(SideIPos::new(min.x, min.y).to_world_vec3(layer.z));
+
+            let entity = commands
+                .spawn((
+                    MaterialMesh2dBundle {
+                        mesh: meshes.add(layer_mesh(&layer)).into(),
+                        material,
+                        transform,
+                        ..Default::default()
+                    },
+                    Name::new(format!("Chunk {:?} {}", chunk, layer.texture_path)),
+                ))
+                .id();
+            entities.push(entity);
+        }
+
+        tilemap.layers.insert(chunk, entities);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map_generator::{MapGenerator, MapGeneratorParams, MapSeed};
+
+    #[test]
+    fn stress_map_has_a_few_meshes_per_chunk() {
+        let params = MapGeneratorParams::stress();
+        let grid = MapGenerator::new(MapSeed::new(1), params.clone()).generate();
+        assert!(grid.len() >= 256 * 256);
+
+        let chunks: HashSet<SideIPos> = grid.iter().map(|(pos, _)| chunk_of(pos)).collect();
+        let mut layer_count = 0;
+        let mut tile_count = 0;
+
+        for chunk in &chunks {
+            let layers = chunk_layers(*chunk, |pos| grid.get(pos).copied(), |_| false);
+            layer_count += layers.len();
+            tile_count += layers.iter().map(|layer| layer.tiles.len()).sum::<usize>();
+
+            for layer in &layers {
+                let mesh = layer_mesh(layer);
+                assert_eq!(mesh.count_vertices(), layer.tiles.len() * 4);
+            }
+        }
+
+        let textured = grid
+            .iter()
+            .filter(|(_, cell)| cell.texture_path().is_some())
+            .count();
+        assert_eq!(tile_count, textured);
+
+        // Dirt can be full or half, plus rock.
+        assert!(layer_count <= chunks.len() * 3, "{layer_count} layers");
+    }
+
+    #[test]
+    fn food_is_a_layer_on_top() {
+        let chunk = SideIPos::new(0, 0);
+        let layers = chunk_layers(
+            chunk,
+            |_| Some(CellContent::empty_air()),
+            |pos| pos == SideIPos::new(3, 4),
+        );
+
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].texture_path, FOOD_TEXTURE);
+        assert_eq!(layers[0].z, FOOD_Z);
+        assert_eq!(layers[0].tiles, vec![IVec2::new(3, 4)]);
+    }
+}
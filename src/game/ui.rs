@@ -1,4 +1,6 @@
 use crate::game::ants::AntType;
+use crate::game::calendar::Calendar;
+use crate::game::eggs::{BroodSettings, Egg};
 use crate::game::hunger::Hunger;
 use crate::game::plugin::{ActionMode, PlayerState, QueensChoice};
 use crate::game::queen::Queen;
@@ -31,7 +33,10 @@ pub fn control(
     mut contexts: EguiContexts,
     mut player_state: ResMut<PlayerState>,
     mut is_hovering_over_ui: ResMut<IsHoveringOverUi>,
+    calendar: Res<Calendar>,
     queen: Query<(&Hunger, &Queen)>,
+    brood_settings: Res<BroodSettings>,
+    eggs: Query<&Egg>,
 ) {
     let PlayerState {
         action_mode,
@@ -40,6 +45,10 @@ pub fn control(
     } = &mut *player_state;
 
     let (queen_hunger, queen_info) = queen.single();
+    let untended_eggs = eggs
+        .iter()
+        .filter(|egg| egg.is_untended(&brood_settings))
+        .count();
 
     let response = egui::TopBottomPanel::bottom("top_panel")
         .exact_height(80f32)
@@ -55,21 +64,36 @@ pub fn control(
                     // let rate_per_hour = 3600f32 / queen_info.egg_progress_speed;
                     // ui.label(format!("Egg: {:.1}% ({:.02} per hour)", queen_info.egg_progress * 100f32, rate_per_hour));
                     let seconds_per_egg = 1f32 / queen_info.egg_progress_speed;
-                    ui.label(format!("Egg: {:.1}% ({:.0}s per egg)", queen_info.egg_progress * 100f32, seconds_per_egg));
+                    ui.label(format!(
+                        "Egg: {:.1}% ({:.0}s per egg), {} untended",
+                        queen_info.egg_progress * 100f32,
+                        seconds_per_egg,
+                        untended_eggs
+                    ));
+                });
+
+                ui.separator();
+
+                ui.vertical(|ui| {
+                    ui.heading("Time");
+                    ui.label(calendar.to_string());
+                    if calendar.is_night() {
+                        ui.label("Night");
+                    }
+                });
+
+                ui.separator();
+
+                ui.vertical(|ui| {
+                    ui.heading("Actions");
+                    ui.horizontal_centered(|ui| {
+                        ui.selectable_value(action_mode, ActionMode::Select, "Select");
+                        ui.selectable_value(action_mode, ActionMode::Dig, "Dig");
+                    });
                 });
 
                 ui.separator();
 
-                // ui.vertical(|ui| {
-                //     ui.heading("Actions");
-                //     ui.horizontal_centered(|ui| {
-                //         ui.selectable_value(action_mode, ActionMode::Select, "Select");
-                //         ui.selectable_value(action_mode, ActionMode::Dig, "Dig");
-                //     });
-                // });
-                //
-                // ui.separator();
-                //
                 ui.vertical(|ui| {
                     ui.heading("Next Ant Type");
                     ui.horizontal_centered(|ui| {
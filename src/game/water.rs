@@ -0,0 +1,503 @@
+//! Rain on the surface that flows down into the tunnels.
+//!
+//! Water is a simple cellular automaton on top of the side map. Each empty cell holds up to
+//! [MAX_WATER]. Every step, water falls into the cell below if it can, then spreads out to the
+//! sides, so it ends up pooling in the lowest chambers. It slowly soaks into neighbouring dirt,
+//! dries up on the surface and never goes through rock.
+
+use crate::game::eggs::Egg;
+use crate::game::food::FoodState;
+use crate::game::map::{
+    CellContent, SideMapPosToEntities, TileNeedsFoodRenderingUpdate, UpdateTileDirtAmountEvent,
+    SIDE_CELL_SIZE,
+};
+use crate::game::plugin::WATER_Z;
+use crate::game::positions::SideIPos;
+use crate::game::setup::sprite;
+use crate::game::time::GameTime;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use rand::Rng;
+use std::time::Duration;
+
+/// The most water a cell can hold.
+pub const MAX_WATER: u8 = 100;
+
+/// Cells with at least this much water are flooded. Ants avoid them and eggs and food in them get
+/// damaged.
+pub const FLOODED_WATER: u8 = 50;
+
+/// Added to every graph edge touching a flooded cell. Ants will go a long way around, but can still
+/// get out if they're caught in one.
+const FLOODED_WEIGHT: u64 = 1000;
+
+#[derive(Resource, Debug, Clone)]
+pub struct WaterSettings {
+    /// How much game time between each step of the automaton.
+    pub step_interval: Duration,
+
+    /// Chance of rain starting every second it isn't raining. 0 - 1.
+    pub rain_chance: f32,
+
+    /// How long it rains for, in seconds.
+    pub rain_duration: (f32, f32),
+
+    /// Water added to each column on the surface every step while it's raining.
+    pub rain_per_step: u8,
+
+    /// Water lost to each neighbouring dirt cell every step.
+    pub soak_per_step: u8,
+
+    /// Water lost every step while it's sitting on the surface.
+    pub evaporate_per_step: u8,
+
+    /// Egg health lost every second while flooded. Eggs start with 1.
+    pub egg_damage_per_second: f32,
+
+    /// Food washed away every second while flooded, for each type of food in the cell.
+    pub food_damage_per_second: f32,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        Self {
+            step_interval: Duration::from_millis(250),
+            rain_chance: 1.0 / 120.0,
+            rain_duration: (10.0, 30.0),
+            rain_per_step: 5,
+            soak_per_step: 1,
+            evaporate_per_step: 2,
+            egg_damage_per_second: 0.1,
+            food_damage_per_second: 0.5,
+        }
+    }
+}
+
+/// How much longer it will rain for.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct Rain(Duration);
+
+impl Rain {
+    pub fn is_raining(&self) -> bool {
+        !self.0.is_zero()
+    }
+}
+
+/// Water in each cell. Cells without water aren't stored.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct WaterLevels(HashMap<SideIPos, u8>);
+
+impl WaterLevels {
+    pub fn level(&self, pos: &SideIPos) -> u8 {
+        self.0.get(pos).copied().unwrap_or(0)
+    }
+
+    pub fn is_flooded(&self, pos: &SideIPos) -> bool {
+        self.level(pos) >= FLOODED_WATER
+    }
+
+    /// Added to the weight of the graph edges around this cell.
+    pub fn extra_weight(&self, pos: &SideIPos) -> u64 {
+        if self.is_flooded(pos) {
+            FLOODED_WEIGHT
+        } else {
+            0
+        }
+    }
+
+    fn set(&mut self, pos: SideIPos, level: u8) {
+        if level == 0 {
+            self.0.remove(&pos);
+        } else {
+            self.0.insert(pos, level);
+        }
+    }
+}
+
+/// Water can only be in empty cells that are on the map.
+fn is_open(cell_at: &impl Fn(SideIPos) -> Option<CellContent>, pos: SideIPos) -> bool {
+    cell_at(pos).map(|cell| cell.is_empty()).unwrap_or(false)
+}
+
+/// Add rain to the lowest open cell at or above y = 0 in each column, so it lands on top of any
+/// mounds.
+pub fn rain(
+    columns: impl IntoIterator<Item = i32>,
+    cell_at: impl Fn(SideIPos) -> Option<CellContent>,
+    water: &mut WaterLevels,
+    amount: u8,
+) {
+    for x in columns {
+        let mut pos = SideIPos::new(x, 0);
+        while cell_at(pos).is_some() && !is_open(&cell_at, pos) {
+            pos = SideIPos::new(x, pos.y + 1);
+        }
+
+        if !is_open(&cell_at, pos) {
+            continue;
+        }
+
+        let level = water.level(&pos).saturating_add(amount).min(MAX_WATER);
+        water.set(pos, level);
+    }
+}
+
+/// One step of the automaton. Only cells with water and their neighbours are looked at. Returns the
+/// cells where the water level changed.
+pub fn step_water(
+    cell_at: impl Fn(SideIPos) -> Option<CellContent>,
+    water: &mut WaterLevels,
+    settings: &WaterSettings,
+) -> HashSet<SideIPos> {
+    let before = water.0.clone();
+
+    // Bottom up so water only falls one cell per step.
+    let mut positions: Vec<SideIPos> = water.keys().copied().collect();
+    positions.sort_by_key(|pos| (pos.y, pos.x));
+
+    for pos in positions {
+        let mut amount = water.level(&pos);
+        if amount == 0 {
+            continue;
+        }
+
+        // Dirt was dropped on top of the water, or it's off the map.
+        let Some(cell) = cell_at(pos).filter(|cell| cell.is_empty()) else {
+            water.set(pos, 0);
+            continue;
+        };
+
+        let dirt_sides = pos
+            .sides()
+            .iter()
+            .filter(|side| {
+                cell_at(**side)
+                    .map(|cell| !cell.is_empty() && !cell.is_rock())
+                    .unwrap_or(false)
+            })
+            .count() as u8;
+        amount = amount.saturating_sub(settings.soak_per_step.saturating_mul(dirt_sides));
+
+        let below = SideIPos::new(pos.x, pos.y - 1);
+        if is_open(&cell_at, below) {
+            let below_amount = water.level(&below);
+            let flow = amount.min(MAX_WATER - below_amount);
+            amount -= flow;
+            water.set(below, below_amount + flow);
+        } else if !cell.is_underground() {
+            amount = amount.saturating_sub(settings.evaporate_per_step);
+        }
+
+        water.set(pos, amount);
+    }
+
+    // Spread out to the sides using the levels from before any of it spread, so the water doesn't
+    // drift one way depending on which cells were stepped first. A cell gives at most a third of
+    // the difference to each side, so it never runs dry and its neighbours never overflow.
+    let levels = water.0.clone();
+    for (pos, amount) in &levels {
+        for side in [
+            SideIPos::new(pos.x - 1, pos.y),
+            SideIPos::new(pos.x + 1, pos.y),
+        ] {
+            if !is_open(&cell_at, side) {
+                continue;
+            }
+
+            let side_amount = levels.get(&side).copied().unwrap_or(0);
+            if side_amount >= *amount {
+                continue;
+            }
+
+            let flow = (amount - side_amount) / 3;
+            water.set(*pos, water.level(pos) - flow);
+            water.set(side, water.level(&side) + flow);
+        }
+    }
+
+    before
+        .keys()
+        .chain(water.keys())
+        .filter(|pos| before.get(*pos) != water.get(*pos))
+        .copied()
+        .collect()
+}
+
+/// Start and stop the rain, and step the water.
+pub fn simulate_water(
+    time: Res<GameTime>,
+    settings: Res<WaterSettings>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    mut rain_left: ResMut<Rain>,
+    mut water: ResMut<WaterLevels>,
+    mut since_last_step: Local<Duration>,
+    mut surface_columns: Local<HashSet<i32>>,
+    added: Query<&SideIPos, Added<CellContent>>,
+    cells: Query<&CellContent>,
+    mut update_tile_writer: EventWriter<UpdateTileDirtAmountEvent>,
+) {
+    // Rain falls on every column that reaches the surface, including ones in new chunks.
+    surface_columns.extend(added.iter().filter(|pos| pos.y == 0).map(|pos| pos.x));
+
+    *since_last_step += time.delta();
+    if *since_last_step < settings.step_interval {
+        return;
+    }
+    *since_last_step = Duration::ZERO;
+
+    let step_seconds = settings.step_interval.as_secs_f32();
+    if rain_left.is_raining() {
+        **rain_left = rain_left.saturating_sub(settings.step_interval);
+        if !rain_left.is_raining() {
+            info!("The rain stopped");
+        }
+    } else if rand::random::<f32>() < settings.rain_chance * step_seconds {
+        let (min, max) = settings.rain_duration;
+        let duration = Duration::from_secs_f32(rand::thread_rng().gen_range(min..max));
+        info!(?duration, "It started raining");
+        **rain_left = duration;
+    }
+
+    if water.is_empty() && !rain_left.is_raining() {
+        return;
+    }
+
+    let cell_at = |pos: SideIPos| {
+        let entity = side_map_pos_to_entities.get(&pos)?;
+        cells.get(*entity).ok().copied()
+    };
+
+    let was_flooded: HashSet<SideIPos> = water
+        .iter()
+        .filter(|(_, level)| **level >= FLOODED_WATER)
+        .map(|(pos, _)| *pos)
+        .collect();
+
+    if rain_left.is_raining() {
+        let columns = surface_columns.iter().copied();
+        rain(columns, cell_at, &mut water, settings.rain_per_step);
+    }
+
+    let changed = step_water(cell_at, &mut water, &settings);
+
+    // Let the graph know so it can update the edge weights.
+    for pos in changed.union(&was_flooded) {
+        if was_flooded.contains(pos) == water.is_flooded(pos) {
+            continue;
+        }
+
+        if let Some(entity) = side_map_pos_to_entities.get(pos) {
+            update_tile_writer.send(UpdateTileDirtAmountEvent(*entity));
+        }
+    }
+}
+
+/// Drown eggs and wash away food sitting in flooded cells.
+pub fn damage_flooded_cells(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    settings: Res<WaterSettings>,
+    water: Res<WaterLevels>,
+    side_map_pos_to_entities: Res<SideMapPosToEntities>,
+    mut food_state: ResMut<FoodState>,
+    mut eggs: Query<(Entity, &mut Egg, &Transform)>,
+) {
+    let delta = time.delta_seconds();
+    if delta == 0f32 {
+        return;
+    }
+
+    for (entity, mut egg, transform) in &mut eggs {
+        if !water.is_flooded(&SideIPos::from(transform)) {
+            continue;
+        }
+
+        egg.health -= settings.egg_damage_per_second * delta;
+        if egg.health <= 0f32 {
+            info!(?entity, "Egg drowned");
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for (pos, _) in water.iter().filter(|(_, level)| **level >= FLOODED_WATER) {
+        let Some(food_cell) = food_state.food_position_cells.get_mut(pos) else {
+            continue;
+        };
+
+        for amount in food_cell.values_mut() {
+            *amount -= settings.food_damage_per_second * delta;
+        }
+        food_cell.retain(|_, amount| *amount > 0f32);
+
+        if food_cell.is_empty() {
+            info!(?pos, "Food was washed away");
+            food_state.food_position_cells.remove(pos);
+
+            if let Some(entity) = side_map_pos_to_entities.get(pos) {
+                commands
+                    .entity(*entity)
+                    .insert(TileNeedsFoodRenderingUpdate);
+            }
+        }
+    }
+}
+
+/// A translucent blue box in each wet cell, as tall as the water is deep.
+pub fn update_water_rendering(
+    mut commands: Commands,
+    water: Res<WaterLevels>,
+    mut water_sprites: Local<HashMap<SideIPos, Entity>>,
+) {
+    if !water.is_changed() {
+        return;
+    }
+
+    water_sprites.retain(|pos, entity| {
+        let keep = water.contains_key(pos);
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+
+    for (pos, level) in water.iter() {
+        let height = SIDE_CELL_SIZE as f32 * *level as f32 / MAX_WATER as f32;
+        let sprite = Sprite {
+            color: Color::rgba(0.2, 0.4, 1.0, 0.6),
+            custom_size: Some(Vec2::new(SIDE_CELL_SIZE as f32, height)),
+            ..sprite()
+        };
+
+        match water_sprites.get(pos) {
+            Some(entity) => {
+                commands.entity(*entity).insert(sprite);
+            }
+            None => {
+                let entity = commands
+                    .spawn((
+                        SpriteBundle {
+                            sprite,
+                            transform: pos.to_transform(WATER_Z),
+                            ..Default::default()
+                        },
+                        Name::new("Water"),
+                    ))
+                    .id();
+                water_sprites.insert(*pos, entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `.` is air, `_` is an empty tunnel, `9` is dirt and `#` is rock. The top row is y = 0.
+    fn cells(rows: &[&str]) -> HashMap<SideIPos, CellContent> {
+        let mut cells = HashMap::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    '.' => CellContent::empty_air(),
+                    '_' => CellContent::empty_underground(),
+                    '9' => CellContent::dirt(255),
+                    '#' => CellContent::rock(true),
+                    _ => unreachable!(),
+                };
+                cells.insert(SideIPos::new(x as i32, -(y as i32)), cell);
+            }
+        }
+        cells
+    }
+
+    fn cell_at(
+        cells: &HashMap<SideIPos, CellContent>,
+    ) -> impl Fn(SideIPos) -> Option<CellContent> + '_ {
+        |pos| cells.get(&pos).copied()
+    }
+
+    fn no_soaking() -> WaterSettings {
+        WaterSettings {
+            soak_per_step: 0,
+            evaporate_per_step: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn water_pools_in_the_lowest_chamber() {
+        let cells = cells(&["#_#", "#_#", "#_#", "###"]);
+        let mut water = WaterLevels::default();
+        water.set(SideIPos::new(1, 0), 30);
+
+        for _ in 0..5 {
+            step_water(cell_at(&cells), &mut water, &no_soaking());
+        }
+
+        assert_eq!(water.level(&SideIPos::new(1, -2)), 30);
+        assert_eq!(water.len(), 1);
+    }
+
+    #[test]
+    fn water_never_passes_through_rock() {
+        let cells = cells(&["___", "###", "___"]);
+        let mut water = WaterLevels::default();
+        water.set(SideIPos::new(1, 0), 90);
+
+        for _ in 0..20 {
+            step_water(cell_at(&cells), &mut water, &no_soaking());
+        }
+
+        assert!(water.keys().all(|pos| pos.y == 0));
+        assert_eq!(water.values().map(|level| *level as u32).sum::<u32>(), 90);
+    }
+
+    #[test]
+    fn water_soaks_into_dirt() {
+        let cells = cells(&["9_9", "999"]);
+        let mut water = WaterLevels::default();
+        water.set(SideIPos::new(1, 0), 6);
+
+        step_water(cell_at(&cells), &mut water, &WaterSettings::default());
+        assert_eq!(water.level(&SideIPos::new(1, 0)), 3);
+
+        step_water(cell_at(&cells), &mut water, &WaterSettings::default());
+        assert!(water.is_empty());
+    }
+
+    #[test]
+    fn rain_lands_on_top_of_mounds() {
+        let mut cells = cells(&["9.", "99"]);
+        cells.insert(SideIPos::new(0, 1), CellContent::empty_air());
+        cells.insert(SideIPos::new(1, 1), CellContent::empty_air());
+        let mut water = WaterLevels::default();
+
+        rain([0, 1], cell_at(&cells), &mut water, 5);
+
+        assert_eq!(water.level(&SideIPos::new(0, 1)), 5);
+        assert_eq!(water.level(&SideIPos::new(1, 0)), 5);
+        assert_eq!(water.len(), 2);
+    }
+
+    #[test]
+    fn water_spreads_evenly_to_both_sides() {
+        let cells = cells(&["#_______#", "#########"]);
+        let mut water = WaterLevels::default();
+        water.set(SideIPos::new(4, 0), 90);
+
+        for _ in 0..20 {
+            step_water(cell_at(&cells), &mut water, &no_soaking());
+
+            for offset in 1..=3 {
+                assert_eq!(
+                    water.level(&SideIPos::new(4 - offset, 0)),
+                    water.level(&SideIPos::new(4 + offset, 0)),
+                );
+            }
+        }
+
+        assert!(water.level(&SideIPos::new(1, 0)) > 0);
+        assert_eq!(water.values().map(|level| *level as u32).sum::<u32>(), 90);
+    }
+}
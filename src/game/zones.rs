@@ -15,6 +15,18 @@ impl Zones {
         self.cells.insert(position);
     }
 
+    pub fn contains(&self, position: &SideIPos) -> bool {
+        self.cells.contains(position)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SideIPos> {
+        self.cells.iter()
+    }
+
     pub fn random(&self) -> Option<SideIPos> {
         if self.cells.is_empty() {
             return None;
@@ -29,6 +41,14 @@ impl Zones {
 #[derive(Default, Debug, Deref, DerefMut)]
 pub struct FoodStorageZones(Zones);
 
+/// Where ants can dump the dirt they dig out, e.g. an old chamber to fill back in.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct SpoilZones(Zones);
+
+/// Where nurses keep the brood.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct NurseryZones(Zones);
+
 /// Grab some zone events, check if they exist and remove them if they do to accommodate different zone types.
 pub fn add_food_zones(
     mut commands: Commands,
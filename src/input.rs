@@ -67,6 +67,9 @@ pub enum InputAction {
     Speed3,
     Debug1,
     Debug2,
+    Debug3,
+    Debug4,
+    Debug5,
 }
 
 pub fn setup(mut commands: Commands) {
@@ -92,6 +95,9 @@ pub fn setup(mut commands: Commands) {
 
     keyboard_input_map.insert(KeyCode::F1, InputAction::Debug1);
     keyboard_input_map.insert(KeyCode::F2, InputAction::Debug2);
+    keyboard_input_map.insert(KeyCode::F4, InputAction::Debug3);
+    keyboard_input_map.insert(KeyCode::F5, InputAction::Debug4);
+    keyboard_input_map.insert(KeyCode::F6, InputAction::Debug5);
 
     mouse_button_input_map.insert(MouseButton::Left, InputAction::PrimaryAction);
     mouse_button_input_map.insert(MouseButton::Right, InputAction::SecondaryAction);
@@ -205,3 +211,30 @@ pub fn process_mouse_input(
         // info!("Mouse event: {:?}", event);
     }
 }
+
+/// Let go of any held mouse buttons. Mouse input isn't read while the mouse is over the UI, so a
+/// button released there would otherwise stay pressed.
+pub fn release_mouse_buttons(
+    mut input_states: ResMut<InputStates>,
+    mouse_button_input_map: Res<MouseButtonInputMap>,
+    mut input_action_writer: EventWriter<ActionEvent>,
+) {
+    for action in mouse_button_input_map.values() {
+        let Some(state) = input_states.get_mut(action) else {
+            continue;
+        };
+        if !state.is_pressed {
+            continue;
+        }
+
+        *state = InputState {
+            is_pressed: false,
+            just_pressed: false,
+            just_released: true,
+        };
+        input_action_writer.send(ActionEvent {
+            action: *action,
+            state: EventState::Released,
+        });
+    }
+}
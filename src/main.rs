@@ -70,6 +70,7 @@ fn main() -> Result<()> {
         (
             input::process_keyboard_input,
             input::process_mouse_input.run_if(game::not_using_ui),
+            input::release_mouse_buttons.run_if(not(game::not_using_ui)),
         )
             .in_set(game::InputSet::GetInput),
     );